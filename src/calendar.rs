@@ -0,0 +1,630 @@
+//! In-world calendars: the months, weekdays, eras and leap years of a campaign, and the
+//! conversion between dates and text. Every campaign has one calendar, see
+//! [`crate::schema::v1::Schema::calendar`].
+use crate::schema::{Error, Result};
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Month {
+    pub name: String,
+    pub days: u32,
+    /// Added to `days` in leap years
+    pub leap_days: u32,
+}
+
+/// A way of counting years, starting at `start_year`, which is year 1 of the era
+#[derive(Clone, Debug, PartialEq)]
+pub struct Era {
+    pub name: String,
+    pub start_year: i64,
+}
+
+/// Years divisible by `every` are leap years, except those divisible by `except`, unless they
+/// are divisible by `unless`. A rule of 0 is not applied, so the default has no leap years.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct LeapRule {
+    pub every: i64,
+    pub except: i64,
+    pub unless: i64,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Calendar {
+    pub name: String,
+    pub months: Vec<Month>,
+    pub weekdays: Vec<String>,
+    /// Sorted by start year
+    pub eras: Vec<Era>,
+    /// Name for the years before the first era, which are counted backwards like BC
+    pub before_eras: String,
+    pub leap: LeapRule,
+    /// Index into `weekdays` of the first day of year 1
+    pub first_weekday: usize,
+}
+
+/// A day in a [`Calendar`]. Years are absolute, not relative to an era, and `month` is an
+/// index into the calendar's months, so dates sort chronologically.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct CalendarDate {
+    pub year: i64,
+    pub month: u32,
+    /// Starts at 1
+    pub day: u32,
+}
+
+/// How far down a date is shown, from whole eras to single days
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Precision {
+    Era,
+    Year,
+    Month,
+    Day,
+}
+
+impl Precision {
+    pub const ALL: [Precision; 4] = [
+        Precision::Era,
+        Precision::Year,
+        Precision::Month,
+        Precision::Day,
+    ];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Precision::Era => "era",
+            Precision::Year => "year",
+            Precision::Month => "month",
+            Precision::Day => "day",
+        }
+    }
+}
+
+impl Calendar {
+    /// The calendar every campaign starts with
+    pub fn gregorian() -> Self {
+        let months = [
+            ("January", 31, 0),
+            ("February", 28, 1),
+            ("March", 31, 0),
+            ("April", 30, 0),
+            ("May", 31, 0),
+            ("June", 30, 0),
+            ("July", 31, 0),
+            ("August", 31, 0),
+            ("September", 30, 0),
+            ("October", 31, 0),
+            ("November", 30, 0),
+            ("December", 31, 0),
+        ];
+        let weekdays = [
+            "Monday",
+            "Tuesday",
+            "Wednesday",
+            "Thursday",
+            "Friday",
+            "Saturday",
+            "Sunday",
+        ];
+        Self {
+            name: "Gregorian".into(),
+            months: months_from(&months),
+            weekdays: weekdays.iter().map(|d| d.to_string()).collect(),
+            eras: vec![Era {
+                name: "AD".into(),
+                start_year: 1,
+            }],
+            before_eras: "BC".into(),
+            leap: LeapRule {
+                every: 4,
+                except: 100,
+                unless: 400,
+            },
+            first_weekday: 0,
+        }
+    }
+
+    /// Twelve months of three tendays, with festival days between them and Shieldmeet after
+    /// Midsummer every fourth year, as in the Forgotten Realms
+    pub fn harptos() -> Self {
+        let months = [
+            ("Hammer", 30, 0),
+            ("Midwinter", 1, 0),
+            ("Alturiak", 30, 0),
+            ("Ches", 30, 0),
+            ("Tarsakh", 30, 0),
+            ("Greengrass", 1, 0),
+            ("Mirtul", 30, 0),
+            ("Kythorn", 30, 0),
+            ("Flamerule", 30, 0),
+            ("Midsummer", 1, 1),
+            ("Eleasis", 30, 0),
+            ("Eleint", 30, 0),
+            ("Highharvestide", 1, 0),
+            ("Marpenoth", 30, 0),
+            ("Uktar", 30, 0),
+            ("Feast of the Moon", 1, 0),
+            ("Nightal", 30, 0),
+        ];
+        Self {
+            name: "Harptos".into(),
+            months: months_from(&months),
+            weekdays: (1..=10).map(|i| format!("Day {i} of the tenday")).collect(),
+            eras: vec![Era {
+                name: "DR".into(),
+                start_year: 1,
+            }],
+            before_eras: "".into(),
+            leap: LeapRule {
+                every: 4,
+                except: 0,
+                unless: 0,
+            },
+            first_weekday: 0,
+        }
+    }
+
+    pub fn presets() -> Vec<Self> {
+        vec![Self::gregorian(), Self::harptos()]
+    }
+
+    /// Fails with [`Error::InvalidCalendar`] unless the calendar has at least one month, and
+    /// all months have days and distinct names
+    pub fn validate(&self) -> Result<()> {
+        let invalid = |msg: String| Err(Error::InvalidCalendar(msg));
+        if self.months.is_empty() {
+            return invalid("it needs at least one month".into());
+        }
+        for (i, m) in self.months.iter().enumerate() {
+            if m.name.trim().is_empty() {
+                return invalid("all months need a name".into());
+            }
+            if m.days == 0 {
+                return invalid(format!("{} has no days", m.name));
+            }
+            if self.months[..i].iter().any(|o| same_name(&o.name, &m.name)) {
+                return invalid(format!("there are two months named {}", m.name));
+            }
+        }
+        if !self.weekdays.is_empty() && self.first_weekday >= self.weekdays.len() {
+            return invalid("the first weekday doesn't exist".into());
+        }
+        if self
+            .eras
+            .windows(2)
+            .any(|w| w[0].start_year >= w[1].start_year)
+        {
+            return invalid("eras must be sorted by their start".into());
+        }
+        Ok(())
+    }
+
+    pub fn is_leap_year(&self, year: i64) -> bool {
+        let divisible = |n: i64| n > 0 && year.rem_euclid(n) == 0;
+        let LeapRule {
+            every,
+            except,
+            unless,
+        } = self.leap;
+        divisible(every) && (!divisible(except) || divisible(unless))
+    }
+
+    pub fn month_length(&self, year: i64, month: u32) -> u32 {
+        let Some(m) = self.months.get(month as usize) else {
+            return 0;
+        };
+        if self.is_leap_year(year) {
+            m.days + m.leap_days
+        } else {
+            m.days
+        }
+    }
+
+    pub fn year_length(&self, year: i64) -> i64 {
+        (0..self.months.len() as u32)
+            .map(|m| self.month_length(year, m) as i64)
+            .sum()
+    }
+
+    /// Days since the first day of year 1, negative for earlier dates
+    pub fn day_number(&self, date: CalendarDate) -> i64 {
+        let before_month: i64 = (0..date.month)
+            .map(|m| self.month_length(date.year, m) as i64)
+            .sum();
+        self.days_before_year(date.year) + before_month + date.day as i64 - 1
+    }
+
+    /// The inverse of [`Calendar::day_number`]
+    pub fn date_of_day_number(&self, n: i64) -> CalendarDate {
+        let normal_year = self
+            .months
+            .iter()
+            .map(|m| m.days as i64)
+            .sum::<i64>()
+            .max(1);
+        let mut year = 1 + n.div_euclid(normal_year);
+        while self.days_before_year(year) > n {
+            year -= 1;
+        }
+        while self.days_before_year(year + 1) <= n {
+            year += 1;
+        }
+        let mut rest = n - self.days_before_year(year);
+        let mut month = 0;
+        while month + 1 < self.months.len() as u32 && rest >= self.month_length(year, month) as i64
+        {
+            rest -= self.month_length(year, month) as i64;
+            month += 1;
+        }
+        CalendarDate {
+            year,
+            month,
+            day: rest as u32 + 1,
+        }
+    }
+
+    pub fn weekday(&self, date: CalendarDate) -> Option<&str> {
+        if self.weekdays.is_empty() {
+            return None;
+        }
+        let n = self.day_number(date) + self.first_weekday as i64;
+        Some(&self.weekdays[n.rem_euclid(self.weekdays.len() as i64) as usize])
+    }
+
+    /// Fails with [`Error::InvalidDate`] if the month or day doesn't exist
+    pub fn check(&self, date: CalendarDate) -> Result<()> {
+        let Some(month) = self.months.get(date.month as usize) else {
+            return Err(invalid_date(date, "there is no such month"));
+        };
+        let length = self.month_length(date.year, date.month);
+        if date.day == 0 || date.day > length {
+            return Err(invalid_date(
+                date,
+                &format!("{} has {length} days in that year", month.name),
+            ));
+        }
+        Ok(())
+    }
+
+    /// The year as it is written in the calendar's eras, e.g. `(1492, "DR")`
+    pub fn era_year(&self, year: i64) -> (i64, &str) {
+        match self.eras.iter().rev().find(|e| e.start_year <= year) {
+            Some(era) => (year - era.start_year + 1, &era.name),
+            None => match self.eras.first() {
+                Some(first) if !self.before_eras.is_empty() => {
+                    (first.start_year - year, &self.before_eras)
+                }
+                _ => (year, ""),
+            },
+        }
+    }
+
+    /// Writes the date like `14 March 2023 AD`. Single day months leave out the day, like
+    /// `Midwinter 1492 DR`.
+    pub fn format(&self, date: CalendarDate) -> String {
+        let (year, era) = self.era_year(date.year);
+        let year = if era.is_empty() {
+            year.to_string()
+        } else {
+            format!("{year} {era}")
+        };
+        match self.months.get(date.month as usize) {
+            Some(m) if m.days == 1 && date.day == 1 => format!("{} {year}", m.name),
+            Some(m) => format!("{} {} {year}", date.day, m.name),
+            None => format!("{}-{}-{}", date.year, date.month + 1, date.day),
+        }
+    }
+
+    /// The period of the given precision the date lies in, like `March 2023 AD` for months.
+    /// All dates in the same period get the same text.
+    pub fn format_period(&self, date: CalendarDate, precision: Precision) -> String {
+        let (year, era) = self.era_year(date.year);
+        let year = if era.is_empty() {
+            year.to_string()
+        } else {
+            format!("{year} {era}")
+        };
+        let month = self.months.get(date.month as usize);
+        match precision {
+            Precision::Era if era.is_empty() => "No era".to_string(),
+            Precision::Era => era.to_string(),
+            Precision::Year => year,
+            Precision::Month => match month {
+                Some(m) => format!("{} {year}", m.name),
+                None => year,
+            },
+            Precision::Day => self.format(date),
+        }
+    }
+
+    /// Reads dates written like `14 March 2023`, `March 14th, 2023 AD`, `Midwinter 1492 DR`
+    /// or `2023-03-14`. Month, era and weekday names are case insensitive, and weekdays are
+    /// ignored. Years without an era are absolute.
+    pub fn parse(&self, text: &str) -> Result<CalendarDate> {
+        let text = text.trim();
+        if let Some(date) = self.parse_numeric(text) {
+            self.check(date)?;
+            return Ok(date);
+        }
+        let invalid = |reason: &str| Error::InvalidDate(text.to_string(), reason.to_string());
+
+        let mut numbers = vec![];
+        let mut month = None;
+        // Some(None) are the years before the first era
+        let mut era = None;
+        for word in text
+            .split(|c: char| c.is_whitespace() || c == ',')
+            .filter(|w| !w.is_empty())
+        {
+            if let Some(n) = parse_number(word) {
+                numbers.push(n);
+            } else if let Some(m) = self.months.iter().position(|m| same_name(&m.name, word)) {
+                month = Some(m as u32);
+            } else if let Some(e) = self.eras.iter().find(|e| same_name(&e.name, word)) {
+                era = Some(Some(e));
+            } else if !self.before_eras.is_empty() && same_name(&self.before_eras, word) {
+                era = Some(None);
+            } else if !self.weekdays.iter().any(|d| same_name(d, word)) {
+                // multi word month names like "Feast of the Moon"
+                month = month.or_else(|| self.month_in(text));
+                if month.is_none() || !self.month_contains_word(month, word) {
+                    return Err(invalid(&format!("\"{word}\" is no month or era")));
+                }
+            }
+        }
+        let month = month.ok_or_else(|| invalid("the month is missing"))?;
+        let (day, year) = match numbers[..] {
+            [day, year] => (day, year),
+            [year] if self.months[month as usize].days == 1 => (1, year),
+            _ => return Err(invalid("expected a day and a year")),
+        };
+        let year = match era {
+            Some(Some(era)) => era.start_year + year - 1,
+            Some(None) => self.eras.first().map(|e| e.start_year).unwrap_or(1) - year,
+            None => year,
+        };
+        let date = CalendarDate {
+            year,
+            month,
+            day: u32::try_from(day).map_err(|_| invalid("the day can't be negative"))?,
+        };
+        self.check(date)?;
+        Ok(date)
+    }
+
+    /// `year-month-day` with a numeric month, the year may be negative
+    fn parse_numeric(&self, text: &str) -> Option<CalendarDate> {
+        let (sign, rest) = match text.strip_prefix('-') {
+            Some(rest) => (-1, rest),
+            None => (1, text),
+        };
+        let mut parts = rest.split('-');
+        let year = parts.next()?.parse::<i64>().ok()?;
+        let month = parts.next()?.parse::<u32>().ok()?;
+        let day = parts.next()?.parse::<u32>().ok()?;
+        if parts.next().is_some() || month == 0 {
+            return None;
+        }
+        Some(CalendarDate {
+            year: sign * year,
+            month: month - 1,
+            day,
+        })
+    }
+
+    /// The month whose name appears in the text, the longest one if several do
+    fn month_in(&self, text: &str) -> Option<u32> {
+        let text = text.to_lowercase();
+        self.months
+            .iter()
+            .enumerate()
+            .filter(|(_, m)| m.name.contains(' ') && text.contains(&m.name.to_lowercase()))
+            .max_by_key(|(_, m)| m.name.len())
+            .map(|(i, _)| i as u32)
+    }
+
+    fn month_contains_word(&self, month: Option<u32>, word: &str) -> bool {
+        month
+            .and_then(|m| self.months.get(m as usize))
+            .map(|m| m.name.split_whitespace().any(|w| same_name(w, word)))
+            .unwrap_or(false)
+    }
+
+    /// Days between the first day of year 1 and the first day of `year`
+    fn days_before_year(&self, year: i64) -> i64 {
+        let normal_year: i64 = self.months.iter().map(|m| m.days as i64).sum();
+        let leap_days: i64 = self.months.iter().map(|m| m.leap_days as i64).sum();
+        // multiples of n in [1, year), negative for years before 1
+        let multiples = |n: i64| {
+            if n > 0 {
+                (year - 1).div_euclid(n)
+            } else {
+                0
+            }
+        };
+        let LeapRule {
+            every,
+            except,
+            unless,
+        } = self.leap;
+        let leap_years = match (every, except) {
+            (0, _) => 0,
+            (_, 0) => multiples(every),
+            _ => multiples(every) - multiples(except) + multiples(unless),
+        };
+        (year - 1) * normal_year + leap_years * leap_days
+    }
+}
+
+/// Parses one month per line, written as `Name: days` or `Name: days + leap days`
+pub fn parse_months(text: &str) -> Result<Vec<Month>> {
+    let mut res = vec![];
+    for line in text.lines().map(str::trim).filter(|l| !l.is_empty()) {
+        let invalid =
+            || Error::InvalidCalendar(format!("\"{line}\" should look like \"Name: 30\""));
+        let (name, days) = line.rsplit_once(':').ok_or_else(invalid)?;
+        let (days, leap_days) = days.split_once('+').unwrap_or((days, "0"));
+        res.push(Month {
+            name: name.trim().to_string(),
+            days: days.trim().parse().map_err(|_| invalid())?,
+            leap_days: leap_days.trim().parse().map_err(|_| invalid())?,
+        });
+    }
+    Ok(res)
+}
+
+/// The inverse of [`parse_months`]
+pub fn format_months(months: &[Month]) -> String {
+    months
+        .iter()
+        .map(|m| match m.leap_days {
+            0 => format!("{}: {}", m.name, m.days),
+            leap => format!("{}: {} + {leap}", m.name, m.days),
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Parses one era per line, written as `Name: start year`
+pub fn parse_eras(text: &str) -> Result<Vec<Era>> {
+    let mut res = vec![];
+    for line in text.lines().map(str::trim).filter(|l| !l.is_empty()) {
+        let invalid = || Error::InvalidCalendar(format!("\"{line}\" should look like \"DR: 1\""));
+        let (name, start) = line.rsplit_once(':').ok_or_else(invalid)?;
+        res.push(Era {
+            name: name.trim().to_string(),
+            start_year: start.trim().parse().map_err(|_| invalid())?,
+        });
+    }
+    Ok(res)
+}
+
+/// The inverse of [`parse_eras`]
+pub fn format_eras(eras: &[Era]) -> String {
+    eras.iter()
+        .map(|e| format!("{}: {}", e.name, e.start_year))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+fn months_from(months: &[(&str, u32, u32)]) -> Vec<Month> {
+    months
+        .iter()
+        .map(|(name, days, leap_days)| Month {
+            name: name.to_string(),
+            days: *days,
+            leap_days: *leap_days,
+        })
+        .collect()
+}
+
+fn same_name(a: &str, b: &str) -> bool {
+    a.trim().to_lowercase() == b.trim().to_lowercase()
+}
+
+/// A number, optionally with an ordinal suffix like `14th`
+fn parse_number(word: &str) -> Option<i64> {
+    let digits = ["st", "nd", "rd", "th"]
+        .iter()
+        .find_map(|s| word.strip_suffix(s))
+        .unwrap_or(word);
+    digits.parse().ok()
+}
+
+fn invalid_date(date: CalendarDate, reason: &str) -> Error {
+    Error::InvalidDate(
+        format!("{}-{}-{}", date.year, date.month + 1, date.day),
+        reason.to_string(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(year: i64, month: u32, day: u32) -> CalendarDate {
+        CalendarDate { year, month, day }
+    }
+
+    #[test]
+    fn gregorian_leap_years_and_weekdays() {
+        let cal = Calendar::gregorian();
+        assert!(cal.is_leap_year(2000) && cal.is_leap_year(2024));
+        assert!(!cal.is_leap_year(1900) && !cal.is_leap_year(2023));
+        assert_eq!(cal.year_length(2024), 366);
+        // 1 January of year 1 was a Monday in the proleptic Gregorian calendar
+        assert_eq!(cal.weekday(date(2023, 6, 4)), Some("Tuesday"));
+        assert_eq!(cal.weekday(date(2000, 1, 29)), Some("Tuesday"));
+        for n in [-800, -1, 0, 1, 59, 365, 738_000] {
+            assert_eq!(cal.day_number(cal.date_of_day_number(n)), n);
+        }
+    }
+
+    #[test]
+    fn parses_and_formats_dates() {
+        let cal = Calendar::gregorian();
+        let d = date(2023, 2, 14);
+        for text in [
+            "14 March 2023",
+            "March 14th, 2023 AD",
+            "2023-03-14",
+            "Tuesday 14 march 2023",
+        ] {
+            assert_eq!(cal.parse(text).unwrap(), d, "{text}");
+        }
+        assert_eq!(cal.format(d), "14 March 2023 AD");
+        assert_eq!(cal.parse("15 March 44 BC").unwrap().year, -43);
+        assert_eq!(cal.format(date(-43, 2, 15)), "15 March 44 BC");
+        assert!(cal.parse("29 February 2023").is_err());
+        assert!(cal.parse("14 Marsh 2023").is_err());
+
+        let harptos = Calendar::harptos();
+        let midwinter = harptos.parse("Midwinter 1492 DR").unwrap();
+        assert_eq!(harptos.format(midwinter), "Midwinter 1492 DR");
+        let feast = harptos.parse("Feast of the Moon 1492").unwrap();
+        assert_eq!(
+            harptos.months[feast.month as usize].name,
+            "Feast of the Moon"
+        );
+        assert!(harptos.parse("2 Midsummer 1492").is_ok());
+        assert!(harptos.parse("2 Midsummer 1491").is_err());
+        assert_eq!(harptos.year_length(1492), 366);
+    }
+
+    #[test]
+    fn dates_sort_chronologically() {
+        let cal = Calendar::harptos();
+        let mut dates: Vec<_> = ["1 Nightal 1491", "Midwinter 1492", "30 Hammer 1492"]
+            .into_iter()
+            .map(|t| cal.parse(t).unwrap())
+            .collect();
+        dates.reverse();
+        dates.sort();
+        assert_eq!(cal.format(dates[1]), "30 Hammer 1492 DR");
+    }
+
+    #[test]
+    fn formats_periods() {
+        let cal = Calendar::gregorian();
+        let d = date(2023, 2, 14);
+        let periods: Vec<_> = Precision::ALL
+            .into_iter()
+            .map(|p| cal.format_period(d, p))
+            .collect();
+        assert_eq!(
+            periods,
+            ["AD", "2023 AD", "March 2023 AD", "14 March 2023 AD"]
+        );
+        assert_eq!(cal.format_period(date(-5, 0, 1), Precision::Era), "BC");
+    }
+
+    #[test]
+    fn month_and_era_text_round_trips() {
+        let cal = Calendar::harptos();
+        assert_eq!(
+            parse_months(&format_months(&cal.months)).unwrap(),
+            cal.months
+        );
+        assert_eq!(parse_eras(&format_eras(&cal.eras)).unwrap(), cal.eras);
+        assert!(parse_months("Hammer").is_err());
+        assert!(Calendar::gregorian().validate().is_ok());
+    }
+}
@@ -0,0 +1,86 @@
+#![allow(non_snake_case)]
+use dioxus::prelude::*;
+
+use crate::{
+    comp_try,
+    schema::v1::{Backlink, EntityKind, EntityRef},
+    ActiveMode, Schema, State,
+};
+
+/// Lists everything that mentions or links to an entity, grouped by kind
+#[inline_props]
+pub fn Backlinks(cx: Scope, entity: EntityRef) -> Element {
+    let state = use_shared_state::<State>(cx).unwrap();
+    let db_path = state.read().db_path.clone().unwrap();
+    let mut db = comp_try!(state, Schema::open(&db_path));
+    let audience = state.read().audience();
+    let links = comp_try!(state, db.backlinks(*entity, audience));
+
+    let sections = [
+        (EntityKind::Subject, "Subjects"),
+        (EntityKind::Place, "Places"),
+        (EntityKind::Group, "Groups"),
+        (EntityKind::Event, "Events"),
+    ]
+    .into_iter()
+    .map(|(kind, title)| {
+        let entries = links
+            .iter()
+            .filter(|b| b.source.kind == kind)
+            .cloned()
+            .collect::<Vec<_>>();
+        (title, entries)
+    })
+    .filter(|(_, entries)| !entries.is_empty())
+    .collect::<Vec<_>>();
+
+    if sections.is_empty() {
+        return None;
+    }
+
+    render! {
+        div {
+            padding: "1em",
+            h2 { "Backlinks" },
+            sections.into_iter().map(|(title, entries)| rsx!(
+                div {
+                    key: "{title}",
+                    h3 { "{title}" },
+                    ul {
+                        entries.into_iter().map(|b| rsx!(BacklinkEntry {
+                            key: "{b.source.id}",
+                            link: b
+                        }))
+                    }
+                }
+            ))
+        }
+    }
+}
+
+#[inline_props]
+fn BacklinkEntry(cx: Scope, link: Backlink) -> Element {
+    let state = use_shared_state::<State>(cx).unwrap();
+    let mode = ActiveMode::view(link.source.kind, link.label.clone());
+    render! {
+        li {
+            span {
+                cursor: "pointer",
+                text_decoration: "underline",
+                onclick: move |_| {
+                    if let Some(mode) = mode.clone() {
+                        state.write().set_active_mode(mode);
+                    }
+                },
+                link.label.clone()
+            },
+            link.snippet.as_ref().map(|s| rsx!(
+                div {
+                    color: "grey",
+                    font_size: "small",
+                    "{s}"
+                }
+            ))
+        }
+    }
+}
@@ -0,0 +1,138 @@
+#![allow(non_snake_case)]
+use anyhow::anyhow;
+use dioxus::prelude::*;
+
+use crate::{
+    attempt,
+    calendar::{format_eras, format_months, parse_eras, parse_months, Calendar, LeapRule},
+    comp_try, Schema, State,
+};
+
+/// Edits the campaign's calendar. A preset can be loaded into the form before saving, and a
+/// date can be typed in to see how the calendar reads it.
+pub fn CalendarSettings(cx: Scope) -> Element {
+    let state = use_shared_state::<State>(cx).unwrap();
+    // a preset that was loaded, but not saved yet
+    let draft = use_state(cx, || None::<Calendar>);
+    let sample = use_state(cx, String::new);
+    let db_path = state.read().db_path.clone().unwrap();
+    let mut db = comp_try!(state, Schema::open(&db_path));
+    let saved = comp_try!(state, db.calendar());
+    let cal = draft.get().clone().unwrap_or_else(|| saved.clone());
+
+    let months = format_months(&cal.months);
+    let weekdays = cal.weekdays.join("\n");
+    let eras = format_eras(&cal.eras);
+    let first_weekday = cal
+        .weekdays
+        .get(cal.first_weekday)
+        .cloned()
+        .unwrap_or_default();
+    let example = saved.format(saved.date_of_day_number(0));
+    let preview = if sample.is_empty() {
+        String::new()
+    } else {
+        match saved.parse(sample.get()) {
+            Ok(date) => match saved.weekday(date) {
+                Some(day) => format!("{day}, {}", saved.format(date)),
+                None => saved.format(date),
+            },
+            Err(e) => e.to_string(),
+        }
+    };
+
+    render! {
+        div {
+            padding: "1em",
+            h1 { "Calendar" },
+            p {
+                "Load a preset: ",
+                Calendar::presets().into_iter().map(|preset| {
+                    let name = preset.name.clone();
+                    rsx!(span {
+                        key: "{name}",
+                        cursor: "pointer",
+                        text_decoration: "underline",
+                        margin_right: "1em",
+                        onclick: move |_| draft.set(Some(preset.clone())),
+                        "{name}"
+                    })
+                })
+            },
+            form {
+                display: "flex",
+                flex_direction: "column",
+                gap: "0.5em",
+                onsubmit: move |ev| {
+                    attempt!{ state {
+                        let values = &ev.data.values;
+                        let value = |key: &str| values[key][0].clone();
+                        let number = |key: &str| -> anyhow::Result<i64> {
+                            let v = value(key);
+                            if v.trim().is_empty() {
+                                return Ok(0);
+                            }
+                            v.trim().parse().map_err(|_| anyhow!("\"{v}\" is not a number"))
+                        };
+                        let weekdays: Vec<String> = value("weekdays")
+                            .lines()
+                            .map(|d| d.trim().to_string())
+                            .filter(|d| !d.is_empty())
+                            .collect();
+                        let first_weekday = weekdays
+                            .iter()
+                            .position(|d| *d == value("first_weekday").trim())
+                            .unwrap_or(0);
+                        let new = Calendar {
+                            name: value("name").trim().to_string(),
+                            months: parse_months(&value("months"))?,
+                            weekdays,
+                            eras: parse_eras(&value("eras"))?,
+                            before_eras: value("before_eras").trim().to_string(),
+                            leap: LeapRule {
+                                every: number("leap_every")?,
+                                except: number("leap_except")?,
+                                unless: number("leap_unless")?,
+                            },
+                            first_weekday,
+                        };
+                        let db_path = state.read().db_path.clone().unwrap();
+                        Schema::open(&db_path)?.set_calendar(&new)?;
+                        draft.set(None);
+                        cx.needs_update();
+                        Ok(())
+                    }}
+                },
+                label { "Name" },
+                input { name: "name", value: "{cal.name}" },
+                label { "Months in order, one per line, like \"Hammer: 30\" or \"February: 28 + 1\" with leap days" },
+                textarea { name: "months", rows: "12", value: "{months}" },
+                label { "Weekdays, one per line" },
+                textarea { name: "weekdays", rows: "7", value: "{weekdays}" },
+                label { "Weekday of the first day of year 1" },
+                input { name: "first_weekday", value: "{first_weekday}" },
+                label { "Eras, one per line, like \"DR: 1\" for an era whose first year is the year 1" },
+                textarea { name: "eras", rows: "3", value: "{eras}" },
+                label { "Name for the years before the first era, like \"BC\"" },
+                input { name: "before_eras", value: "{cal.before_eras}" },
+                label { "Leap years: every n years, except every n years, unless every n years (0 to turn a rule off)" },
+                div {
+                    display: "flex",
+                    gap: "0.5em",
+                    input { name: "leap_every", r#type: "number", value: "{cal.leap.every}" },
+                    input { name: "leap_except", r#type: "number", value: "{cal.leap.except}" },
+                    input { name: "leap_unless", r#type: "number", value: "{cal.leap.unless}" },
+                },
+                p { "Saving reads the dates of all events again with the new calendar." },
+                input { r#type: "submit", value: "Save" },
+            },
+            h2 { "Try a date" },
+            input {
+                value: "{sample}",
+                placeholder: "e.g. {example}",
+                oninput: move |evt| sample.set(evt.value.clone()),
+            },
+            p { "{preview}" }
+        }
+    }
+}
@@ -0,0 +1,60 @@
+#![allow(non_snake_case)]
+use dioxus::prelude::*;
+
+use crate::{attempt, comp_try, components::PrimaryButton, Mode, Schema, State};
+
+/// Asks before moving an entity to the trash, and lists the links that will be lost once the
+/// trash is emptied
+pub fn ConfirmDelete(cx: Scope) -> Element {
+    let state = use_shared_state::<State>(cx).unwrap();
+    let Mode::ConfirmDelete {
+        entity,
+        label,
+        parent,
+    } = state.read().mode.clone()
+    else {
+        panic!("ConfirmDelete component used without being in ConfirmDelete State");
+    };
+    let db_path = state.read().db_path.clone().unwrap();
+    let mut db = comp_try!(state, Schema::open(&db_path));
+    let links = comp_try!(state, db.links_removed_on_delete(entity));
+    let parent2 = parent.clone();
+
+    render! {
+        div {
+            padding: "1em",
+            h1 { "Delete {entity.kind} \"{label}\"?" },
+            p { "It will be moved to the trash, from where it can be restored until the trash is emptied." },
+            if !links.is_empty() {
+                rsx!(
+                    p { "Emptying the trash will also remove these links:" },
+                    ul {
+                        links.iter().map(|l| rsx!(li {
+                            key: "{l.entity.kind:?}{l.entity.id}",
+                            "{l.entity.kind}: {l.label}"
+                        }))
+                    }
+                )
+            },
+            div {
+                display: "flex",
+                gap: "1em",
+                padding_top: "1em",
+                PrimaryButton {
+                    onclick: move |_| {
+                        attempt!{ state {
+                            Schema::open(&db_path)?.trash(entity)?;
+                            state.write().mode = (*parent).clone();
+                            Ok(())
+                        }}
+                    },
+                    "Delete"
+                },
+                PrimaryButton {
+                    onclick: move |_| { state.write().mode = (*parent2).clone() },
+                    "Cancel"
+                },
+            }
+        }
+    }
+}
@@ -6,14 +6,16 @@ use std::{
 };
 
 use anyhow::Context;
-use comrak::{markdown_to_html, ComrakOptions};
 use dioxus::prelude::*;
 use futures::{stream, StreamExt};
 use log::{debug, trace};
 use persistent_structs::PersistentStruct;
 use tokio::select;
 
-use crate::{schema::v1::Subject, ActiveMode, Mode, Schema, State};
+use crate::{
+    schema::v1::{EntityKind, EntityRef, Subject},
+    wiki, ActiveMode, Mode, Schema, State,
+};
 
 macro_rules! coro_try {
     ($state:ident, $expr:expr) => {
@@ -41,7 +43,7 @@ pub fn EditingSubject(cx: Scope, name: String) -> Element {
             let mut db = coro_try!(state, Schema::open(&db_path));
             // create subject if it doesn't exist, otherwise simply query id
             debug!("Editing Subject with name: {name}");
-            let mut sub = match coro_try!(state, db.get_sub_by_name(&name)) {
+            let sub = match coro_try!(state, db.get_sub_by_name(&name)) {
                 Some(sub) => {
                     trace!("Subject already exists");
                     sub
@@ -59,6 +61,13 @@ pub fn EditingSubject(cx: Scope, name: String) -> Element {
                     sub
                 }
             };
+            let entity = EntityRef::new(EntityKind::Subject, sub.id);
+            coro_try!(state, db.ensure_revision(entity, &sub.description));
+            // all saves of this editor run are coalesced into as few revisions as possible
+            let session = SystemTime::now()
+                .duration_since(SystemTime::UNIX_EPOCH)
+                .map(|d| d.as_millis() as i64)
+                .unwrap_or(0);
             let mut adapter = coro_try!(state, editor_stream(sub.description.clone()));
 
             loop {
@@ -71,10 +80,10 @@ pub fn EditingSubject(cx: Scope, name: String) -> Element {
                         match msg {
                             Some(Ok(s)) => {
                                 debug!("got text update");
-                                sub.description = s.clone();
-                                coro_try!(state, db.update_subject(&sub));
+                                coro_try!(state, db.save_description(entity, &s, Some(session)));
                                 trace!("succesfully wrote to db");
-                                html.set(markdown_to_html(&s, &ComrakOptions::default()));
+                                let audience = state.read().audience();
+                                html.set(coro_try!(state, wiki::render_markdown(&mut db, &s, audience)));
                                 is_err.set(false);
                             },
                             Some(Err(s)) => {
@@ -1,8 +1,299 @@
 #![allow(non_snake_case)]
 use dioxus::prelude::*;
 
-use crate::Mode;
+use crate::{
+    attempt,
+    calendar::{Calendar, Precision},
+    comp_try,
+    components::{color, Markdown, SecretToggle, TagEditor},
+    schema::{
+        v1::{Audience, EntityKind, EntityRef, Event},
+        WithId,
+    },
+    wiki, ActiveMode, Schema, State,
+};
 
+/// The kinds events can be filtered by, in the order the filters are shown
+const FILTER_KINDS: [EntityKind; 4] = [
+    EntityKind::Subject,
+    EntityKind::Place,
+    EntityKind::Group,
+    EntityKind::Tag,
+];
+
+/// All events on a timeline, in the order they happen in the world. Events are grouped by
+/// era, year, month or day, and can be filtered by the entities they are linked to. Clicking
+/// an event shows its details next to the timeline.
 pub fn Events(cx: Scope) -> Element {
-    render! { p { "Nothing here yet" } }
+    let state = use_shared_state::<State>(cx).unwrap();
+    let precision = use_state(cx, || Precision::Year);
+    // the chosen filter for each of FILTER_KINDS
+    let filters = use_state(cx, || [None::<i64>; 4]);
+    let selected = use_state(cx, || None::<i64>);
+    let db_path = state.read().db_path.clone().unwrap();
+    let mut db = comp_try!(state, Schema::open(&db_path));
+
+    if comp_try!(state, db.list_events()).is_empty() {
+        return render! { p { "Nothing here yet" } };
+    }
+    let calendar = comp_try!(state, db.calendar());
+    let linked_to: Vec<_> = FILTER_KINDS
+        .iter()
+        .zip(filters.get())
+        .filter_map(|(kind, id)| id.map(|id| EntityRef::new(*kind, id)))
+        .collect();
+    let audience = state.read().audience();
+    let mut events = vec![];
+    for ev in comp_try!(state, db.events_linked_to(&linked_to)) {
+        if comp_try!(
+            state,
+            db.is_visible(EntityRef::new(EntityKind::Event, ev.id), audience)
+        ) {
+            events.push(ev);
+        }
+    }
+    let periods = group_by_period(&calendar, events, *precision.get());
+
+    let mut choices = vec![];
+    for kind in FILTER_KINDS {
+        let names: Vec<_> = match kind {
+            EntityKind::Tag => comp_try!(state, db.list_tags(audience))
+                .into_iter()
+                .map(|t| (t.id, t.name))
+                .collect(),
+            _ => comp_try!(state, db.query_names(kind, ""))
+                .into_iter()
+                .map(|n| (n.id, n.t))
+                .collect(),
+        };
+        let mut visible = vec![];
+        for (id, name) in names {
+            if comp_try!(state, db.is_visible(EntityRef::new(kind, id), audience)) {
+                visible.push((id, name));
+            }
+        }
+        choices.push((kind, visible));
+    }
+    let timeline_width = if selected.is_some() { "55%" } else { "100%" };
+
+    render! {
+        div {
+            padding: "1em",
+            div {
+                display: "flex",
+                flex_wrap: "wrap",
+                gap: "1em",
+                align_items: "center",
+                span {
+                    "Zoom: ",
+                    Precision::ALL.into_iter().map(|p| {
+                        let weight = if p == *precision.get() { "bold" } else { "normal" };
+                        let name = p.as_str();
+                        rsx!(span {
+                            key: "{name}",
+                            cursor: "pointer",
+                            margin_right: "0.5em",
+                            font_weight: "{weight}",
+                            text_decoration: "underline",
+                            onclick: move |_| precision.set(p),
+                            "{name}"
+                        })
+                    })
+                },
+                choices.into_iter().enumerate().map(|(i, (kind, names))| rsx!(
+                    select {
+                        key: "{kind}",
+                        onchange: move |evt| {
+                            let mut new = *filters.get();
+                            new[i] = evt.value.parse().ok();
+                            filters.set(new);
+                        },
+                        option { value: "", "Any {kind}" },
+                        names.into_iter().map(|(id, name)| rsx!(option {
+                            key: "{id}",
+                            value: "{id}",
+                            selected: filters.get()[i] == Some(id),
+                            "{name}"
+                        }))
+                    }
+                ))
+            },
+            div {
+                display: "flex",
+                gap: "1em",
+                align_items: "flex-start",
+                div {
+                    width: "{timeline_width}",
+                    if periods.is_empty() {
+                        rsx!(p { "No events match the filters" })
+                    }
+                    periods.into_iter().map(|(period, events)| rsx!(
+                        div {
+                            key: "{period}",
+                            border_left: "3px solid {color::PRIMARY}",
+                            padding_left: "1em",
+                            margin_bottom: "1em",
+                            h2 { margin: "0.3em 0", "{period}" },
+                            events.into_iter().map(|ev| {
+                                let id = ev.id;
+                                let background = if *selected.get() == Some(id) { color::GREY } else { color::WHITE };
+                                let summary = summary(&ev.description, audience);
+                                rsx!(div {
+                                    key: "{id}",
+                                    cursor: "pointer",
+                                    padding: "0.3em 0.5em",
+                                    border_radius: "6px",
+                                    background_color: "{background}",
+                                    onclick: move |_| selected.set(Some(id)),
+                                    strong { "{ev.refered_date}" },
+                                    " {summary}"
+                                })
+                            })
+                        }
+                    ))
+                },
+                if let Some(id) = *selected.get() {
+                    rsx!(EventDetails { id: id, onclose: move |_| selected.set(None) })
+                }
+            }
+        }
+    }
+}
+
+/// An event with its weekday, description, participants and tags. Secret events show nothing
+/// in the player view.
+#[inline_props]
+fn EventDetails<'a>(cx: Scope<'a>, id: i64, onclose: EventHandler<'a, ()>) -> Element<'a> {
+    let state = use_shared_state::<State>(cx).unwrap();
+    let db_path = state.read().db_path.clone().unwrap();
+    let mut db = comp_try!(state, Schema::open(&db_path));
+    let Some(ev) = comp_try!(state, db.get_event(*id)) else {
+        return None;
+    };
+    let entity = EntityRef::new(EntityKind::Event, ev.id);
+    let audience = state.read().audience();
+    // the event may have been selected before the player view was turned on
+    if !comp_try!(state, db.is_visible(entity, audience)) {
+        return None;
+    }
+    let calendar = comp_try!(state, db.calendar());
+    let weekday = ev
+        .date
+        .and_then(|d| calendar.weekday(d))
+        .map(|d| format!("{d}, "))
+        .unwrap_or_default();
+    let html = comp_try!(
+        state,
+        wiki::render_markdown(&mut db, &ev.description, audience)
+    );
+    let participants: Vec<_> = comp_try!(state, db.outgoing_links(entity))
+        .into_iter()
+        .filter(|l| l.entity.kind != EntityKind::Tag && audience.sees(l.secret))
+        .collect();
+    let sessions = comp_try!(state, db.list_sessions());
+    let event_id = ev.id;
+
+    render! {
+        div {
+            flex: "1",
+            padding: "0.5em 1em",
+            background_color: color::GREY,
+            border_radius: "10px",
+            div {
+                display: "flex",
+                justify_content: "space-between",
+                h2 { margin: "0.3em 0", "{weekday}{ev.refered_date}" },
+                span {
+                    cursor: "pointer",
+                    title: "Close",
+                    onclick: move |_| onclose.call(()),
+                    "×"
+                }
+            },
+            TagEditor { entity: entity }
+            SecretToggle { entity: entity }
+            p {
+                "Session: ",
+                select {
+                    onchange: move |evt| {
+                        attempt!{ state {
+                            let db_path = state.read().db_path.clone().unwrap();
+                            let session = evt.value.parse().ok();
+                            Schema::open(&db_path)?.set_event_session(event_id, session)?;
+                            cx.needs_update();
+                            Ok(())
+                        }}
+                    },
+                    option { value: "", "None" },
+                    sessions.into_iter().map(|s| rsx!(option {
+                        key: "{s.id}",
+                        value: "{s.id}",
+                        selected: ev.session == Some(s.id),
+                        "#{s.number} {s.title}"
+                    }))
+                }
+            },
+            Markdown { html: html }
+            if !participants.is_empty() {
+                rsx!(
+                    h3 { "Participants" },
+                    ul {
+                        participants.into_iter().map(|l| {
+                            let view = ActiveMode::view(l.entity.kind, l.label.clone());
+                            rsx!(li {
+                                key: "{l.entity.kind}-{l.entity.id}",
+                                cursor: "pointer",
+                                text_decoration: "underline",
+                                onclick: move |_| {
+                                    if let Some(view) = view.clone() {
+                                        state.write().set_active_mode(view);
+                                    }
+                                },
+                                "{l.label} ({l.entity.kind})"
+                            })
+                        })
+                    }
+                )
+            }
+        }
+    }
+}
+
+/// Groups the events, which are in world order, into consecutive periods of the given
+/// precision. Events without an in-world date are put last.
+fn group_by_period(
+    calendar: &Calendar,
+    events: Vec<WithId<Event>>,
+    precision: Precision,
+) -> Vec<(String, Vec<WithId<Event>>)> {
+    let mut res: Vec<(String, Vec<WithId<Event>>)> = vec![];
+    for ev in events {
+        let period = match ev.date {
+            Some(date) => calendar.format_period(date, precision),
+            None => "Undated".to_string(),
+        };
+        match res.last_mut() {
+            Some((last, events)) if *last == period => events.push(ev),
+            _ => res.push((period, vec![ev])),
+        }
+    }
+    res
+}
+
+/// The first line of a description the audience may see, shortened to fit on a line of the
+/// timeline
+fn summary(description: &str, audience: Audience) -> String {
+    let description = match audience {
+        Audience::GameMaster => description.to_string(),
+        Audience::Players => wiki::strip_secrets(description),
+    };
+    let line = description
+        .lines()
+        .map(|l| l.trim_start_matches('#').trim())
+        .find(|l| !l.is_empty())
+        .unwrap_or_default();
+    match line.char_indices().nth(80) {
+        Some((i, _)) => format!("{}…", &line[..i]),
+        None => line.to_string(),
+    }
 }
@@ -0,0 +1,84 @@
+#![allow(non_snake_case)]
+use dioxus::prelude::*;
+
+use crate::{
+    attempt, components::SecondaryButton, html_export::export_html, schema::v1::Audience,
+    vault::export_vault, Schema, State,
+};
+
+/// Writes the campaign into other formats
+pub fn Export(cx: Scope) -> Element {
+    let state = use_shared_state::<State>(cx).unwrap();
+    // exports are meant to be handed out, so they leave out secrets unless told otherwise
+    let players_only = use_state(cx, || true);
+    let message = use_state(cx, String::new);
+    let audience = if *players_only.get() {
+        Audience::Players
+    } else {
+        Audience::GameMaster
+    };
+
+    render! {
+        div {
+            padding: "1em",
+            h1 { "Export" },
+            label {
+                input {
+                    r#type: "checkbox",
+                    checked: "{players_only}",
+                    onchange: move |evt| players_only.set(evt.value == "true"),
+                },
+                " Only what the players may see"
+            },
+            h2 { "HTML wiki" },
+            p { "A page for every subject, place, group and event, with an index, a timeline and a search page. Open index.html in a browser, or put the folder on a web server." },
+            SecondaryButton {
+                onclick: move |_| {
+                    attempt!{ state {
+                        let Some(dir) = pick_folder(&state.read().user_dirs) else {
+                            return Ok(());
+                        };
+                        let db_path = state.read().db_path.clone().unwrap();
+                        let summary = export_html(&mut Schema::open(&db_path)?, &dir, audience)?;
+                        message.set(format!(
+                            "Wrote {} pages and {} images to {}",
+                            summary.pages,
+                            summary.images,
+                            dir.display()
+                        ));
+                        Ok(())
+                    }}
+                },
+                "Export wiki"
+            },
+            h2 { "Markdown vault" },
+            p { "A markdown file for every subject, place, group and event, in a folder per kind, with tags, fields and relationships in the front matter and images in an Attachments folder. Open the folder as a vault in Obsidian." },
+            SecondaryButton {
+                onclick: move |_| {
+                    attempt!{ state {
+                        let Some(dir) = pick_folder(&state.read().user_dirs) else {
+                            return Ok(());
+                        };
+                        let db_path = state.read().db_path.clone().unwrap();
+                        let summary = export_vault(&mut Schema::open(&db_path)?, &dir, audience)?;
+                        message.set(format!(
+                            "Wrote {} notes and {} images to {}",
+                            summary.notes,
+                            summary.images,
+                            dir.display()
+                        ));
+                        Ok(())
+                    }}
+                },
+                "Export vault"
+            },
+            p { "{message}" }
+        }
+    }
+}
+
+fn pick_folder(user_dirs: &directories::UserDirs) -> Option<std::path::PathBuf> {
+    rfd::FileDialog::new()
+        .set_directory(user_dirs.home_dir())
+        .pick_folder()
+}
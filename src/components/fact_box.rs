@@ -0,0 +1,178 @@
+#![allow(non_snake_case)]
+use anyhow::anyhow;
+use dioxus::prelude::*;
+
+use crate::{
+    attempt, comp_try,
+    components::color,
+    schema::v1::{EntityKind, EntityRef, Field, FieldType, FieldValue},
+    ActiveMode, Schema, State,
+};
+
+/// The custom fields of an entity as a small table next to the description. In edit mode
+/// fields can be removed and added. Adding a field with an existing name replaces its value.
+#[inline_props]
+pub fn FactBox(cx: Scope, entity: EntityRef) -> Element {
+    let state = use_shared_state::<State>(cx).unwrap();
+    let editing = use_state(cx, || false);
+    let new_name = use_state(cx, String::new);
+    let new_type = use_state(cx, || FieldType::Text);
+    let new_value = use_state(cx, String::new);
+    let db_path = state.read().db_path.clone().unwrap();
+    let player_view = state.read().player_view;
+    let audience = state.read().audience();
+    // the players neither edit nor get to pick hidden entities as references
+    let is_editing = *editing.get() && !player_view;
+    let mut db = comp_try!(state, Schema::open(&db_path));
+    let mut rows = vec![];
+    for field in comp_try!(state, db.visible_fields_of(*entity, audience)) {
+        let text = comp_try!(state, db.field_text(&field.value));
+        rows.push((field, text));
+    }
+    let mut names = vec![];
+    if is_editing && *new_type.get() == FieldType::Reference {
+        for kind in [EntityKind::Subject, EntityKind::Place, EntityKind::Group] {
+            names.extend(
+                comp_try!(state, db.query_names(kind, ""))
+                    .into_iter()
+                    .map(|x| x.t),
+            );
+        }
+    }
+    let list_id = format!("field-refs-{:?}-{}", entity.kind, entity.id);
+    let toggle = if is_editing { "Done" } else { "Edit" };
+    let value_input = match new_type.get() {
+        FieldType::Boolean => rsx!(select {
+            onchange: move |evt| new_value.set(evt.value.clone()),
+            option { value: "", "" },
+            option { value: "yes", "yes" },
+            option { value: "no", "no" },
+        }),
+        FieldType::Reference => rsx!(
+            input {
+                value: "{new_value}",
+                placeholder: "Name of a subject, place or group",
+                list: "{list_id}",
+                oninput: move |evt| new_value.set(evt.value.clone()),
+            },
+            datalist {
+                id: "{list_id}",
+                names.iter().map(|n| rsx!(option { key: "{n}", value: "{n}" }))
+            }
+        ),
+        _ => rsx!(input {
+            value: "{new_value}",
+            placeholder: "Value",
+            oninput: move |evt| new_value.set(evt.value.clone()),
+        }),
+    };
+
+    render! {
+        div {
+            float: "right",
+            width: "18em",
+            margin: "1em",
+            padding: "0.5em 1em",
+            background_color: color::GREY,
+            border_radius: "10px",
+            div {
+                display: "flex",
+                justify_content: "space-between",
+                align_items: "baseline",
+                h3 { margin: "0.3em 0", "Facts" },
+                if !player_view {
+                    rsx!(span {
+                        cursor: "pointer",
+                        text_decoration: "underline",
+                        onclick: move |_| editing.set(!*editing.get()),
+                        "{toggle}"
+                    })
+                }
+            },
+            table {
+                width: "100%",
+                rows.into_iter().map(|(field, text)| {
+                    let id = field.id;
+                    let target = match field.value {
+                        FieldValue::Reference(r) => ActiveMode::view(r.kind, text.clone()),
+                        _ => None,
+                    };
+                    rsx!(tr {
+                        key: "{id}",
+                        th { text_align: "left", vertical_align: "top", "{field.name}" },
+                        td {
+                            if let Some(target) = target {
+                                rsx!(span {
+                                    cursor: "pointer",
+                                    text_decoration: "underline",
+                                    onclick: move |_| state.write().set_active_mode(target.clone()),
+                                    "{text}"
+                                })
+                            } else {
+                                rsx!("{text}")
+                            }
+                        },
+                        if is_editing {
+                            rsx!(td {
+                                cursor: "pointer",
+                                title: "Remove field",
+                                onclick: move |_| {
+                                    attempt!{ state {
+                                        let db_path = state.read().db_path.clone().unwrap();
+                                        Schema::open(&db_path)?.delete_field(id)?;
+                                        cx.needs_update();
+                                        Ok(())
+                                    }}
+                                },
+                                "×"
+                            })
+                        }
+                    })
+                })
+            },
+            if is_editing {
+                rsx!(form {
+                    display: "flex",
+                    flex_direction: "column",
+                    gap: "0.3em",
+                    onsubmit: move |_| {
+                        attempt!{ state {
+                            let db_path = state.read().db_path.clone().unwrap();
+                            let mut db = Schema::open(&db_path)?;
+                            let name = new_name.trim();
+                            if name.is_empty() {
+                                return Err(anyhow!("Fields need a name"));
+                            }
+                            let value = db.parse_field_value(name, *new_type.get(), new_value.get())?;
+                            db.set_field(*entity, &Field { name: name.to_string(), value })?;
+                            new_name.set(String::new());
+                            new_value.set(String::new());
+                            Ok(())
+                        }}
+                    },
+                    input {
+                        value: "{new_name}",
+                        placeholder: "Name",
+                        oninput: move |evt| new_name.set(evt.value.clone()),
+                    },
+                    select {
+                        onchange: move |evt| {
+                            new_type.set(FieldType::from_name(&evt.value).unwrap_or(FieldType::Text))
+                        },
+                        FieldType::ALL.into_iter().map(|t| {
+                            let name = t.as_str();
+                            rsx!(option {
+                                key: "{name}",
+                                value: "{name}",
+                                selected: t == *new_type.get(),
+                                "{name}"
+                            })
+                        })
+                    },
+                    value_input,
+                    input { r#type: "submit", value: "Add" },
+                })
+            }
+        }
+    }
+}
@@ -0,0 +1,132 @@
+#![allow(non_snake_case)]
+use dioxus::prelude::*;
+
+use crate::{
+    attempt, comp_try,
+    components::{color, SecondaryButton},
+    images,
+    schema::v1::EntityRef,
+    Schema, State,
+};
+
+/// The portrait of an entity next to thumbnails of all its images. Clicking a thumbnail shows
+/// the image in full size, where it can be made the portrait or removed.
+#[inline_props]
+pub fn Gallery(cx: Scope, entity: EntityRef) -> Element {
+    let state = use_shared_state::<State>(cx).unwrap();
+    let selected = use_state(cx, || None::<i64>);
+    let db_path = state.read().db_path.clone().unwrap();
+    let mut db = comp_try!(state, Schema::open(&db_path));
+    let attachments = comp_try!(state, db.attachments_of(*entity));
+    let mut thumbs = vec![];
+    for a in &attachments {
+        let thumb = comp_try!(state, db.thumbnail(a.id)).unwrap_or_default();
+        thumbs.push((a.clone(), images::data_url("image/png", &thumb)));
+    }
+    let portrait = thumbs
+        .iter()
+        .find(|(a, _)| a.portrait)
+        .map(|(_, url)| url.clone());
+    let full = match selected.get() {
+        Some(id) => comp_try!(state, db.attachment_data(*id))
+            .map(|(mime, data)| (*id, images::data_url(&mime, &data))),
+        None => None,
+    };
+
+    render! {
+        div {
+            padding: "0 1em",
+            if let Some(url) = portrait {
+                rsx!(img {
+                    src: "{url}",
+                    max_width: "256px",
+                    max_height: "256px",
+                    border_radius: "10px",
+                })
+            },
+            div {
+                display: "flex",
+                flex_wrap: "wrap",
+                align_items: "center",
+                gap: "0.5em",
+                thumbs.into_iter().map(|(a, url)| {
+                    let id = a.id;
+                    let border = if *selected.get() == Some(id) { color::PRIMARY } else { color::GREY };
+                    rsx!(img {
+                        key: "{id}",
+                        src: "{url}",
+                        title: "{a.file_name}",
+                        max_width: "64px",
+                        max_height: "64px",
+                        cursor: "pointer",
+                        border: "2px solid {border}",
+                        onclick: move |_| {
+                            let next = if *selected.get() == Some(id) { None } else { Some(id) };
+                            selected.set(next);
+                        },
+                    })
+                }),
+                SecondaryButton {
+                    onclick: move |_| {
+                        attempt!{ state {
+                            let Some(paths) = pick_images() else {
+                                return Ok(());
+                            };
+                            let db_path = state.read().db_path.clone().unwrap();
+                            let mut db = Schema::open(&db_path)?;
+                            for path in paths {
+                                let data = std::fs::read(&path)?;
+                                let file_name = path
+                                    .file_name()
+                                    .map(|n| n.to_string_lossy().to_string())
+                                    .unwrap_or_default();
+                                db.attach_image(*entity, &data, &file_name)?;
+                            }
+                            cx.needs_update();
+                            Ok(())
+                        }}
+                    },
+                    "Attach image"
+                }
+            },
+            if let Some((id, url)) = full {
+                rsx!(div {
+                    margin: "0.5em 0",
+                    div {
+                        display: "flex",
+                        gap: "1em",
+                        SecondaryButton {
+                            onclick: move |_| {
+                                attempt!{ state {
+                                    let db_path = state.read().db_path.clone().unwrap();
+                                    Schema::open(&db_path)?.set_portrait(*entity, id)?;
+                                    cx.needs_update();
+                                    Ok(())
+                                }}
+                            },
+                            "Set as portrait"
+                        },
+                        SecondaryButton {
+                            onclick: move |_| {
+                                attempt!{ state {
+                                    let db_path = state.read().db_path.clone().unwrap();
+                                    Schema::open(&db_path)?.detach_image(*entity, id)?;
+                                    selected.set(None);
+                                    Ok(())
+                                }}
+                            },
+                            "Remove"
+                        },
+                    },
+                    img { src: "{url}", max_width: "100%" }
+                })
+            }
+        }
+    }
+}
+
+fn pick_images() -> Option<Vec<std::path::PathBuf>> {
+    rfd::FileDialog::new()
+        .add_filter("Images", &["png", "jpg", "jpeg", "webp"])
+        .pick_files()
+}
@@ -0,0 +1,45 @@
+#![allow(non_snake_case)]
+use dioxus::prelude::*;
+
+use crate::{
+    comp_try,
+    components::{
+        Backlinks, FactBox, Gallery, Markdown, Related, Rename, SecretNotice, SecretToggle,
+        TagEditor,
+    },
+    schema::v1::{EntityKind, EntityRef},
+    wiki, Schema, State,
+};
+
+#[inline_props]
+pub fn Group(cx: Scope, name: String) -> Element {
+    let state = use_shared_state::<State>(cx).unwrap();
+    let db_path = state.read().db_path.clone().unwrap();
+    let mut db = comp_try!(state, Schema::open(&db_path));
+    let group =
+        comp_try!(state, db.get_group_by_name(name)).expect("Trying to display non existing group");
+    let entity = EntityRef::new(EntityKind::Group, group.id);
+    let audience = state.read().audience();
+    if !comp_try!(state, db.is_visible(entity, audience)) {
+        return render! { SecretNotice {} };
+    }
+    let html = comp_try!(
+        state,
+        wiki::render_markdown(&mut db, &group.description, audience)
+    );
+
+    render! {
+        div {
+            padding: "1em",
+            h1 { "{group.name}" },
+            Rename { entity: entity, name: group.name.clone() }
+        }
+        SecretToggle { entity: entity }
+        FactBox { entity: entity }
+        Gallery { entity: entity }
+        TagEditor { entity: entity }
+        Markdown { html: html }
+        Related { entity: entity }
+        Backlinks { entity: entity }
+    }
+}
@@ -0,0 +1,137 @@
+#![allow(non_snake_case)]
+use dioxus::prelude::*;
+
+use crate::{
+    attempt, comp_try,
+    components::SecondaryButton,
+    diff::{line_diff, DiffLine},
+    schema::{v1::EntityRef, v1::Revision, WithId},
+    Schema, State,
+};
+
+/// The revisions of an entity's description. Two revisions can be picked as A and B to see a
+/// diff between them, and any revision can be restored.
+#[inline_props]
+pub fn History(cx: Scope, entity: EntityRef) -> Element {
+    let state = use_shared_state::<State>(cx).unwrap();
+    let show = use_state(cx, || false);
+    let a = use_state(cx, || None::<i64>);
+    let b = use_state(cx, || None::<i64>);
+
+    if !*show.get() {
+        return render! {
+            div {
+                padding: "1em",
+                SecondaryButton { onclick: move |_| show.set(true), "History" }
+            }
+        };
+    }
+
+    let db_path = state.read().db_path.clone().unwrap();
+    let mut db = comp_try!(state, Schema::open(&db_path));
+    let revs = comp_try!(state, db.list_revisions(*entity));
+    let find = |id: &Option<i64>| id.and_then(|id| revs.iter().find(|r| r.id == id));
+    let diff = match (find(a.get()), find(b.get())) {
+        (Some(a), Some(b)) => Some(line_diff(&a.description, &b.description)),
+        _ => None,
+    };
+
+    render! {
+        div {
+            padding: "1em",
+            h2 { "History" },
+            table {
+                revs.iter().map(|rev| rsx!(RevisionRow {
+                    key: "{rev.id}",
+                    rev: rev.clone(),
+                    is_a: *a.get() == Some(rev.id),
+                    is_b: *b.get() == Some(rev.id),
+                    on_a: move |_| a.set(Some(rev.id)),
+                    on_b: move |_| b.set(Some(rev.id)),
+                }))
+            },
+            if let Some(diff) = diff {
+                rsx!(Diff { lines: diff })
+            }
+        }
+    }
+}
+
+#[inline_props]
+fn RevisionRow<'a>(
+    cx: Scope<'a>,
+    rev: WithId<Revision>,
+    is_a: bool,
+    is_b: bool,
+    on_a: EventHandler<'a, MouseEvent>,
+    on_b: EventHandler<'a, MouseEvent>,
+) -> Element<'a> {
+    let state = use_shared_state::<State>(cx).unwrap();
+    let weight = |selected: bool| if selected { "bold" } else { "normal" };
+    render! {
+        tr {
+            td { padding_right: "1em", format_timestamp(rev.updated_at) },
+            td {
+                button { font_weight: weight(*is_a), onclick: move |evt| on_a.call(evt), "A" }
+            },
+            td {
+                button { font_weight: weight(*is_b), onclick: move |evt| on_b.call(evt), "B" }
+            },
+            td {
+                button {
+                    onclick: move |_| {
+                        attempt!{ state {
+                            let db_path = state.read().db_path.clone().unwrap();
+                            Schema::open(&db_path)?.revert_to_revision(rev)?;
+                            // setting the same mode again triggers a rerender with the new text
+                            let mode = state.read().mode.clone();
+                            state.write().set_mode(mode);
+                            Ok(())
+                        }}
+                    },
+                    "Revert"
+                }
+            },
+        }
+    }
+}
+
+#[inline_props]
+fn Diff(cx: Scope, lines: Vec<DiffLine>) -> Element {
+    render! {
+        pre {
+            padding: "1em",
+            font_family: "monospace",
+            user_select: "text",
+            lines.iter().map(|l| {
+                let (prefix, color, text) = match l {
+                    DiffLine::Same(t) => (" ", "inherit", t),
+                    DiffLine::Added(t) => ("+", "green", t),
+                    DiffLine::Removed(t) => ("-", "red", t),
+                };
+                rsx!(div { color: color, "{prefix} {text}" })
+            })
+        }
+    }
+}
+
+/// Formats a unix timestamp as UTC date and time, e.g. `2023-07-14 18:03 UTC`
+fn format_timestamp(ts: i64) -> String {
+    let days = ts.div_euclid(86400);
+    let secs = ts.rem_euclid(86400);
+    // civil from days, see http://howardhinnant.github.io/date_algorithms.html
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z.rem_euclid(146097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    format!(
+        "{year:04}-{month:02}-{day:02} {:02}:{:02} UTC",
+        secs / 3600,
+        secs % 3600 / 60
+    )
+}
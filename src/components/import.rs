@@ -0,0 +1,65 @@
+#![allow(non_snake_case)]
+use dioxus::prelude::*;
+
+use crate::{
+    attempt,
+    components::SecondaryButton,
+    vault::{import_vault, ImportReport},
+    Schema, State,
+};
+
+/// Brings notes from other apps into the campaign
+pub fn Import(cx: Scope) -> Element {
+    let state = use_shared_state::<State>(cx).unwrap();
+    let report = use_state(cx, || None::<ImportReport>);
+
+    render! {
+        div {
+            padding: "1em",
+            h1 { "Import" },
+            h2 { "Markdown vault" },
+            p { "Every markdown file in the folder and its subfolders becomes a subject, place, group or event named like the file. The kind is taken from a kind or type key in the front matter, or else from folder names like Places or Factions. Tags, a parent, relationships and other properties in the front matter are imported, wikilinks become links, and embedded images are attached." },
+            SecondaryButton {
+                onclick: move |_| {
+                    attempt!{ state {
+                        let Some(dir) = rfd::FileDialog::new()
+                            .set_directory(state.read().user_dirs.home_dir())
+                            .pick_folder()
+                        else {
+                            return Ok(());
+                        };
+                        let db_path = state.read().db_path.clone().unwrap();
+                        report.set(Some(import_vault(&mut Schema::open(&db_path)?, &dir)?));
+                        Ok(())
+                    }}
+                },
+                "Import vault"
+            },
+            report.get().as_ref().map(|report| rsx!(
+                p { "Created {report.created} entries and attached {report.images} images." },
+                if !report.conflicts.is_empty() {
+                    rsx!(
+                        h3 { "Not imported" },
+                        ul {
+                            report.conflicts.iter().enumerate().map(|(i, (note, reason))| rsx!(li {
+                                key: "{i}",
+                                "{note}: {reason}"
+                            }))
+                        }
+                    )
+                },
+                if !report.unresolved.is_empty() {
+                    rsx!(
+                        h3 { "Links to nothing" },
+                        ul {
+                            report.unresolved.iter().enumerate().map(|(i, (note, target))| rsx!(li {
+                                key: "{i}",
+                                "{note}: {target}"
+                            }))
+                        }
+                    )
+                },
+            ))
+        }
+    }
+}
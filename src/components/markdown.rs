@@ -0,0 +1,67 @@
+#![allow(non_snake_case)]
+use dioxus::prelude::*;
+
+use crate::{
+    attempt,
+    schema::v1::EntityKind,
+    wiki::{parse_url, LinkTarget},
+    ActiveMode, Mode, Schema, State,
+};
+
+/// Displays rendered markdown, and follows the wiki links in it. The html must come from
+/// [`crate::wiki::render_markdown`].
+#[inline_props]
+pub fn Markdown(cx: Scope, html: String) -> Element {
+    let state = use_shared_state::<State>(cx).unwrap();
+    render! {
+        div {
+            class: "wiki-root",
+            // receives the url of clicked wiki links, see wiki::LINK_HANDLER_JS
+            input {
+                class: "wiki-nav",
+                display: "none",
+                oninput: move |evt| {
+                    attempt!{ state {
+                        if let Some(target) = parse_url(&evt.value) {
+                            follow_link(state, target)?;
+                        }
+                        Ok(())
+                    }}
+                },
+            },
+            div {
+                dangerous_inner_html: "{html}"
+            }
+        }
+    }
+}
+
+fn follow_link(state: &UseSharedState<State>, target: LinkTarget) -> anyhow::Result<()> {
+    let entity = match target {
+        LinkTarget::Entity(entity) => entity,
+        LinkTarget::Missing(name) => {
+            // the editor creates the subject
+            state.write().set_mode(Mode::EditingSubject(name));
+            return Ok(());
+        }
+    };
+    let db_path = state.read().db_path.clone().unwrap();
+    let mut db = Schema::open(&db_path)?;
+    let mode = match entity.kind {
+        EntityKind::Subject => db
+            .get_subject(entity.id)?
+            .map(|x| ActiveMode::Subject(x.t.name)),
+        EntityKind::Place => db
+            .get_place(entity.id)?
+            .map(|x| ActiveMode::Place(x.t.name)),
+        EntityKind::Group => db
+            .get_group(entity.id)?
+            .map(|x| ActiveMode::Group(x.t.name)),
+        EntityKind::Event => Some(ActiveMode::Events),
+        EntityKind::Tag => None,
+    };
+    if let Some(mode) = mode {
+        state.write().set_active_mode(mode);
+    }
+    Ok(())
+}
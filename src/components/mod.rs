@@ -20,6 +20,27 @@ pub use events::Events;
 mod new_subject;
 pub use new_subject::NewSubject;
 
+mod new_entity;
+pub use new_entity::*;
+
+mod new_event;
+pub use new_event::*;
+
+mod templates;
+pub use templates::*;
+
+mod calendar;
+pub use calendar::*;
+
+mod sessions;
+pub use sessions::*;
+
+mod export;
+pub use export::*;
+
+mod import;
+pub use import::*;
+
 mod button;
 pub use button::*;
 
@@ -34,3 +55,48 @@ pub use search::*;
 
 mod bimg;
 pub use bimg::*;
+
+mod backlinks;
+pub use backlinks::*;
+
+mod tags;
+pub use tags::*;
+
+mod fact_box;
+pub use fact_box::*;
+
+mod gallery;
+pub use gallery::*;
+
+mod place_map;
+pub use place_map::*;
+
+mod related;
+pub use related::*;
+
+mod relationships;
+pub use relationships::*;
+
+mod confirm_delete;
+pub use confirm_delete::*;
+
+mod trash;
+pub use trash::*;
+
+mod history;
+pub use history::*;
+
+mod rename;
+pub use rename::*;
+
+mod secret;
+pub use secret::*;
+
+mod markdown;
+pub use markdown::*;
+
+mod place;
+pub use place::*;
+
+mod group;
+pub use group::*;
@@ -0,0 +1,60 @@
+#![allow(non_snake_case)]
+use dioxus::prelude::*;
+
+use crate::{
+    attempt,
+    components::{apply_chosen_template, TemplateSelect},
+    schema::v1::{EntityKind, EntityRef, Group, Place},
+    ActiveMode, Schema, State,
+};
+
+/// Creates a place or a group, and shows it afterwards
+#[inline_props]
+pub fn NewEntity(cx: Scope, kind: EntityKind) -> Element {
+    let state = use_shared_state::<State>(cx).unwrap();
+    render! {
+        form {
+            width: "100%",
+            height: "100%",
+            margin: 0,
+            padding: 0,
+            display: "flex",
+            flex_direction: "row",
+            align_items: "center",
+            justify_content: "center",
+            gap: "1em",
+            onsubmit: move |ev| {
+                attempt!{ state {
+                    let name = ev.data.values["name"][0].trim().to_string();
+                    let db_path = state.read().db_path.clone().unwrap();
+                    let mut db = Schema::open(&db_path)?;
+                    db.in_transaction(|db| {
+                        // both fail if the name is already taken
+                        let id = match kind {
+                            EntityKind::Place => db.insert_place(&Place {
+                                name: name.clone(),
+                                description: "".into(),
+                                parent_place: None,
+                            })?,
+                            EntityKind::Group => db.insert_group(&Group {
+                                name: name.clone(),
+                                description: "".into(),
+                                parent_group: None,
+                            })?,
+                            _ => unreachable!("NewEntity only creates places and groups"),
+                        };
+                        apply_chosen_template(db, EntityRef::new(*kind, id), &ev.data.values)
+                    })?;
+                    if let Some(mode) = ActiveMode::view(*kind, name) {
+                        state.write().set_active_mode(mode);
+                    }
+                    Ok(())
+                }}
+            },
+            label { "{kind} name" },
+            input { name: "name" },
+            TemplateSelect { kind: *kind },
+            input { r#type: "submit" },
+        }
+    }
+}
@@ -0,0 +1,58 @@
+#![allow(non_snake_case)]
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use anyhow::anyhow;
+use dioxus::prelude::*;
+
+use crate::{
+    attempt,
+    schema::v1::{EntityKind, EntityRef, Event},
+    wiki, ActiveMode, Schema, State,
+};
+
+/// Records an event with the links made from its description, and shows the timeline
+/// afterwards. The in-world date is read with the campaign's calendar, dates it doesn't
+/// understand are kept as text.
+pub fn NewEvent(cx: Scope) -> Element {
+    let state = use_shared_state::<State>(cx).unwrap();
+    render! {
+        form {
+            padding: "1em",
+            display: "flex",
+            flex_direction: "column",
+            gap: "0.5em",
+            onsubmit: move |ev| {
+                attempt!{ state {
+                    let values = &ev.data.values;
+                    let date = values["date"][0].trim().to_string();
+                    if date.is_empty() {
+                        return Err(anyhow!("An event needs a date"));
+                    }
+                    let record_date = SystemTime::now()
+                        .duration_since(UNIX_EPOCH)
+                        .map(|d| d.as_secs())
+                        .unwrap_or(0);
+                    let description = values["description"][0].clone();
+                    let db_path = state.read().db_path.clone().unwrap();
+                    Schema::open(&db_path)?.in_transaction(|db| {
+                        let id = db.insert_event(&Event {
+                            record_date,
+                            refered_date: date,
+                            description: description.clone(),
+                            date: None,
+                            session: None,
+                        })?;
+                        wiki::sync_links(db, EntityRef::new(EntityKind::Event, id), &description)
+                    })?;
+                    state.write().set_active_mode(ActiveMode::Events);
+                    Ok(())
+                }}
+            },
+            label { "Date in the world" },
+            input { name: "date" },
+            label { "What happened" },
+            textarea { name: "description", rows: "15" },
+            input { r#type: "submit" },
+        }
+    }
+}
@@ -1,7 +1,12 @@
 #![allow(non_snake_case)]
 use dioxus::prelude::*;
 
-use crate::{Mode, State};
+use crate::{
+    attempt,
+    components::{apply_chosen_template, TemplateSelect},
+    schema::v1::{EntityKind, EntityRef, Subject},
+    Mode, Schema, State,
+};
 
 pub fn NewSubject(cx: Scope) -> Element {
     let state = use_shared_state::<State>(cx).unwrap();
@@ -17,12 +22,31 @@ pub fn NewSubject(cx: Scope) -> Element {
             justify_content: "center",
             gap: "1em",
             onsubmit: move |ev| {
-                state.write().mode = Mode::EditingSubject(ev.data.values["subject_name"][0].to_string());
+                attempt!{ state {
+                    let name = ev.data.values["subject_name"][0].trim().to_string();
+                    let db_path = state.read().db_path.clone().unwrap();
+                    let mut db = Schema::open(&db_path)?;
+                    db.in_transaction(|db| {
+                        // fails if the name is already taken
+                        let id = db.insert_subject(&Subject {
+                            name: name.clone(),
+                            description: "".into(),
+                        })?;
+                        apply_chosen_template(
+                            db,
+                            EntityRef::new(EntityKind::Subject, id),
+                            &ev.data.values,
+                        )
+                    })?;
+                    state.write().mode = Mode::EditingSubject(name);
+                    Ok(())
+                }}
             },
             label {
                 "SubjectId"
             },
             input { name: "subject_name" },
+            TemplateSelect { kind: EntityKind::Subject },
             input { r#type: "submit" },
         }
     }
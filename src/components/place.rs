@@ -0,0 +1,65 @@
+#![allow(non_snake_case)]
+use dioxus::prelude::*;
+
+use crate::{
+    comp_try,
+    components::{
+        Backlinks, FactBox, Gallery, Markdown, PlaceMap, Related, Rename, SecretNotice,
+        SecretToggle, TagEditor,
+    },
+    schema::v1::{EntityKind, EntityRef},
+    wiki, ActiveMode, Schema, State,
+};
+
+#[inline_props]
+pub fn Place(cx: Scope, name: String) -> Element {
+    let state = use_shared_state::<State>(cx).unwrap();
+    let db_path = state.read().db_path.clone().unwrap();
+    let mut db = comp_try!(state, Schema::open(&db_path));
+    let place =
+        comp_try!(state, db.get_place_by_name(name)).expect("Trying to display non existing place");
+    let mut path = comp_try!(state, db.place_path(place.id));
+    let entity = EntityRef::new(EntityKind::Place, place.id);
+    let audience = state.read().audience();
+    if !comp_try!(state, db.is_visible(entity, audience)) {
+        return render! { SecretNotice {} };
+    }
+    let html = comp_try!(
+        state,
+        wiki::render_markdown(&mut db, &place.description, audience)
+    );
+    let mut visible_path = vec![];
+    for p in path.drain(..) {
+        if comp_try!(
+            state,
+            db.is_visible(EntityRef::new(EntityKind::Place, p.id), audience)
+        ) {
+            visible_path.push(p);
+        }
+    }
+    let path = visible_path;
+
+    render! {
+        div {
+            padding: "1em",
+            // breadcrumbs
+            p {
+                path.iter().map(|p| rsx!(span {
+                    key: "{p.id}",
+                    cursor: "pointer",
+                    onclick: move |_| state.write().set_active_mode(ActiveMode::Place(p.name.clone())),
+                    " / {p.name}"
+                }))
+            },
+            Rename { entity: entity, name: place.name.clone() }
+        }
+        SecretToggle { entity: entity }
+        FactBox { entity: entity }
+        Gallery { entity: entity }
+        TagEditor { entity: entity }
+        Markdown { html: html }
+        PlaceMap { place: place.id }
+        Related { entity: entity }
+        Backlinks { entity: entity }
+    }
+}
@@ -0,0 +1,258 @@
+#![allow(non_snake_case)]
+use anyhow::anyhow;
+use dioxus::prelude::*;
+
+use crate::{
+    attempt, comp_try,
+    components::{color, SecondaryButton},
+    images,
+    schema::v1::{EntityKind, EntityRef, MapPin},
+    ActiveMode, Schema, State,
+};
+
+const MIN_ZOOM: f64 = 0.25;
+const MAX_ZOOM: f64 = 8.0;
+
+/// The map of a place with its pins. The map can be dragged around and zoomed with the mouse
+/// wheel. Clicking a pin opens the entity it points to. In edit mode a click on the map drops
+/// a pin for the chosen subject or place, and clicking a pin removes it.
+#[inline_props]
+pub fn PlaceMap(cx: Scope, place: i64) -> Element {
+    let state = use_shared_state::<State>(cx).unwrap();
+    let zoom = use_state(cx, || 1.0_f64);
+    let offset = use_state(cx, || (0.0_f64, 0.0_f64));
+    // last mouse position while the map is dragged
+    let drag = use_state(cx, || None::<(f64, f64)>);
+    let editing = use_state(cx, || false);
+    let target = use_state(cx, String::new);
+    let db_path = state.read().db_path.clone().unwrap();
+    let mut db = comp_try!(state, Schema::open(&db_path));
+
+    let Some(map) = comp_try!(state, db.place_map(*place)) else {
+        return render! {
+            div {
+                padding: "0 1em",
+                SecondaryButton {
+                    onclick: move |_| {
+                        attempt!{ state {
+                            let Some(path) = pick_map() else {
+                                return Ok(());
+                            };
+                            let data = std::fs::read(&path)?;
+                            let file_name = path
+                                .file_name()
+                                .map(|n| n.to_string_lossy().to_string())
+                                .unwrap_or_default();
+                            let db_path = state.read().db_path.clone().unwrap();
+                            Schema::open(&db_path)?.set_place_map(*place, &data, &file_name)?;
+                            cx.needs_update();
+                            Ok(())
+                        }}
+                    },
+                    "Add map"
+                }
+            }
+        };
+    };
+    let Some((mime, data)) = comp_try!(state, db.attachment_data(map)) else {
+        return None;
+    };
+    let url = images::data_url(&mime, &data);
+    let (width, height) = comp_try!(state, image_size(&data));
+
+    let audience = state.read().audience();
+    let mut pins = vec![];
+    for pin in comp_try!(state, db.map_pins(*place)) {
+        if !comp_try!(state, db.is_visible(pin.target, audience)) {
+            continue;
+        }
+        if let Some(label) = comp_try!(state, db.entity_label(pin.target)) {
+            pins.push((pin, label));
+        }
+    }
+    // the choices for new pins, values are `table:id`
+    let mut targets = vec![];
+    if *editing.get() {
+        for (depth, p) in comp_try!(state, db.place_subtree(*place)) {
+            let indent = "\u{a0}\u{a0}".repeat(depth - 1);
+            targets.push((format!("places:{}", p.id), format!("{indent}{}", p.name)));
+        }
+        for s in comp_try!(state, db.query_names(EntityKind::Subject, "")) {
+            targets.push((format!("subjects:{}", s.id), s.t));
+        }
+    }
+
+    let (dx, dy) = *offset.get();
+    let transform = format!("translate({dx}px, {dy}px) scale({})", zoom.get());
+    let cursor = if *editing.get() { "crosshair" } else { "grab" };
+    let toggle = if *editing.get() { "Done" } else { "Edit pins" };
+
+    render! {
+        div {
+            padding: "0 1em",
+            div {
+                display: "flex",
+                gap: "1em",
+                align_items: "center",
+                margin: "0.5em 0",
+                SecondaryButton { onclick: move |_| zoom.set((zoom.get() * 1.25).min(MAX_ZOOM)), "+" },
+                SecondaryButton { onclick: move |_| zoom.set((zoom.get() / 1.25).max(MIN_ZOOM)), "−" },
+                SecondaryButton {
+                    onclick: move |_| {
+                        zoom.set(1.0);
+                        offset.set((0.0, 0.0));
+                    },
+                    "Reset"
+                },
+                SecondaryButton { onclick: move |_| editing.set(!*editing.get()), "{toggle}" },
+                if *editing.get() {
+                    rsx!(
+                        select {
+                            onchange: move |evt| target.set(evt.value.clone()),
+                            option { value: "", "Pin what?" },
+                            targets.iter().map(|(value, label)| rsx!(option {
+                                key: "{value}",
+                                value: "{value}",
+                                selected: *value == **target.get(),
+                                "{label}"
+                            }))
+                        },
+                        SecondaryButton {
+                            onclick: move |_| {
+                                attempt!{ state {
+                                    let db_path = state.read().db_path.clone().unwrap();
+                                    Schema::open(&db_path)?.remove_place_map(*place)?;
+                                    editing.set(false);
+                                    Ok(())
+                                }}
+                            },
+                            "Remove map"
+                        }
+                    )
+                }
+            },
+            div {
+                position: "relative",
+                overflow: "hidden",
+                height: "60vh",
+                background_color: color::GREY,
+                border_radius: "10px",
+                cursor: "{cursor}",
+                onmousedown: move |evt| {
+                    if !*editing.get() {
+                        let p = evt.client_coordinates();
+                        drag.set(Some((p.x, p.y)));
+                    }
+                },
+                onmousemove: move |evt| {
+                    if let Some((x, y)) = *drag.get() {
+                        let p = evt.client_coordinates();
+                        let (dx, dy) = *offset.get();
+                        offset.set((dx + p.x - x, dy + p.y - y));
+                        drag.set(Some((p.x, p.y)));
+                    }
+                },
+                onmouseup: move |_| drag.set(None),
+                onmouseleave: move |_| drag.set(None),
+                onwheel: move |evt| {
+                    let factor = if evt.delta().strip_units().y < 0.0 { 1.1 } else { 1.0 / 1.1 };
+                    zoom.set((zoom.get() * factor).clamp(MIN_ZOOM, MAX_ZOOM));
+                },
+                div {
+                    position: "absolute",
+                    left: "0",
+                    top: "0",
+                    width: "{width}px",
+                    height: "{height}px",
+                    transform: "{transform}",
+                    transform_origin: "0 0",
+                    img {
+                        src: "{url}",
+                        width: "{width}",
+                        height: "{height}",
+                        draggable: "false",
+                        onclick: move |evt| {
+                            if !*editing.get() {
+                                return;
+                            }
+                            attempt!{ state {
+                                let target = parse_target(target.get())
+                                    .ok_or_else(|| anyhow!("Choose what to pin first"))?;
+                                // element coordinates are in the untransformed image's pixels
+                                let p = evt.element_coordinates();
+                                let pin = MapPin {
+                                    target,
+                                    x: p.x / width as f64,
+                                    y: p.y / height as f64,
+                                };
+                                let db_path = state.read().db_path.clone().unwrap();
+                                Schema::open(&db_path)?.add_map_pin(*place, &pin)?;
+                                cx.needs_update();
+                                Ok(())
+                            }}
+                        },
+                    },
+                    pins.into_iter().map(|(pin, label)| {
+                        let id = pin.id;
+                        let left = pin.x * 100.0;
+                        let top = pin.y * 100.0;
+                        let view = ActiveMode::view(pin.target.kind, label.clone());
+                        let title = if *editing.get() { format!("Remove pin of {label}") } else { label.clone() };
+                        rsx!(div {
+                            key: "{id}",
+                            position: "absolute",
+                            left: "{left}%",
+                            top: "{top}%",
+                            transform: "translate(-50%, -100%)",
+                            cursor: "pointer",
+                            title: "{title}",
+                            white_space: "nowrap",
+                            onmousedown: move |evt| evt.stop_propagation(),
+                            onclick: move |_| {
+                                if *editing.get() {
+                                    attempt!{ state {
+                                        let db_path = state.read().db_path.clone().unwrap();
+                                        Schema::open(&db_path)?.delete_map_pin(id)?;
+                                        cx.needs_update();
+                                        Ok(())
+                                    }}
+                                } else if let Some(view) = view.clone() {
+                                    state.write().set_active_mode(view);
+                                }
+                            },
+                            span { font_size: "1.5em", "📍" },
+                            span {
+                                background_color: color::WHITE,
+                                border_radius: "4px",
+                                padding: "0 0.2em",
+                                "{label}"
+                            }
+                        })
+                    })
+                }
+            }
+        }
+    }
+}
+
+/// Parses the `table:id` values of the target select
+fn parse_target(value: &str) -> Option<EntityRef> {
+    let (table, id) = value.split_once(':')?;
+    Some(EntityRef::new(
+        EntityKind::from_table(table)?,
+        id.parse().ok()?,
+    ))
+}
+
+/// Width and height of an image, without decoding all of it
+fn image_size(data: &[u8]) -> anyhow::Result<(u32, u32)> {
+    Ok(image::io::Reader::new(std::io::Cursor::new(data))
+        .with_guessed_format()?
+        .into_dimensions()?)
+}
+
+fn pick_map() -> Option<std::path::PathBuf> {
+    rfd::FileDialog::new()
+        .add_filter("Images", &["png", "jpg", "jpeg", "webp"])
+        .pick_file()
+}
@@ -0,0 +1,84 @@
+#![allow(non_snake_case)]
+use dioxus::prelude::*;
+
+use crate::{
+    comp_try,
+    schema::v1::{EntityKind, EntityRef, Linked},
+    ActiveMode, Schema, State,
+};
+
+/// Lists the subjects, groups and places an entity is linked with, in both directions.
+/// Relationships between subjects are left out, they are shown by [`super::Relationships`].
+#[inline_props]
+pub fn Related(cx: Scope, entity: EntityRef) -> Element {
+    let state = use_shared_state::<State>(cx).unwrap();
+    let db_path = state.read().db_path.clone().unwrap();
+    let mut db = comp_try!(state, Schema::open(&db_path));
+    let audience = state.read().audience();
+    let mut links = comp_try!(state, db.outgoing_links(*entity));
+    for l in comp_try!(state, db.incoming_links(*entity)) {
+        if !links.iter().any(|x| x.entity == l.entity) {
+            links.push(l);
+        }
+    }
+    links.retain(|l| audience.sees(l.secret));
+
+    let sections = [
+        (EntityKind::Subject, "Subjects"),
+        (EntityKind::Group, "Groups"),
+        (EntityKind::Place, "Places"),
+    ]
+    .into_iter()
+    // relations between subjects are shown by the Relationships component
+    .filter(|(kind, _)| !(*kind == EntityKind::Subject && entity.kind == EntityKind::Subject))
+    .map(|(kind, title)| {
+        let mut entries = links
+            .iter()
+            .filter(|l| l.entity.kind == kind)
+            .cloned()
+            .collect::<Vec<_>>();
+        entries.sort_by(|a, b| a.label.cmp(&b.label));
+        (title, entries)
+    })
+    .filter(|(_, entries)| !entries.is_empty())
+    .collect::<Vec<_>>();
+
+    if sections.is_empty() {
+        return None;
+    }
+
+    render! {
+        div {
+            padding: "1em",
+            h2 { "Related" },
+            sections.into_iter().map(|(title, entries)| rsx!(
+                div {
+                    key: "{title}",
+                    h3 { "{title}" },
+                    ul {
+                        entries.into_iter().map(|l| rsx!(RelatedEntry { link: l }))
+                    }
+                }
+            ))
+        }
+    }
+}
+
+#[inline_props]
+fn RelatedEntry(cx: Scope, link: Linked) -> Element {
+    let state = use_shared_state::<State>(cx).unwrap();
+    if link.entity.kind == EntityKind::Subject {
+        render! {
+            li {
+                cursor: "pointer",
+                text_decoration: "underline",
+                onclick: move |_| {
+                    state.write().set_active_mode(ActiveMode::Subject(link.label.clone()))
+                },
+                link.label.clone()
+            }
+        }
+    } else {
+        render! { li { link.label.clone() } }
+    }
+}
@@ -0,0 +1,269 @@
+#![allow(non_snake_case)]
+use anyhow::anyhow;
+use dioxus::prelude::*;
+
+use crate::{
+    attempt, comp_try,
+    components::SecondaryButton,
+    schema::{
+        v1::{EntityKind, EntityRef, RelationView, Relationship},
+        WithId,
+    },
+    ActiveMode, Schema, State,
+};
+
+/// The relationships of a subject to other subjects, grouped by their label. In the GM view
+/// they can be added, edited and deleted.
+#[inline_props]
+pub fn Relationships(cx: Scope, subject: i64) -> Element {
+    let state = use_shared_state::<State>(cx).unwrap();
+    // None: nothing is edited, Some(None): a new relationship
+    let editing = use_state(cx, || None::<Option<RelationView>>);
+    let db_path = state.read().db_path.clone().unwrap();
+    let mut db = comp_try!(state, Schema::open(&db_path));
+    let audience = state.read().audience();
+    let player_view = state.read().player_view;
+
+    if let Some(rel) = editing.get() {
+        if !player_view {
+            return render! {
+                RelationshipForm {
+                    subject: *subject,
+                    rel: rel.clone(),
+                    ondone: move |_| editing.set(None),
+                }
+            };
+        }
+    }
+
+    let mut rels = vec![];
+    for rel in comp_try!(state, db.relationships_of(*subject)) {
+        let other = EntityRef::new(EntityKind::Subject, rel.other_id);
+        if audience.sees(rel.rel.secret) && comp_try!(state, db.is_visible(other, audience)) {
+            rels.push(rel);
+        }
+    }
+    if rels.is_empty() && player_view {
+        return None;
+    }
+
+    // relationships_of() sorts by label, so equal labels are next to each other
+    let mut groups: Vec<(String, Vec<RelationView>)> = vec![];
+    for rel in rels {
+        let label = rel.label();
+        match groups.last_mut() {
+            Some((l, entries)) if *l == label => entries.push(rel),
+            _ => groups.push((label, vec![rel])),
+        }
+    }
+
+    render! {
+        div {
+            padding: "1em",
+            h2 { "Relationships" },
+            groups.into_iter().map(|(label, entries)| {
+                let title = if label.is_empty() { "Linked".to_string() } else { label };
+                rsx!(div {
+                    key: "{title}",
+                    h3 { "{title}" },
+                    ul {
+                        entries.into_iter().map(|rel| rsx!(RelationshipEntry {
+                            rel: rel,
+                            onedit: move |rel| editing.set(Some(Some(rel))),
+                            ondelete: move |id| {
+                                attempt!{ state {
+                                    let db_path = state.read().db_path.clone().unwrap();
+                                    Schema::open(&db_path)?.delete_relationship(id)?;
+                                    cx.needs_update();
+                                    Ok(())
+                                }}
+                            },
+                        }))
+                    }
+                })
+            })
+            if !player_view {
+                rsx!(SecondaryButton {
+                    onclick: move |_| editing.set(Some(None)),
+                    "Add relationship"
+                })
+            }
+        }
+    }
+}
+
+#[inline_props]
+fn RelationshipEntry<'a>(
+    cx: Scope<'a>,
+    rel: RelationView,
+    onedit: EventHandler<'a, RelationView>,
+    ondelete: EventHandler<'a, i64>,
+) -> Element<'a> {
+    let state = use_shared_state::<State>(cx).unwrap();
+    let period = match (&rel.rel.valid_from, &rel.rel.valid_to) {
+        (None, None) => "".to_string(),
+        (Some(from), None) => format!(" (since {from})"),
+        (None, Some(to)) => format!(" (until {to})"),
+        (Some(from), Some(to)) => format!(" ({from} – {to})"),
+    };
+    let player_view = state.read().player_view;
+    let id = rel.rel.id;
+    let secret = rel.rel.secret;
+    render! {
+        li {
+            span {
+                cursor: "pointer",
+                text_decoration: "underline",
+                onclick: move |_| {
+                    state.write().set_active_mode(ActiveMode::Subject(rel.other_name.clone()))
+                },
+                rel.other_name.clone()
+            },
+            "{period}",
+            if !player_view {
+                rsx!(
+                    label {
+                        margin_left: "1em",
+                        font_size: "small",
+                        color: "grey",
+                        input {
+                            r#type: "checkbox",
+                            checked: "{secret}",
+                            onchange: move |evt| {
+                                attempt!{ state {
+                                    let db_path = state.read().db_path.clone().unwrap();
+                                    Schema::open(&db_path)?.set_relationship_secret(id, evt.value == "true")?;
+                                    Ok(())
+                                }}
+                            },
+                        },
+                        " secret"
+                    },
+                    span {
+                        margin_left: "1em",
+                        font_size: "small",
+                        cursor: "pointer",
+                        text_decoration: "underline",
+                        onclick: move |_| onedit.call(rel.clone()),
+                        "edit"
+                    },
+                    span {
+                        margin_left: "0.5em",
+                        font_size: "small",
+                        cursor: "pointer",
+                        text_decoration: "underline",
+                        onclick: move |_| ondelete.call(id),
+                        "delete"
+                    }
+                )
+            }
+            if !rel.rel.note.is_empty() {
+                rsx!(p { font_style: "italic", rel.rel.note.clone() })
+            }
+        }
+    }
+}
+
+/// Adds a relationship of `subject` to another subject, or edits `rel`. The labels are always
+/// read in the direction the relationship was made in, from its first subject to the second.
+#[inline_props]
+fn RelationshipForm<'a>(
+    cx: Scope<'a>,
+    subject: i64,
+    rel: Option<RelationView>,
+    ondone: EventHandler<'a, ()>,
+) -> Element<'a> {
+    let state = use_shared_state::<State>(cx).unwrap();
+    let db_path = state.read().db_path.clone().unwrap();
+    let mut db = comp_try!(state, Schema::open(&db_path));
+    let Some(own) = comp_try!(state, db.get_subject(*subject)) else {
+        return None;
+    };
+    let incoming = rel.as_ref().map(|r| r.incoming).unwrap_or(false);
+    let other_name = rel
+        .as_ref()
+        .map(|r| r.other_name.clone())
+        .unwrap_or_default();
+    let current = rel.as_ref().map(|r| r.rel.t.clone());
+    let text =
+        |f: fn(&Relationship) -> Option<String>| current.as_ref().and_then(f).unwrap_or_default();
+    let label = text(|r| Some(r.label.clone()));
+    let inverse_label = text(|r| r.inverse_label.clone());
+    let note = text(|r| Some(r.note.clone()));
+    let valid_from = text(|r| r.valid_from.clone());
+    let valid_to = text(|r| r.valid_to.clone());
+    let title = if rel.is_some() {
+        "Edit relationship"
+    } else {
+        "New relationship"
+    };
+    let (first, second) = if incoming {
+        ("the other subject".to_string(), own.name.clone())
+    } else {
+        (own.name.clone(), "the other subject".to_string())
+    };
+
+    render! {
+        form {
+            padding: "1em",
+            display: "flex",
+            flex_direction: "column",
+            gap: "0.5em",
+            onsubmit: move |ev| {
+                attempt!{ state {
+                    let values = &ev.data.values;
+                    let value = |key: &str| values[key][0].trim().to_string();
+                    let optional = |key: &str| Some(value(key)).filter(|v| !v.is_empty());
+                    let label = value("label");
+                    if label.is_empty() {
+                        return Err(anyhow!("A relationship needs a label"));
+                    }
+                    let db_path = state.read().db_path.clone().unwrap();
+                    let mut db = Schema::open(&db_path)?;
+                    let other_name = value("other");
+                    let other = db
+                        .get_sub_by_name(&other_name)?
+                        .ok_or_else(|| anyhow!("There is no subject called {other_name}"))?;
+                    let (from, to) = if incoming { (other.id, *subject) } else { (*subject, other.id) };
+                    let new = Relationship {
+                        from,
+                        to,
+                        label,
+                        inverse_label: optional("inverse_label"),
+                        note: value("note"),
+                        valid_from: optional("valid_from"),
+                        valid_to: optional("valid_to"),
+                        secret: current.as_ref().map(|r| r.secret).unwrap_or(false),
+                    };
+                    match rel {
+                        Some(rel) => db.update_relationship(&WithId { t: new, id: rel.rel.id })?,
+                        None => {
+                            db.add_relationship(&new)?;
+                        }
+                    }
+                    ondone.call(());
+                    Ok(())
+                }}
+            },
+            h2 { "{title}" },
+            label { "Other subject" },
+            input { name: "other", value: "{other_name}" },
+            label { "How {first} relates to {second}, e.g. \"brother of\"" },
+            input { name: "label", value: "{label}" },
+            label { "How {second} relates to {first}, e.g. \"sister of\" (optional)" },
+            input { name: "inverse_label", value: "{inverse_label}" },
+            label { "Note" },
+            textarea { name: "note", rows: "3", value: "{note}" },
+            label { "Since (in-world date, optional)" },
+            input { name: "valid_from", value: "{valid_from}" },
+            label { "Until (in-world date, optional)" },
+            input { name: "valid_to", value: "{valid_to}" },
+            div {
+                display: "flex",
+                gap: "1em",
+                input { r#type: "submit", value: "Save" },
+                input { r#type: "button", value: "Cancel", onclick: move |_| ondone.call(()) },
+            }
+        }
+    }
+}
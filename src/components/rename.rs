@@ -0,0 +1,48 @@
+#![allow(non_snake_case)]
+use dioxus::prelude::*;
+
+use crate::{
+    attempt, components::SecondaryButton, schema::v1::EntityRef, ActiveMode, Schema, State,
+};
+
+/// A button that opens a small form to rename an entity. Mentions of the old name in other
+/// descriptions can optionally be updated as well.
+#[inline_props]
+pub fn Rename(cx: Scope, entity: EntityRef, name: String) -> Element {
+    let state = use_shared_state::<State>(cx).unwrap();
+    let open = use_state(cx, || false);
+
+    if !*open.get() {
+        return render! {
+            SecondaryButton { onclick: move |_| open.set(true), "Rename" }
+        };
+    }
+
+    render! {
+        form {
+            display: "flex",
+            align_items: "center",
+            gap: "1em",
+            onsubmit: move |ev| {
+                attempt!{ state {
+                    let new_name = ev.data.values["new_name"][0].trim().to_string();
+                    let propagate = ev.data.values.contains_key("propagate");
+                    let db_path = state.read().db_path.clone().unwrap();
+                    Schema::open(&db_path)?.rename(*entity, &new_name, propagate)?;
+                    open.set(false);
+                    // the views look entities up by name
+                    if let Some(mode) = ActiveMode::view(entity.kind, new_name) {
+                        state.write().set_active_mode(mode);
+                    }
+                    Ok(())
+                }}
+            },
+            input { name: "new_name", value: "{name}" },
+            label {
+                input { r#type: "checkbox", name: "propagate", checked: true },
+                "Update wiki links in other descriptions"
+            },
+            input { r#type: "submit", value: "Rename" },
+        }
+    }
+}
@@ -2,8 +2,14 @@
 use anyhow::Result;
 use dioxus::prelude::{GlobalAttributes, *};
 use proc_macros::b64_embed;
+use std::cmp::Ordering;
 
-use crate::{attempt, components::BImg, ActiveMode, Mode, Schema, State};
+use crate::{
+    attempt,
+    components::BImg,
+    schema::v1::{split_field_filters, split_tag_filters, Audience, EntityKind, EntityRef},
+    ActiveMode, Mode, Schema, State,
+};
 
 const PERSON_ICON: &str = b64_embed!("assets/person_icon.png");
 const LOCATION_ICON: &str = b64_embed!("assets/location_icon.png");
@@ -13,8 +19,11 @@ const TRASH_ICON: &str = b64_embed!("assets/trash_icon.png");
 
 #[derive(PartialEq)]
 pub struct ResInfo {
+    entity: EntityRef,
     kind: ResKind,
     name: String,
+    /// Html excerpt of the description with highlighted matches, for full text results
+    snippet: Option<String>,
 }
 
 #[derive(PartialEq)]
@@ -22,18 +31,73 @@ pub enum ResKind {
     Subject,
     Group,
     Place,
+    Event,
+}
+
+impl ResKind {
+    fn icon(&self) -> Option<&'static str> {
+        match self {
+            ResKind::Subject => Some(PERSON_ICON),
+            ResKind::Group => Some(GROUP_ICON),
+            ResKind::Place => Some(LOCATION_ICON),
+            ResKind::Event => None,
+        }
+    }
+
+    fn from_kind(kind: EntityKind) -> Option<Self> {
+        match kind {
+            EntityKind::Subject => Some(ResKind::Subject),
+            EntityKind::Place => Some(ResKind::Place),
+            EntityKind::Group => Some(ResKind::Group),
+            EntityKind::Event => Some(ResKind::Event),
+            EntityKind::Tag => None,
+        }
+    }
 }
 
 pub fn Search(cx: Scope) -> Element {
     let sterm = use_state(&cx, || "".to_string());
     let state = use_shared_state::<State>(&cx).unwrap();
+    let sort_by = use_state(&cx, || None::<String>);
+    let descending = use_state(&cx, || false);
     let sresults = use_state(&cx, || -> Vec<ResInfo> {
-        if let Ok(r) = query_entries(&state.read().db_path.as_ref().unwrap(), "") {
+        let state = state.read();
+        if let Ok(r) = query_entries(
+            state.db_path.as_ref().unwrap(),
+            "",
+            None,
+            false,
+            state.audience(),
+        ) {
             r
         } else {
             vec![]
         }
     });
+    let field_names = use_state(&cx, || {
+        let state = state.read();
+        visible_field_names(state.db_path.as_ref().unwrap(), state.audience())
+    });
+    let requery = move |query: &str, sort: Option<String>, desc: bool| {
+        attempt! { state {
+            let db_path = state.read().db_path.clone().unwrap();
+            let audience = state.read().audience();
+            sresults.set(query_entries(&db_path, query, sort.as_deref(), desc, audience)?);
+            Ok(())
+        }};
+    };
+
+    // toggling the player view must not leave secret results or field names on screen
+    let results_for = use_state(&cx, || state.read().audience());
+    let audience = state.read().audience();
+    if *results_for.get() != audience {
+        results_for.set(audience);
+        let names = visible_field_names(state.read().db_path.as_ref().unwrap(), audience);
+        let sort = (*sort_by.get()).clone().filter(|s| names.contains(s));
+        sort_by.set(sort.clone());
+        field_names.set(names);
+        requery(sterm.get(), sort, *descending.get());
+    }
 
     render!(
         div {
@@ -65,14 +129,44 @@ pub fn Search(cx: Scope) -> Element {
                     value: "{sterm}",
                     oninput: move |evt| {
                         sterm.set(evt.value.clone());
-                        attempt!{ state {
-                            Ok(sresults.set(query_entries(&state.read().db_path.as_ref().unwrap(), &evt.value)?))
-                        }};
+                        requery(&evt.value, (*sort_by.get()).clone(), *descending.get());
                     },
                 }
 
             }
 
+            if !field_names.is_empty() {
+                rsx!(div {
+                    width: "70%",
+                    margin: "auto",
+                    display: "flex",
+                    align_items: "center",
+                    gap: "0.5em",
+                    "Sort by",
+                    select {
+                        onchange: move |evt| {
+                            let sort = Some(evt.value.clone()).filter(|s| !s.is_empty());
+                            sort_by.set(sort.clone());
+                            requery(sterm.get(), sort, *descending.get());
+                        },
+                        option { value: "", "Relevance" },
+                        field_names.iter().map(|name| rsx!(option { key: "{name}", value: "{name}", "{name}" }))
+                    },
+                    label {
+                        input {
+                            r#type: "checkbox",
+                            checked: "{descending}",
+                            onchange: move |evt| {
+                                let desc = evt.value == "true";
+                                descending.set(desc);
+                                requery(sterm.get(), (*sort_by.get()).clone(), desc);
+                            },
+                        },
+                        "descending"
+                    }
+                })
+            }
+
             div {
                 flex: "6 0 auto",
                 ul {
@@ -95,6 +189,9 @@ pub fn Search(cx: Scope) -> Element {
 #[inline_props]
 pub fn SearchResult<'a>(cx: Scope, result: &'a ResInfo) -> Element {
     let state = use_shared_state::<State>(&cx).unwrap();
+    let player_view = state.read().player_view;
+    // the editor shows the whole description, secrets included
+    let editable = result.kind == ResKind::Subject && !player_view;
     render! {
         div {
             padding: "5px",
@@ -102,14 +199,14 @@ pub fn SearchResult<'a>(cx: Scope, result: &'a ResInfo) -> Element {
             align_items: "center",
             gap: "5px",
 
-            if result.kind == ResKind::Subject {
+            if let Some(icon) = result.kind.icon() {
                 rsx!{
                     div{
                         onclick: move |_| view_item(state, result),
                         BImg {
                             w: 30,
                             h: 30,
-                            data: PERSON_ICON,
+                            data: icon,
                             format: "png",
                         },
                     }
@@ -120,47 +217,183 @@ pub fn SearchResult<'a>(cx: Scope, result: &'a ResInfo) -> Element {
                 flex: 1,
                 onclick: move |_| view_item(state, result),
                 style: "user-select: none;",
-                result.name.clone()
+                result.name.clone(),
+                if let Some(snippet) = &result.snippet {
+                    rsx!(div {
+                        font_size: "small",
+                        color: "grey",
+                        dangerous_inner_html: "{snippet}"
+                    })
+                }
             },
 
-            div {
-                onclick: move |_| {
-                    assert!(result.kind == ResKind::Subject);
-                    state.write().set_mode(Mode::EditingSubject(result.name.clone()))
-                },
-                BImg {
-                    w: 30,
-                    h: 30,
-                    data: PENCIL_ICON,
-                    format: "png",
+            if editable {
+                rsx!{
+                    div {
+                        onclick: move |_| {
+                            state.write().set_mode(Mode::EditingSubject(result.name.clone()))
+                        },
+                        BImg {
+                            w: 30,
+                            h: 30,
+                            data: PENCIL_ICON,
+                            format: "png",
+                        }
+                    },
                 }
-            },
-            BImg {
-                w: 30,
-                h: 30,
-                data: TRASH_ICON,
-                format: "png",
-            },
+            }
+
+            if !player_view {
+                rsx!{
+                    div {
+                        onclick: move |_| confirm_delete(state, result),
+                        BImg {
+                            w: 30,
+                            h: 30,
+                            data: TRASH_ICON,
+                            format: "png",
+                        }
+                    },
+                }
+            }
         }
 
     }
 }
 
 fn view_item(state: &UseSharedState<State>, info: &ResInfo) {
-    assert!(info.kind == ResKind::Subject);
-    state
-        .write()
-        .set_active_mode(ActiveMode::Subject(info.name.clone()));
+    let mode = match info.kind {
+        ResKind::Subject => ActiveMode::Subject(info.name.clone()),
+        ResKind::Place => ActiveMode::Place(info.name.clone()),
+        ResKind::Group => ActiveMode::Group(info.name.clone()),
+        ResKind::Event => ActiveMode::Events,
+    };
+    state.write().set_active_mode(mode);
+}
+
+fn confirm_delete(state: &UseSharedState<State>, info: &ResInfo) {
+    let mut state = state.write();
+    let parent = Box::new(state.mode.clone());
+    state.set_mode(Mode::ConfirmDelete {
+        entity: info.entity,
+        label: info.name.clone(),
+        parent,
+    });
+}
+
+/// The names for the "Sort by" choice, empty if the database can't be read
+fn visible_field_names(db_path: &str, audience: Audience) -> Vec<String> {
+    Schema::open(db_path)
+        .and_then(|mut db| db.field_names(audience))
+        .unwrap_or_default()
 }
 
-fn query_entries(db_path: &str, query: &str) -> Result<Vec<ResInfo>> {
+/// Subjects whose name matches come first, followed by full text matches in names and
+/// descriptions of all entities. Words like `#villain` only keep results with that tag, words
+/// like `hp>10` only results whose custom field matches, see
+/// [`crate::schema::v1::FieldFilter`]. If `sort_by` names
+/// a field, results are ordered by it, those without the field last. Results `audience` may
+/// not see are left out.
+fn query_entries(
+    db_path: &str,
+    query: &str,
+    sort_by: Option<&str>,
+    descending: bool,
+    audience: Audience,
+) -> Result<Vec<ResInfo>> {
     let mut db = Schema::open(db_path)?;
-    Ok(db
-        .query_subj_names(query)?
+    let (query, tags) = split_tag_filters(query);
+    let (query, filters) = split_field_filters(&query, &db.field_names(audience)?);
+    let mut res = db
+        .query_names(EntityKind::Subject, &query)?
         .into_iter()
         .map(|x| ResInfo {
+            entity: EntityRef::new(EntityKind::Subject, x.id),
             kind: ResKind::Subject,
-            name: x,
+            name: x.t,
+            snippet: None,
         })
-        .collect())
+        .collect::<Vec<_>>();
+    if query.is_empty() && !filters.is_empty() {
+        // only filters, so places and groups can match as well
+        for kind in [EntityKind::Place, EntityKind::Group] {
+            for x in db.query_names(kind, "")? {
+                res.push(ResInfo {
+                    entity: EntityRef::new(kind, x.id),
+                    kind: ResKind::from_kind(kind).unwrap(),
+                    name: x.t,
+                    snippet: None,
+                });
+            }
+        }
+    }
+    for hit in db.full_text_search(&query, 50, audience)? {
+        let Some(kind) = ResKind::from_kind(hit.entity.kind) else {
+            continue;
+        };
+        let snippet = Some(hit.snippet).filter(|s| !s.is_empty());
+        match res.iter_mut().find(|r| r.entity == hit.entity) {
+            Some(existing) => existing.snippet = snippet,
+            None => res.push(ResInfo {
+                entity: hit.entity,
+                kind,
+                name: hit.name,
+                snippet,
+            }),
+        }
+    }
+
+    if !tags.is_empty() {
+        let tagged = db.entities_with_tags(&tags, audience)?;
+        if query.is_empty() {
+            // only tag filters, so everything with the tags is a result
+            res = tagged
+                .into_iter()
+                .filter_map(|l| {
+                    Some(ResInfo {
+                        entity: l.entity,
+                        kind: ResKind::from_kind(l.entity.kind)?,
+                        name: l.label,
+                        snippet: None,
+                    })
+                })
+                .collect();
+        } else {
+            res.retain(|r| tagged.iter().any(|l| l.entity == r.entity));
+        }
+    }
+
+    if !filters.is_empty() {
+        let mut kept = vec![];
+        for r in res {
+            if db.matches_field_filters(r.entity, &filters, audience)? {
+                kept.push(r);
+            }
+        }
+        res = kept;
+    }
+
+    let mut visible = vec![];
+    for r in res {
+        if db.is_visible(r.entity, audience)? {
+            visible.push(r);
+        }
+    }
+    res = visible;
+
+    if let Some(field) = sort_by {
+        let mut keyed = vec![];
+        for r in res {
+            keyed.push((db.field_sort_key(r.entity, field, audience)?, r));
+        }
+        keyed.sort_by(|(a, _), (b, _)| match (a, b) {
+            (Some(a), Some(b)) if descending => b.compare(a),
+            (Some(a), Some(b)) => a.compare(b),
+            (Some(_), None) => Ordering::Less,
+            (None, Some(_)) => Ordering::Greater,
+            (None, None) => Ordering::Equal,
+        });
+        res = keyed.into_iter().map(|(_, r)| r).collect();
+    }
+    Ok(res)
 }
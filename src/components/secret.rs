@@ -0,0 +1,46 @@
+#![allow(non_snake_case)]
+use dioxus::prelude::*;
+
+use crate::{attempt, comp_try, schema::v1::EntityRef, Schema, State};
+
+/// A checkbox that hides an entity from the players. Not shown in the player view.
+#[inline_props]
+pub fn SecretToggle(cx: Scope, entity: EntityRef) -> Element {
+    let state = use_shared_state::<State>(cx).unwrap();
+    if state.read().player_view {
+        return None;
+    }
+    let db_path = state.read().db_path.clone().unwrap();
+    let mut db = comp_try!(state, Schema::open(&db_path));
+    let secret = comp_try!(state, db.is_secret(*entity));
+
+    render! {
+        label {
+            padding: "0 1em",
+            input {
+                r#type: "checkbox",
+                checked: "{secret}",
+                onchange: move |evt| {
+                    attempt!{ state {
+                        let db_path = state.read().db_path.clone().unwrap();
+                        Schema::open(&db_path)?.set_secret(*entity, evt.value == "true")?;
+                        cx.needs_update();
+                        Ok(())
+                    }}
+                },
+            },
+            " Secret, hidden in the player view"
+        }
+    }
+}
+
+/// Shown in the player view instead of a secret entity
+pub fn SecretNotice(cx: Scope) -> Element {
+    render! {
+        p {
+            padding: "1em",
+            font_style: "italic",
+            "This is hidden in the player view."
+        }
+    }
+}
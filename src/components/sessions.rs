@@ -0,0 +1,302 @@
+#![allow(non_snake_case)]
+use anyhow::anyhow;
+use dioxus::prelude::*;
+
+use crate::{
+    attempt,
+    calendar::Calendar,
+    comp_try,
+    components::{color, Markdown, SecondaryButton},
+    schema::{
+        v1::{EntityKind, EntityRef, Session},
+        WithId,
+    },
+    wiki, ActiveMode, Schema, State,
+};
+
+/// The session log: all play sessions, the latest first. Clicking a session shows its recap,
+/// players, events and everything the events involve.
+pub fn Sessions(cx: Scope) -> Element {
+    let state = use_shared_state::<State>(cx).unwrap();
+    let selected = use_state(cx, || None::<i64>);
+    // None: nothing is edited, Some(None): a new session
+    let editing = use_state(cx, || None::<Option<WithId<Session>>>);
+    let db_path = state.read().db_path.clone().unwrap();
+    let mut db = comp_try!(state, Schema::open(&db_path));
+    let sessions = comp_try!(state, db.list_sessions());
+
+    if let Some(session) = editing.get() {
+        let session = match session {
+            Some(s) => s.clone(),
+            None => WithId {
+                t: Session {
+                    number: comp_try!(state, db.next_session_number()),
+                    played_on: today(),
+                    title: String::new(),
+                    recap: String::new(),
+                    players: sessions
+                        .first()
+                        .map(|s| s.players.clone())
+                        .unwrap_or_default(),
+                },
+                id: -1,
+            },
+        };
+        return render! {
+            SessionForm {
+                session: session,
+                ondone: move |id| {
+                    if let Some(id) = id {
+                        selected.set(Some(id));
+                    }
+                    editing.set(None);
+                },
+            }
+        };
+    }
+
+    render! {
+        div {
+            padding: "1em",
+            h1 { "Sessions" },
+            SecondaryButton { onclick: move |_| editing.set(Some(None)), "New session" },
+            div {
+                display: "flex",
+                gap: "1em",
+                align_items: "flex-start",
+                margin_top: "1em",
+                table {
+                    width: "40%",
+                    sessions.iter().map(|s| {
+                        let id = s.id;
+                        let background = if *selected.get() == Some(id) { color::GREY } else { color::WHITE };
+                        rsx!(tr {
+                            key: "{id}",
+                            cursor: "pointer",
+                            background_color: "{background}",
+                            onclick: move |_| selected.set(Some(id)),
+                            td { "#{s.number}" },
+                            td { "{s.played_on}" },
+                            td { "{s.title}" },
+                        })
+                    })
+                },
+                if let Some(id) = *selected.get() {
+                    rsx!(SessionDetails {
+                        id: id,
+                        onedit: move |s| editing.set(Some(Some(s))),
+                        ondelete: move |_| selected.set(None),
+                    })
+                }
+            }
+        }
+    }
+}
+
+#[inline_props]
+fn SessionDetails<'a>(
+    cx: Scope<'a>,
+    id: i64,
+    onedit: EventHandler<'a, WithId<Session>>,
+    ondelete: EventHandler<'a, ()>,
+) -> Element<'a> {
+    let state = use_shared_state::<State>(cx).unwrap();
+    let db_path = state.read().db_path.clone().unwrap();
+    let mut db = comp_try!(state, Schema::open(&db_path));
+    let Some(session) = comp_try!(state, db.get_session(*id)) else {
+        return None;
+    };
+    let audience = state.read().audience();
+    let html = comp_try!(
+        state,
+        wiki::render_markdown(&mut db, &session.recap, audience)
+    );
+    let mut events = vec![];
+    for ev in comp_try!(state, db.session_events(*id)) {
+        if comp_try!(
+            state,
+            db.is_visible(EntityRef::new(EntityKind::Event, ev.id), audience)
+        ) {
+            events.push(ev);
+        }
+    }
+    let mut unassigned = vec![];
+    for ev in comp_try!(state, db.events_in_world_order()) {
+        let entity = EntityRef::new(EntityKind::Event, ev.id);
+        if ev.session.is_none() && comp_try!(state, db.is_visible(entity, audience)) {
+            unassigned.push(ev);
+        }
+    }
+    let participants = comp_try!(state, db.session_participants(*id, audience));
+    let players = session.players.join(", ");
+    let edit = session.clone();
+
+    render! {
+        div {
+            flex: "1",
+            padding: "0.5em 1em",
+            background_color: color::GREY,
+            border_radius: "10px",
+            h2 { "Session {session.number}: {session.title}" },
+            p { "Played on {session.played_on} by {players}" },
+            div {
+                display: "flex",
+                gap: "1em",
+                SecondaryButton { onclick: move |_| onedit.call(edit.clone()), "Edit" },
+                SecondaryButton {
+                    onclick: move |_| {
+                        attempt!{ state {
+                            let db_path = state.read().db_path.clone().unwrap();
+                            Schema::open(&db_path)?.delete_session(*id)?;
+                            ondelete.call(());
+                            Ok(())
+                        }}
+                    },
+                    "Delete"
+                },
+            },
+            Markdown { html: html }
+            h3 { "Events" },
+            ul {
+                events.into_iter().map(|ev| {
+                    let event = ev.id;
+                    rsx!(li {
+                        key: "{event}",
+                        "{ev.refered_date} ",
+                        span {
+                            cursor: "pointer",
+                            title: "Remove from this session",
+                            onclick: move |_| {
+                                attempt!{ state {
+                                    let db_path = state.read().db_path.clone().unwrap();
+                                    Schema::open(&db_path)?.set_event_session(event, None)?;
+                                    cx.needs_update();
+                                    Ok(())
+                                }}
+                            },
+                            "×"
+                        }
+                    })
+                })
+            },
+            select {
+                onchange: move |evt| {
+                    attempt!{ state {
+                        let Ok(event) = evt.value.parse::<i64>() else {
+                            return Ok(());
+                        };
+                        let db_path = state.read().db_path.clone().unwrap();
+                        Schema::open(&db_path)?.set_event_session(event, Some(*id))?;
+                        cx.needs_update();
+                        Ok(())
+                    }}
+                },
+                option { value: "", "Add an event…" },
+                unassigned.into_iter().map(|ev| rsx!(option {
+                    key: "{ev.id}",
+                    value: "{ev.id}",
+                    "{ev.refered_date}"
+                }))
+            },
+            h3 { "Involved" },
+            ul {
+                participants.into_iter().map(|l| {
+                    let view = ActiveMode::view(l.entity.kind, l.label.clone());
+                    rsx!(li {
+                        key: "{l.entity.kind}-{l.entity.id}",
+                        cursor: "pointer",
+                        text_decoration: "underline",
+                        onclick: move |_| {
+                            if let Some(view) = view.clone() {
+                                state.write().set_active_mode(view);
+                            }
+                        },
+                        "{l.label} ({l.entity.kind})"
+                    })
+                })
+            }
+        }
+    }
+}
+
+/// Edits a session. A session with id -1 is new, and inserted on save. `ondone` gets the id
+/// of the saved session, or None if editing was cancelled.
+#[inline_props]
+fn SessionForm<'a>(
+    cx: Scope<'a>,
+    session: WithId<Session>,
+    ondone: EventHandler<'a, Option<i64>>,
+) -> Element<'a> {
+    let state = use_shared_state::<State>(cx).unwrap();
+    let players = session.players.join("\n");
+
+    render! {
+        form {
+            padding: "1em",
+            display: "flex",
+            flex_direction: "column",
+            gap: "0.5em",
+            onsubmit: move |ev| {
+                attempt!{ state {
+                    let values = &ev.data.values;
+                    let value = |key: &str| values[key][0].clone();
+                    let number = value("number")
+                        .trim()
+                        .parse()
+                        .map_err(|_| anyhow!("The session number must be a number"))?;
+                    let new = Session {
+                        number,
+                        played_on: value("played_on"),
+                        title: value("title").trim().to_string(),
+                        recap: value("recap"),
+                        players: value("players")
+                            .lines()
+                            .map(|p| p.trim().to_string())
+                            .filter(|p| !p.is_empty())
+                            .collect(),
+                    };
+                    let db_path = state.read().db_path.clone().unwrap();
+                    let mut db = Schema::open(&db_path)?;
+                    let id = if session.id < 0 {
+                        db.insert_session(&new)?
+                    } else {
+                        db.update_session(&WithId { t: new, id: session.id })?;
+                        session.id
+                    };
+                    ondone.call(Some(id));
+                    Ok(())
+                }}
+            },
+            label { "Number" },
+            input { name: "number", r#type: "number", value: "{session.number}" },
+            label { "Played on" },
+            input { name: "played_on", r#type: "date", value: "{session.played_on}" },
+            label { "Title" },
+            input { name: "title", value: "{session.title}" },
+            label { "Players, one per line" },
+            textarea { name: "players", rows: "5", value: "{players}" },
+            label { "Recap" },
+            textarea { name: "recap", rows: "15", value: "{session.recap}" },
+            div {
+                display: "flex",
+                gap: "1em",
+                input { r#type: "submit", value: "Save" },
+                input { r#type: "button", value: "Cancel", onclick: move |_| ondone.call(None) },
+            }
+        }
+    }
+}
+
+/// Today's date like `2023-07-14`
+fn today() -> String {
+    let secs = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_secs() as i64)
+        .unwrap_or(0);
+    let gregorian = Calendar::gregorian();
+    let epoch = gregorian
+        .parse("1970-01-01")
+        .map(|d| gregorian.day_number(d));
+    let date = gregorian.date_of_day_number(epoch.unwrap_or(0) + secs.div_euclid(86_400));
+    format!("{:04}-{:02}-{:02}", date.year, date.month + 1, date.day)
+}
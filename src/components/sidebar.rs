@@ -7,6 +7,11 @@ use crate::{components::button::SecondaryButton, ActiveMode, Mode, State};
 
 pub fn Sidebar(cx: Scope) -> Element {
     let state = use_shared_state::<State>(cx).unwrap();
+    let view = if state.read().player_view {
+        "Player view"
+    } else {
+        "GM view"
+    };
     cx.render(rsx!(div {
         style: "
             background-color: #26B3D1;
@@ -22,6 +27,13 @@ pub fn Sidebar(cx: Scope) -> Element {
             height: 100%;
         ",
         NewButtons {},
+        SecondaryButton {
+            onclick: move |_| {
+                debug!("Sessions Clicked");
+                state.write().mode = Mode::Active(ActiveMode::Sessions);
+            },
+            "Sessions"
+        },
         SecondaryButton {
             onclick: move |_| {
                 debug!("Search Clicked");
@@ -29,6 +41,59 @@ pub fn Sidebar(cx: Scope) -> Element {
             },
             "Search"
         },
+        SecondaryButton {
+            onclick: move |_| {
+                debug!("Tags Clicked");
+                state.write().mode = Mode::Active(ActiveMode::Tags);
+            },
+            "Tags"
+        },
+        SecondaryButton {
+            onclick: move |_| {
+                debug!("Templates Clicked");
+                state.write().mode = Mode::Active(ActiveMode::Templates);
+            },
+            "Templates"
+        },
+        SecondaryButton {
+            onclick: move |_| {
+                debug!("Calendar Clicked");
+                state.write().mode = Mode::Active(ActiveMode::Calendar);
+            },
+            "Calendar"
+        },
+        SecondaryButton {
+            onclick: move |_| {
+                debug!("Export Clicked");
+                state.write().mode = Mode::Active(ActiveMode::Export);
+            },
+            "Export"
+        },
+        SecondaryButton {
+            onclick: move |_| {
+                debug!("Import Clicked");
+                state.write().mode = Mode::Active(ActiveMode::Import);
+            },
+            "Import"
+        },
+        // the trash lists secret entities as well
+        if !state.read().player_view {
+            rsx!(SecondaryButton {
+                onclick: move |_| {
+                    debug!("Trash Clicked");
+                    state.write().mode = Mode::Active(ActiveMode::Trash);
+                },
+                "Trash"
+            })
+        }
+        SecondaryButton {
+            onclick: move |_| {
+                debug!("View Toggled");
+                let mut state = state.write();
+                state.player_view = !state.player_view;
+            },
+            "{view}"
+        },
         SecondaryButton { onclick: |_| {}, "Help" },
     }))
 }
@@ -61,8 +126,23 @@ pub fn NewButtons(cx: Scope) -> Element {
                 state.write().mode = Mode::Active(ActiveMode::NewSubject);
             },
             "Subject" },
-        SecondaryButton { onclick: |_| {}, "Group" },
-        SecondaryButton { onclick: |_| {}, "Place"},
-        SecondaryButton { onclick: |_| {}, "Event" },
+        SecondaryButton {
+            onclick: move |_| {
+                debug!("New Group Clicked");
+                state.write().mode = Mode::Active(ActiveMode::NewGroup);
+            },
+            "Group" },
+        SecondaryButton {
+            onclick: move |_| {
+                debug!("New Place Clicked");
+                state.write().mode = Mode::Active(ActiveMode::NewPlace);
+            },
+            "Place"},
+        SecondaryButton {
+            onclick: move |_| {
+                debug!("New Event Clicked");
+                state.write().mode = Mode::Active(ActiveMode::NewEvent);
+            },
+            "Event"},
     }))
 }
@@ -1,6 +1,13 @@
 #![allow(non_snake_case)]
-use crate::{comp_try, Schema, State};
-use comrak::ComrakOptions;
+use crate::{
+    comp_try,
+    components::{
+        Backlinks, FactBox, Gallery, History, Markdown, Related, Relationships, Rename,
+        SecretNotice, SecretToggle, TagEditor,
+    },
+    schema::v1::{EntityKind, EntityRef},
+    wiki, Schema, State,
+};
 use dioxus::prelude::*;
 
 #[inline_props]
@@ -10,10 +17,32 @@ pub fn Subject(cx: Scope, name: String) -> Element {
     let mut db = comp_try!(state, Schema::open(&db_path));
     let sub = comp_try!(state, db.get_sub_by_name(&name))
         .expect("Trying to display non existing subject");
-    let html = comrak::markdown_to_html(&sub.description, &ComrakOptions::default());
+    let entity = EntityRef::new(EntityKind::Subject, sub.id);
+    let audience = state.read().audience();
+    if !comp_try!(state, db.is_visible(entity, audience)) {
+        return render! { SecretNotice {} };
+    }
+    let html = comp_try!(
+        state,
+        wiki::render_markdown(&mut db, &sub.description, audience)
+    );
+    let player_view = state.read().player_view;
     render! {
         div {
-            dangerous_inner_html: "{html}"
+            padding: "1em",
+            Rename { entity: entity, name: sub.name.clone() }
+        }
+        SecretToggle { entity: entity }
+        FactBox { entity: entity }
+        Gallery { entity: entity }
+        TagEditor { entity: entity }
+        Markdown { html: html }
+        Relationships { subject: sub.id }
+        Related { entity: entity }
+        Backlinks { entity: entity }
+        // older versions of the description may contain secrets
+        if !player_view {
+            rsx!(History { entity: entity })
         }
     }
 }
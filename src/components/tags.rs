@@ -0,0 +1,243 @@
+#![allow(non_snake_case)]
+use dioxus::prelude::*;
+
+use crate::{
+    attempt, comp_try,
+    components::{color, SecondaryButton},
+    schema::v1::{EntityKind, EntityRef, TagCount},
+    ActiveMode, Mode, Schema, State,
+};
+
+/// The tags of an entity, with a field to add more. New names create new tags.
+#[inline_props]
+pub fn TagEditor(cx: Scope, entity: EntityRef) -> Element {
+    let state = use_shared_state::<State>(cx).unwrap();
+    let new_tag = use_state(cx, String::new);
+    let db_path = state.read().db_path.clone().unwrap();
+    let mut db = comp_try!(state, Schema::open(&db_path));
+    let tags = comp_try!(state, db.tags_of(*entity));
+    let known = comp_try!(state, db.list_tags(state.read().audience()));
+    let list_id = format!("known-tags-{:?}-{}", entity.kind, entity.id);
+
+    render! {
+        div {
+            padding: "1em",
+            display: "flex",
+            flex_wrap: "wrap",
+            align_items: "center",
+            gap: "0.5em",
+            tags.iter().map(|t| {
+                let tag = t.entity.id;
+                rsx!(span {
+                    key: "{tag}",
+                    background_color: color::GREY,
+                    border_radius: "1em",
+                    padding: "0.2em 0.6em",
+                    "#{t.label} ",
+                    span {
+                        cursor: "pointer",
+                        title: "Remove tag",
+                        onclick: move |_| {
+                            attempt!{ state {
+                                let db_path = state.read().db_path.clone().unwrap();
+                                Schema::open(&db_path)?.remove_tag(*entity, tag)?;
+                                cx.needs_update();
+                                Ok(())
+                            }}
+                        },
+                        "×"
+                    }
+                })
+            }),
+            form {
+                onsubmit: move |_| {
+                    attempt!{ state {
+                        let name = new_tag.trim();
+                        if !name.trim_start_matches('#').is_empty() {
+                            let db_path = state.read().db_path.clone().unwrap();
+                            Schema::open(&db_path)?.add_tag(*entity, name)?;
+                        }
+                        new_tag.set(String::new());
+                        Ok(())
+                    }}
+                },
+                input {
+                    value: "{new_tag}",
+                    placeholder: "Add tag",
+                    list: "{list_id}",
+                    oninput: move |evt| new_tag.set(evt.value.clone()),
+                },
+                datalist {
+                    id: "{list_id}",
+                    known.iter().map(|t| rsx!(option { key: "{t.id}", value: "{t.name}" }))
+                }
+            }
+        }
+    }
+}
+
+/// All tags, sized by how often they are used. Selecting a tag shows what it is attached to,
+/// and allows renaming, merging and deleting it.
+pub fn TagCloud(cx: Scope) -> Element {
+    let state = use_shared_state::<State>(cx).unwrap();
+    let selected = use_state(cx, || None::<i64>);
+    let db_path = state.read().db_path.clone().unwrap();
+    let mut db = comp_try!(state, Schema::open(&db_path));
+    let tags = comp_try!(state, db.list_tags(state.read().audience()));
+
+    if tags.is_empty() {
+        return render! { p { padding: "1em", "No tags yet" } };
+    }
+    let max = tags.iter().map(|t| t.count).max().unwrap_or(0).max(1);
+    let current = (*selected.get()).and_then(|id| tags.iter().find(|t| t.id == id).cloned());
+    let others = tags
+        .iter()
+        .filter(|t| Some(t.id) != *selected.get())
+        .cloned()
+        .collect::<Vec<_>>();
+
+    render! {
+        div {
+            padding: "1em",
+            h1 { "Tags" },
+            div {
+                display: "flex",
+                flex_wrap: "wrap",
+                align_items: "baseline",
+                gap: "0.2em 1em",
+                tags.iter().map(|t| {
+                    let size = 0.8 + 1.4 * t.count as f64 / max as f64;
+                    let id = t.id;
+                    let weight = if Some(id) == *selected.get() { "bold" } else { "normal" };
+                    rsx!(span {
+                        key: "{t.id}",
+                        cursor: "pointer",
+                        font_size: "{size:.2}em",
+                        font_weight: "{weight}",
+                        onclick: move |_| selected.set(Some(id)),
+                        "#{t.name} ({t.count})"
+                    })
+                })
+            },
+            current.map(|tag| {
+                let id = tag.id;
+                rsx!(TagDetails {
+                    key: "{id}",
+                    tag: tag,
+                    others: others.clone(),
+                    onchange: move |id| selected.set(id),
+                })
+            })
+        }
+    }
+}
+
+/// What a tag is attached to, and forms to change it. `onchange` is called with the tag that
+/// should be selected afterwards.
+#[inline_props]
+fn TagDetails<'a>(
+    cx: Scope<'a>,
+    tag: TagCount,
+    others: Vec<TagCount>,
+    onchange: EventHandler<'a, Option<i64>>,
+) -> Element<'a> {
+    let state = use_shared_state::<State>(cx).unwrap();
+    let new_name = use_state(cx, || tag.name.clone());
+    let merge_into = use_state(cx, || None::<i64>);
+    let db_path = state.read().db_path.clone().unwrap();
+    let mut db = comp_try!(state, Schema::open(&db_path));
+    let audience = state.read().audience();
+    let tagged: Vec<_> = comp_try!(
+        state,
+        db.incoming_links(EntityRef::new(EntityKind::Tag, tag.id))
+    )
+    .into_iter()
+    .filter(|l| audience.sees(l.secret))
+    .collect();
+
+    render! {
+        div {
+            padding_top: "1em",
+            h2 { "#{tag.name}" },
+            ul {
+                tagged.into_iter().map(|l| {
+                    let mode = ActiveMode::view(l.entity.kind, l.label.clone());
+                    rsx!(li {
+                        key: "{l.entity.kind:?}{l.entity.id}",
+                        cursor: "pointer",
+                        text_decoration: "underline",
+                        onclick: move |_| {
+                            if let Some(mode) = mode.clone() {
+                                state.write().set_active_mode(mode);
+                            }
+                        },
+                        "{l.entity.kind}: {l.label}"
+                    })
+                })
+            },
+            div {
+                display: "flex",
+                align_items: "center",
+                gap: "1em",
+                input {
+                    value: "{new_name}",
+                    oninput: move |evt| new_name.set(evt.value.clone()),
+                },
+                SecondaryButton {
+                    onclick: move |_| {
+                        attempt!{ state {
+                            let db_path = state.read().db_path.clone().unwrap();
+                            let entity = EntityRef::new(EntityKind::Tag, tag.id);
+                            Schema::open(&db_path)?.rename(entity, new_name.trim(), false)?;
+                            onchange.call(Some(tag.id));
+                            Ok(())
+                        }}
+                    },
+                    "Rename"
+                },
+            },
+            if !others.is_empty() {
+                rsx!(div {
+                    display: "flex",
+                    align_items: "center",
+                    gap: "1em",
+                    "Merge into",
+                    select {
+                        onchange: move |evt| merge_into.set(evt.value.parse().ok()),
+                        option { value: "", "" },
+                        others.iter().map(|t| rsx!(option {
+                            key: "{t.id}",
+                            value: "{t.id}",
+                            "#{t.name}"
+                        }))
+                    },
+                    SecondaryButton {
+                        onclick: move |_| {
+                            attempt!{ state {
+                                if let Some(into) = *merge_into.get() {
+                                    let db_path = state.read().db_path.clone().unwrap();
+                                    Schema::open(&db_path)?.merge_tags(tag.id, into)?;
+                                    onchange.call(Some(into));
+                                }
+                                Ok(())
+                            }}
+                        },
+                        "Merge"
+                    },
+                })
+            },
+            SecondaryButton {
+                onclick: move |_| {
+                    let mut state = state.write();
+                    let parent = Box::new(state.mode.clone());
+                    state.set_mode(Mode::ConfirmDelete {
+                        entity: EntityRef::new(EntityKind::Tag, tag.id),
+                        label: tag.name.clone(),
+                        parent,
+                    });
+                },
+                "Delete"
+            },
+        }
+    }
+}
@@ -0,0 +1,204 @@
+#![allow(non_snake_case)]
+use anyhow::anyhow;
+use dioxus::prelude::*;
+
+use crate::{
+    attempt, comp_try,
+    components::SecondaryButton,
+    schema::{
+        self,
+        v1::{format_template_fields, parse_template_fields, EntityKind, EntityRef, Template},
+        WithId,
+    },
+    Schema, State,
+};
+
+/// The kinds that can be created from templates
+const KINDS: [EntityKind; 3] = [EntityKind::Subject, EntityKind::Place, EntityKind::Group];
+
+/// A select named `template` for use in the forms that create new entities. The value is the
+/// id of the chosen template, or empty.
+#[inline_props]
+pub fn TemplateSelect(cx: Scope, kind: EntityKind) -> Element {
+    let state = use_shared_state::<State>(cx).unwrap();
+    let db_path = state.read().db_path.clone().unwrap();
+    let mut db = comp_try!(state, Schema::open(&db_path));
+    let templates = comp_try!(state, db.list_templates(*kind));
+    render! {
+        label { "Template" },
+        select {
+            name: "template",
+            option { value: "", "None" },
+            templates.iter().map(|t| rsx!(option { key: "{t.id}", value: "{t.id}", "{t.name}" }))
+        }
+    }
+}
+
+/// Applies the template chosen in a [`TemplateSelect`] to a freshly created entity. `values`
+/// are the values of the submitted form. Call it in the transaction that creates the entity,
+/// so that a template that doesn't apply leaves no entity behind.
+pub fn apply_chosen_template(
+    db: &mut Schema,
+    entity: EntityRef,
+    values: &std::collections::HashMap<String, Vec<String>>,
+) -> schema::Result<()> {
+    let Some(id) = values
+        .get("template")
+        .and_then(|v| v.first())
+        .and_then(|v| v.parse::<i64>().ok())
+    else {
+        return Ok(());
+    };
+    if let Some(template) = db.get_template(id)? {
+        db.apply_template(entity, &template)?;
+    }
+    Ok(())
+}
+
+/// Lists all templates, and lets the user create, change and delete them
+pub fn Templates(cx: Scope) -> Element {
+    let state = use_shared_state::<State>(cx).unwrap();
+    // None: nothing is edited, Some(None): a new template
+    let editing = use_state(cx, || None::<Option<WithId<Template>>>);
+    let db_path = state.read().db_path.clone().unwrap();
+    let mut db = comp_try!(state, Schema::open(&db_path));
+    let mut sections = vec![];
+    for kind in KINDS {
+        sections.push((kind, comp_try!(state, db.list_templates(kind))));
+    }
+
+    if let Some(template) = editing.get() {
+        return render! {
+            TemplateForm {
+                template: template.clone(),
+                ondone: move |_| editing.set(None),
+            }
+        };
+    }
+
+    render! {
+        div {
+            padding: "1em",
+            h1 { "Templates" },
+            SecondaryButton { onclick: move |_| editing.set(Some(None)), "New template" },
+            sections.into_iter().map(|(kind, templates)| rsx!(
+                div {
+                    key: "{kind}",
+                    h2 { "{kind}" },
+                    ul {
+                        templates.into_iter().map(|t| {
+                            let id = t.id;
+                            let name = t.name.clone();
+                            rsx!(li {
+                                key: "{id}",
+                                span {
+                                    cursor: "pointer",
+                                    text_decoration: "underline",
+                                    onclick: move |_| editing.set(Some(Some(t.clone()))),
+                                    "{name}"
+                                },
+                                " ",
+                                span {
+                                    cursor: "pointer",
+                                    title: "Delete template",
+                                    onclick: move |_| {
+                                        attempt!{ state {
+                                            let db_path = state.read().db_path.clone().unwrap();
+                                            Schema::open(&db_path)?.delete_template(id)?;
+                                            cx.needs_update();
+                                            Ok(())
+                                        }}
+                                    },
+                                    "×"
+                                }
+                            })
+                        })
+                    }
+                }
+            ))
+        }
+    }
+}
+
+/// Edits an existing template, or creates a new one if `template` is None
+#[inline_props]
+fn TemplateForm<'a>(
+    cx: Scope<'a>,
+    template: Option<WithId<Template>>,
+    ondone: EventHandler<'a, ()>,
+) -> Element<'a> {
+    let state = use_shared_state::<State>(cx).unwrap();
+    let t = template.as_ref();
+    let name = t.map(|t| t.name.clone()).unwrap_or_default();
+    let kind = t.map(|t| t.kind).unwrap_or(EntityKind::Subject);
+    let body = t.map(|t| t.body.clone()).unwrap_or_default();
+    let fields = t
+        .map(|t| format_template_fields(&t.fields))
+        .unwrap_or_default();
+    let tags = t.map(|t| t.tags.join(" ")).unwrap_or_default();
+
+    render! {
+        form {
+            padding: "1em",
+            display: "flex",
+            flex_direction: "column",
+            gap: "0.5em",
+            onsubmit: move |ev| {
+                attempt!{ state {
+                    let values = &ev.data.values;
+                    let value = |key: &str| values[key][0].clone();
+                    let kind = KINDS
+                        .into_iter()
+                        .find(|k| k.table() == value("kind"))
+                        .ok_or_else(|| anyhow!("Unknown kind"))?;
+                    let new = Template {
+                        name: value("name").trim().to_string(),
+                        kind,
+                        body: value("body"),
+                        fields: parse_template_fields(&value("fields"))?,
+                        tags: value("tags")
+                            .split_whitespace()
+                            .map(|t| t.trim_start_matches('#').to_string())
+                            .filter(|t| !t.is_empty())
+                            .collect(),
+                    };
+                    if new.name.is_empty() {
+                        return Err(anyhow!("Templates need a name"));
+                    }
+                    let db_path = state.read().db_path.clone().unwrap();
+                    let mut db = Schema::open(&db_path)?;
+                    match template {
+                        Some(old) => db.update_template(&WithId { t: new, id: old.id })?,
+                        None => {
+                            db.insert_template(&new)?;
+                        }
+                    }
+                    ondone.call(());
+                    Ok(())
+                }}
+            },
+            label { "Name" },
+            input { name: "name", value: "{name}" },
+            label { "For" },
+            select {
+                name: "kind",
+                KINDS.into_iter().map(|k| {
+                    let table = k.table();
+                    rsx!(option { key: "{table}", value: "{table}", selected: k == kind, "{k}" })
+                })
+            },
+            label { "Description" },
+            textarea { name: "body", rows: "12", value: "{body}" },
+            label { "Fields, one per line, like \"Age: number = 30\"" },
+            textarea { name: "fields", rows: "6", value: "{fields}" },
+            label { "Tags, separated by spaces" },
+            input { name: "tags", value: "{tags}" },
+            div {
+                display: "flex",
+                gap: "1em",
+                input { r#type: "submit", value: "Save" },
+                input { r#type: "button", value: "Cancel", onclick: move |_| ondone.call(()) },
+            }
+        }
+    }
+}
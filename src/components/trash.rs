@@ -0,0 +1,70 @@
+#![allow(non_snake_case)]
+use dioxus::prelude::*;
+
+use crate::{
+    attempt, comp_try,
+    components::{PrimaryButton, SecondaryButton, SecretNotice},
+    schema::v1::TrashEntry,
+    ActiveMode, Schema, State,
+};
+
+/// The trashed entities, only in the GM view. Secret ones are in there as well.
+pub fn Trash(cx: Scope) -> Element {
+    let state = use_shared_state::<State>(cx).unwrap();
+    if state.read().player_view {
+        return render! { SecretNotice {} };
+    }
+    let db_path = state.read().db_path.clone().unwrap();
+    let mut db = comp_try!(state, Schema::open(&db_path));
+    let entries = comp_try!(state, db.list_trash());
+
+    if entries.is_empty() {
+        return render! { p { padding: "1em", "The trash is empty" } };
+    }
+
+    render! {
+        div {
+            padding: "1em",
+            PrimaryButton {
+                onclick: move |_| {
+                    attempt!{ state {
+                        Schema::open(&db_path)?.empty_trash()?;
+                        state.write().set_active_mode(ActiveMode::Trash);
+                        Ok(())
+                    }}
+                },
+                "Empty trash"
+            },
+            ul {
+                width: "70%",
+                padding_top: "1em",
+                entries.into_iter().map(|e| rsx!(TrashItem { entry: e }))
+            }
+        }
+    }
+}
+
+#[inline_props]
+fn TrashItem(cx: Scope, entry: TrashEntry) -> Element {
+    let state = use_shared_state::<State>(cx).unwrap();
+    render! {
+        div {
+            padding: "5px",
+            display: "flex",
+            align_items: "center",
+            gap: "5px",
+            div { flex: 1, "{entry.entity.kind}: {entry.label}" },
+            SecondaryButton {
+                onclick: move |_| {
+                    attempt!{ state {
+                        let db_path = state.read().db_path.clone().unwrap();
+                        Schema::open(&db_path)?.restore(entry.entity)?;
+                        state.write().set_active_mode(ActiveMode::Trash);
+                        Ok(())
+                    }}
+                },
+                "Restore"
+            }
+        }
+    }
+}
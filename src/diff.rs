@@ -0,0 +1,72 @@
+/// One line of a line based diff
+#[derive(Clone, Debug, PartialEq)]
+pub enum DiffLine {
+    Same(String),
+    Added(String),
+    Removed(String),
+}
+
+/// Computes a line diff that turns `old` into `new`, based on the longest common subsequence
+/// of lines. Descriptions are short, so the quadratic table is fine.
+pub fn line_diff(old: &str, new: &str) -> Vec<DiffLine> {
+    let old = old.lines().collect::<Vec<_>>();
+    let new = new.lines().collect::<Vec<_>>();
+
+    // lcs[i][j] is the length of the longest common subsequence of old[i..] and new[j..]
+    let mut lcs = vec![vec![0usize; new.len() + 1]; old.len() + 1];
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            lcs[i][j] = if old[i] == new[j] {
+                lcs[i + 1][j + 1] + 1
+            } else {
+                lcs[i + 1][j].max(lcs[i][j + 1])
+            };
+        }
+    }
+
+    let mut res = vec![];
+    let (mut i, mut j) = (0, 0);
+    while i < old.len() && j < new.len() {
+        if old[i] == new[j] {
+            res.push(DiffLine::Same(old[i].to_string()));
+            i += 1;
+            j += 1;
+        } else if lcs[i + 1][j] >= lcs[i][j + 1] {
+            res.push(DiffLine::Removed(old[i].to_string()));
+            i += 1;
+        } else {
+            res.push(DiffLine::Added(new[j].to_string()));
+            j += 1;
+        }
+    }
+    res.extend(old[i..].iter().map(|l| DiffLine::Removed(l.to_string())));
+    res.extend(new[j..].iter().map(|l| DiffLine::Added(l.to_string())));
+    res
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn diffs_changed_added_and_removed_lines() {
+        use DiffLine::*;
+        assert_eq!(
+            line_diff("a\nb\nc\nd", "a\nx\nc\nd\ne"),
+            vec![
+                Same("a".into()),
+                Removed("b".into()),
+                Added("x".into()),
+                Same("c".into()),
+                Same("d".into()),
+                Added("e".into()),
+            ]
+        );
+        assert_eq!(line_diff("", "a"), vec![Added("a".into())]);
+        assert_eq!(
+            line_diff("a\nb", ""),
+            vec![Removed("a".into()), Removed("b".into())]
+        );
+        assert_eq!(line_diff("same", "same"), vec![Same("same".into())]);
+    }
+}
@@ -0,0 +1,124 @@
+//! The folder an export is written into. Every file that is written is recorded in a manifest
+//! next to it, so the next export into the same folder can remove what the previous one left
+//! behind: pages of entities that were deleted or are hidden from the players, and their
+//! images. Files that aren't in the manifest are never touched.
+use std::collections::HashSet;
+use std::fs::{self, File, OpenOptions};
+use std::io::{ErrorKind, Write};
+use std::path::{Component, Path};
+
+use crate::schema::Result;
+
+/// Name of the manifest in the export folder, one path relative to the folder per line
+pub const MANIFEST: &str = ".campman-export";
+
+pub struct ExportDir<'a> {
+    dir: &'a Path,
+    manifest: File,
+    /// Paths written so far, relative to `dir`
+    written: HashSet<String>,
+}
+
+impl<'a> ExportDir<'a> {
+    /// Creates `dir` if it doesn't exist and removes the files of the previous export into it
+    pub fn open(dir: &'a Path) -> Result<Self> {
+        fs::create_dir_all(dir)?;
+        let manifest = dir.join(MANIFEST);
+        match fs::read_to_string(&manifest) {
+            Ok(list) => remove_listed(dir, &list)?,
+            Err(e) if e.kind() == ErrorKind::NotFound => {}
+            Err(e) => return Err(e.into()),
+        }
+        let manifest = OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(manifest)?;
+        Ok(Self {
+            dir,
+            manifest,
+            written: HashSet::new(),
+        })
+    }
+
+    pub fn create_dir(&self, path: &str) -> Result<()> {
+        fs::create_dir_all(self.dir.join(path))?;
+        Ok(())
+    }
+
+    /// Writes the file at `path`, relative to the folder. It is recorded before it is written,
+    /// so even an export that fails halfway is cleaned up by the next one.
+    pub fn write(&mut self, path: &str, data: impl AsRef<[u8]>) -> Result<()> {
+        if self.written.insert(path.to_string()) {
+            writeln!(self.manifest, "{path}")?;
+        }
+        fs::write(self.dir.join(path), data)?;
+        Ok(())
+    }
+
+    /// True if `path` was written by this export
+    pub fn contains(&self, path: &str) -> bool {
+        self.written.contains(path)
+    }
+}
+
+/// Removes the files in the manifest `list`, and the folders that are empty afterwards
+fn remove_listed(dir: &Path, list: &str) -> Result<()> {
+    let mut folders = HashSet::new();
+    for line in list.lines().filter(|l| !l.is_empty()) {
+        let path = Path::new(line);
+        // a manifest that was edited by hand must not delete anything outside the folder
+        if !path.components().all(|c| matches!(c, Component::Normal(_))) {
+            continue;
+        }
+        match fs::remove_file(dir.join(path)) {
+            Ok(()) => {}
+            Err(e) if e.kind() == ErrorKind::NotFound => {}
+            Err(e) => return Err(e.into()),
+        }
+        if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+            folders.insert(parent.to_path_buf());
+        }
+    }
+    for folder in folders {
+        // fails if the user put other files there, which then stay
+        let _ = fs::remove_dir(dir.join(folder));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn removes_only_the_files_of_the_previous_export() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().join("export");
+        fs::create_dir_all(root.join("pages")).unwrap();
+        fs::write(root.join("pages/mine.txt"), "mine").unwrap();
+
+        let mut out = ExportDir::open(&root).unwrap();
+        out.create_dir("pages").unwrap();
+        out.create_dir("images").unwrap();
+        out.write("pages/a.html", "a").unwrap();
+        out.write("images/1.png", "png").unwrap();
+        assert!(out.contains("pages/a.html"));
+        drop(out);
+
+        let mut out = ExportDir::open(&root).unwrap();
+        assert!(!root.join("pages/a.html").exists());
+        assert!(!root.join("images").exists());
+        assert!(root.join("pages/mine.txt").exists());
+        assert!(!out.contains("pages/a.html"));
+        out.write("pages/b.html", "b").unwrap();
+        drop(out);
+        assert!(root.join("pages/b.html").exists());
+
+        // nothing outside the folder, even if the manifest says so
+        fs::write(dir.path().join("outside.txt"), "").unwrap();
+        fs::write(root.join(MANIFEST), "../outside.txt\n").unwrap();
+        ExportDir::open(&root).unwrap();
+        assert!(dir.path().join("outside.txt").exists());
+    }
+}
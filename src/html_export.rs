@@ -0,0 +1,802 @@
+//! Writes the campaign as a static html wiki that can be opened in any browser or put on a web
+//! server: a page for every subject, place, group and event, an index, a timeline of events,
+//! and a search page that works without a server. Exported for the players, nothing secret
+//! ends up in the files, see [`Audience`].
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+
+use crate::calendar::{Calendar, Precision};
+use crate::export_dir::ExportDir;
+use crate::images;
+use crate::schema::{
+    v1::{escape_html, Audience, EntityKind, EntityRef, FieldValue, Linked},
+    Result,
+};
+use crate::wiki::{parse_links, render_markdown_with, LinkTarget};
+use crate::Schema;
+
+/// The kinds that get pages, in the order they are listed on the index
+const KINDS: [EntityKind; 4] = [
+    EntityKind::Subject,
+    EntityKind::Place,
+    EntityKind::Group,
+    EntityKind::Event,
+];
+
+/// What [`export_html`] has written
+#[derive(Clone, Debug, PartialEq)]
+pub struct ExportSummary {
+    /// Number of subject, place, group and event pages
+    pub pages: usize,
+    pub images: usize,
+}
+
+/// Writes the wiki into `dir`, which is created if it doesn't exist. The files of an earlier
+/// export into `dir` are removed first, see [`ExportDir`].
+pub fn export_html(db: &mut Schema, dir: &Path, audience: Audience) -> Result<ExportSummary> {
+    let mut exporter = Exporter::new(db, dir, audience)?;
+    exporter.write_all()?;
+    Ok(ExportSummary {
+        pages: exporter.pages.len(),
+        images: exporter.images.len(),
+    })
+}
+
+struct Exporter<'a> {
+    db: &'a mut Schema,
+    out: ExportDir<'a>,
+    audience: Audience,
+    calendar: Calendar,
+    /// Everything that gets a page, with its label, in the order of the index
+    entries: Vec<(EntityRef, String)>,
+    /// Path of every page, relative to `dir`
+    pages: HashMap<EntityRef, String>,
+    /// Path of every image that was written, relative to `dir`
+    images: HashMap<i64, String>,
+}
+
+impl<'a> Exporter<'a> {
+    fn new(db: &'a mut Schema, dir: &'a Path, audience: Audience) -> Result<Self> {
+        let mut candidates = vec![];
+        for kind in [EntityKind::Subject, EntityKind::Place, EntityKind::Group] {
+            for x in db.query_names(kind, "")? {
+                candidates.push((EntityRef::new(kind, x.id), x.t));
+            }
+        }
+        for ev in db.events_in_world_order()? {
+            candidates.push((EntityRef::new(EntityKind::Event, ev.id), ev.t.refered_date));
+        }
+        let mut entries = vec![];
+        for (entity, label) in candidates {
+            if db.is_visible(entity, audience)? {
+                entries.push((entity, label));
+            }
+        }
+
+        let mut pages = HashMap::new();
+        let mut taken = HashSet::new();
+        for (entity, label) in &entries {
+            let base = match entity.kind {
+                EntityKind::Event => format!("{}-{}", entity.id, slug(label)),
+                _ => slug(label),
+            };
+            let base = base.trim_end_matches('-');
+            let mut name = base.to_string();
+            // names that only differ in case or punctuation have the same slug, and the id
+            // appended to tell them apart may end up as the slug of another name, e.g. "Mira 3"
+            let mut attempt = 1;
+            while name.is_empty() || !taken.insert((entity.kind, name.clone())) {
+                name = match attempt {
+                    1 => format!("{base}-{}", entity.id),
+                    n => format!("{base}-{}-{n}", entity.id),
+                }
+                .trim_start_matches('-')
+                .to_string();
+                attempt += 1;
+            }
+            pages.insert(*entity, format!("{}/{name}.html", entity.kind.table()));
+        }
+
+        Ok(Self {
+            calendar: db.calendar()?,
+            db,
+            out: ExportDir::open(dir)?,
+            audience,
+            entries,
+            pages,
+            images: HashMap::new(),
+        })
+    }
+
+    fn write_all(&mut self) -> Result<()> {
+        self.out.create_dir("images")?;
+        for kind in KINDS {
+            self.out.create_dir(kind.table())?;
+        }
+        for (entity, label) in self.entries.clone() {
+            let html = self.entity_page(entity, &label)?;
+            let path = self.pages[&entity].clone();
+            self.out.write(&path, html)?;
+        }
+        let index = self.index_page();
+        self.out.write("index.html", index)?;
+        let timeline = self.timeline_page()?;
+        self.out.write("events.html", timeline)?;
+        let search_index = self.search_index()?;
+        self.out.write("search-index.js", search_index)?;
+        self.out
+            .write("search.html", page("Search", "", SEARCH_BODY))?;
+        self.out.write("style.css", STYLE)?;
+        Ok(())
+    }
+
+    fn entity_page(&mut self, entity: EntityRef, label: &str) -> Result<String> {
+        let mut body = format!(
+            "<p class=\"kind\">{}</p>\n<h1>{}</h1>\n",
+            entity.kind,
+            escape_html(label)
+        );
+        if self.audience == Audience::GameMaster && self.db.is_secret(entity)? {
+            body.push_str("<p class=\"secret-badge\">Secret</p>\n");
+        }
+        body.push_str(&self.breadcrumbs(entity)?);
+        if entity.kind == EntityKind::Event {
+            body.push_str(&self.event_info(entity.id)?);
+        }
+        body.push_str(&self.gallery(entity, label)?);
+        body.push_str(&self.tags(entity)?);
+        body.push_str(&self.facts(entity)?);
+
+        let description = self
+            .db
+            .visible_description(entity, Audience::GameMaster)?
+            .unwrap_or_default();
+        let pages = &self.pages;
+        let html =
+            render_markdown_with(
+                self.db,
+                &description,
+                self.audience,
+                &mut |target| match target {
+                    LinkTarget::Entity(e) => pages.get(e).map(|p| format!("../{p}")),
+                    LinkTarget::Missing(_) => None,
+                },
+            )?;
+        body.push_str(&format!("<div class=\"description\">\n{html}</div>\n"));
+
+        if entity.kind == EntityKind::Place {
+            body.push_str(&self.place_map(entity.id)?);
+        }
+        body.push_str(&self.children(entity)?);
+        if entity.kind == EntityKind::Subject {
+            body.push_str(&self.relationships(entity.id)?);
+        }
+        body.push_str(&self.related(entity)?);
+        body.push_str(&self.backlinks(entity)?);
+        Ok(page(label, "../", &body))
+    }
+
+    /// A link to the page of `entity` from another entity page, or just the label if the
+    /// entity has no page
+    fn link(&self, entity: EntityRef, label: &str) -> String {
+        match self.pages.get(&entity) {
+            Some(path) => format!("<a href=\"../{path}\">{}</a>", escape_html(label)),
+            None => escape_html(label),
+        }
+    }
+
+    /// The ancestors of places and groups
+    fn breadcrumbs(&mut self, entity: EntityRef) -> Result<String> {
+        let ancestors: Vec<_> = match entity.kind {
+            EntityKind::Place => self
+                .db
+                .place_path(entity.id)?
+                .into_iter()
+                .map(|p| (p.id, p.t.name))
+                .collect(),
+            EntityKind::Group => self
+                .db
+                .group_path(entity.id)?
+                .into_iter()
+                .map(|g| (g.id, g.t.name))
+                .collect(),
+            _ => return Ok(String::new()),
+        };
+        let links: Vec<_> = ancestors
+            .into_iter()
+            .filter(|(id, _)| *id != entity.id)
+            .map(|(id, name)| (EntityRef::new(entity.kind, id), name))
+            .filter(|(e, _)| self.pages.contains_key(e))
+            .map(|(e, name)| self.link(e, &name))
+            .collect();
+        if links.is_empty() {
+            return Ok(String::new());
+        }
+        Ok(format!(
+            "<p class=\"breadcrumbs\">{}</p>\n",
+            links.join(" / ")
+        ))
+    }
+
+    /// The in-world date with its weekday, and the session an event was played in
+    fn event_info(&mut self, id: i64) -> Result<String> {
+        let Some(ev) = self.db.get_event(id)? else {
+            return Ok(String::new());
+        };
+        let mut res = String::new();
+        if let Some(date) = ev.date {
+            let day = match self.calendar.weekday(date) {
+                Some(weekday) => format!("{weekday}, {}", self.calendar.format(date)),
+                None => self.calendar.format(date),
+            };
+            res.push_str(&format!("<p class=\"date\">{}</p>\n", escape_html(&day)));
+        }
+        if let Some(id) = ev.session {
+            if let Some(session) = self.db.get_session(id)? {
+                res.push_str(&format!(
+                    "<p class=\"session\">Session {}: {}</p>\n",
+                    session.number,
+                    escape_html(&session.title)
+                ));
+            }
+        }
+        Ok(res)
+    }
+
+    /// The portrait at the top, and all other images as thumbnails that link to the full image
+    fn gallery(&mut self, entity: EntityRef, label: &str) -> Result<String> {
+        let mut portrait = String::new();
+        let mut figures = String::new();
+        for a in self.db.attachments_of(entity)? {
+            let Some(file) = self.image_file(a.id)? else {
+                continue;
+            };
+            let caption = escape_html(&a.caption);
+            if a.portrait {
+                portrait = format!(
+                    "<img class=\"portrait\" src=\"../{file}\" alt=\"{}\">\n",
+                    escape_html(label)
+                );
+                continue;
+            }
+            let thumbnail = self.thumbnail_file(a.id)?.unwrap_or_else(|| file.clone());
+            figures.push_str(&format!(
+                "<figure><a href=\"../{file}\"><img src=\"../{thumbnail}\" alt=\"{caption}\"></a>\
+                 <figcaption>{caption}</figcaption></figure>\n"
+            ));
+        }
+        if !figures.is_empty() {
+            figures = format!("<div class=\"gallery\">\n{figures}</div>\n");
+        }
+        Ok(portrait + &figures)
+    }
+
+    fn tags(&mut self, entity: EntityRef) -> Result<String> {
+        let tags: Vec<_> = self
+            .db
+            .tags_of(entity)?
+            .into_iter()
+            .filter(|t| self.audience.sees(t.secret))
+            .map(|t| format!("#{}", escape_html(&t.label)))
+            .collect();
+        if tags.is_empty() {
+            return Ok(String::new());
+        }
+        Ok(format!("<p class=\"tags\">{}</p>\n", tags.join(" ")))
+    }
+
+    /// The custom fields. References to entities without a page are left out.
+    fn facts(&mut self, entity: EntityRef) -> Result<String> {
+        let mut rows = String::new();
+        for field in self.db.fields_of(entity)? {
+            let value = match &field.value {
+                FieldValue::Reference(r) if self.pages.contains_key(r) => {
+                    let label = self.db.entity_label(*r)?.unwrap_or_default();
+                    self.link(*r, &label)
+                }
+                FieldValue::Reference(_) => continue,
+                value => escape_html(&self.db.field_text(value)?),
+            };
+            rows.push_str(&format!(
+                "<tr><th>{}</th><td>{value}</td></tr>\n",
+                escape_html(&field.name)
+            ));
+        }
+        if rows.is_empty() {
+            return Ok(rows);
+        }
+        Ok(format!("<table class=\"facts\">\n{rows}</table>\n"))
+    }
+
+    /// The map of a place, with a link on every pin
+    fn place_map(&mut self, place: i64) -> Result<String> {
+        let Some(map) = self.db.place_map(place)? else {
+            return Ok(String::new());
+        };
+        let Some(file) = self.image_file(map)? else {
+            return Ok(String::new());
+        };
+        let mut res = format!("<div class=\"map\">\n<img src=\"../{file}\" alt=\"Map\">\n");
+        for pin in self.db.map_pins(place)? {
+            if !self.pages.contains_key(&pin.target) {
+                continue;
+            }
+            let label = self.db.entity_label(pin.target)?.unwrap_or_default();
+            res.push_str(&format!(
+                "<span class=\"pin\" style=\"left: {:.2}%; top: {:.2}%;\">{}</span>\n",
+                pin.x * 100.0,
+                pin.y * 100.0,
+                self.link(pin.target, &label)
+            ));
+        }
+        res.push_str("</div>\n");
+        Ok(res)
+    }
+
+    /// The places inside a place, or the groups inside a group
+    fn children(&mut self, entity: EntityRef) -> Result<String> {
+        let children: Vec<_> = match entity.kind {
+            EntityKind::Place => self
+                .db
+                .child_places(entity.id)?
+                .into_iter()
+                .map(|p| (p.id, p.t.name))
+                .collect(),
+            EntityKind::Group => self
+                .db
+                .child_groups(entity.id)?
+                .into_iter()
+                .map(|g| (g.id, g.t.name))
+                .collect(),
+            _ => return Ok(String::new()),
+        };
+        let items: Vec<_> = children
+            .into_iter()
+            .map(|(id, name)| (EntityRef::new(entity.kind, id), name))
+            .filter(|(e, _)| self.pages.contains_key(e))
+            .map(|(e, name)| format!("<li>{}</li>\n", self.link(e, &name)))
+            .collect();
+        Ok(list("Contains", &items))
+    }
+
+    fn relationships(&mut self, subject: i64) -> Result<String> {
+        let mut items = vec![];
+        for rel in self.db.relationships_of(subject)? {
+            let other = EntityRef::new(EntityKind::Subject, rel.other_id);
+            if !self.audience.sees(rel.rel.secret) || !self.pages.contains_key(&other) {
+                continue;
+            }
+            let label = match rel.label() {
+                l if l.is_empty() => "Linked".to_string(),
+                l => l,
+            };
+            let period = match (&rel.rel.valid_from, &rel.rel.valid_to) {
+                (None, None) => "".to_string(),
+                (Some(from), None) => format!(" (since {from})"),
+                (None, Some(to)) => format!(" (until {to})"),
+                (Some(from), Some(to)) => format!(" ({from} – {to})"),
+            };
+            items.push(format!(
+                "<li><em>{}</em> {}{}</li>\n",
+                escape_html(&label),
+                self.link(other, &rel.other_name),
+                escape_html(&period)
+            ));
+        }
+        Ok(list("Relationships", &items))
+    }
+
+    /// Everything linked to `entity` in either direction, except tags. Links between subjects
+    /// are shown as relationships instead.
+    fn related(&mut self, entity: EntityRef) -> Result<String> {
+        let mut links: Vec<Linked> = self.db.outgoing_links(entity)?;
+        for l in self.db.incoming_links(entity)? {
+            if !links.iter().any(|x| x.entity == l.entity) {
+                links.push(l);
+            }
+        }
+        links.retain(|l| {
+            l.entity.kind != EntityKind::Tag
+                && !(entity.kind == EntityKind::Subject && l.entity.kind == EntityKind::Subject)
+                && self.audience.sees(l.secret)
+                && self.pages.contains_key(&l.entity)
+        });
+        links.sort_by(|a, b| (a.entity.kind, &a.label).cmp(&(b.entity.kind, &b.label)));
+        let items: Vec<_> = links
+            .iter()
+            .map(|l| {
+                format!(
+                    "<li>{} <span class=\"kind\">{}</span></li>\n",
+                    self.link(l.entity, &l.label),
+                    l.entity.kind
+                )
+            })
+            .collect();
+        Ok(list("Related", &items))
+    }
+
+    fn backlinks(&mut self, entity: EntityRef) -> Result<String> {
+        let items: Vec<_> = self
+            .db
+            .backlinks(entity, self.audience)?
+            .into_iter()
+            .filter(|b| self.pages.contains_key(&b.source))
+            .map(|b| {
+                let snippet = b
+                    .snippet
+                    .map(|s| format!("<div class=\"snippet\">{}</div>", escape_html(&s)))
+                    .unwrap_or_default();
+                format!("<li>{}{snippet}</li>\n", self.link(b.source, &b.label))
+            })
+            .collect();
+        Ok(list("Mentioned by", &items))
+    }
+
+    /// Writes the image once, and returns its path
+    fn image_file(&mut self, id: i64) -> Result<Option<String>> {
+        if let Some(path) = self.images.get(&id) {
+            return Ok(Some(path.clone()));
+        }
+        let Some((mime, data)) = self.db.attachment_data(id)? else {
+            return Ok(None);
+        };
+        let path = format!("images/{id}.{}", images::extension(&mime));
+        self.out.write(&path, data)?;
+        self.images.insert(id, path.clone());
+        Ok(Some(path))
+    }
+
+    fn thumbnail_file(&mut self, id: i64) -> Result<Option<String>> {
+        let path = format!("images/{id}-thumbnail.png");
+        if self.out.contains(&path) {
+            return Ok(Some(path));
+        }
+        let Some(data) = self.db.thumbnail(id)? else {
+            return Ok(None);
+        };
+        self.out.write(&path, data)?;
+        Ok(Some(path))
+    }
+
+    fn index_page(&self) -> String {
+        let mut body = String::from("<h1>Campaign wiki</h1>\n");
+        body.push_str(
+            "<p><a href=\"events.html\">Timeline</a> · <a href=\"search.html\">Search</a></p>\n",
+        );
+        for (kind, title) in [
+            (EntityKind::Subject, "Subjects"),
+            (EntityKind::Place, "Places"),
+            (EntityKind::Group, "Groups"),
+        ] {
+            let items: Vec<_> = self
+                .entries
+                .iter()
+                .filter(|(e, _)| e.kind == kind)
+                .map(|(e, label)| {
+                    format!(
+                        "<li><a href=\"{}\">{}</a></li>\n",
+                        self.pages[e],
+                        escape_html(label)
+                    )
+                })
+                .collect();
+            if !items.is_empty() {
+                body.push_str(&format!(
+                    "<h2 id=\"{}\">{title}</h2>\n<ul>\n{}</ul>\n",
+                    kind.table(),
+                    items.concat()
+                ));
+            }
+        }
+        page("Campaign wiki", "", &body)
+    }
+
+    /// All events in world order, grouped by year
+    fn timeline_page(&mut self) -> Result<String> {
+        let mut body = String::from("<h1>Timeline</h1>\n");
+        let mut period = None;
+        for (entity, label) in self.entries.clone() {
+            if entity.kind != EntityKind::Event {
+                continue;
+            }
+            let Some(ev) = self.db.get_event(entity.id)? else {
+                continue;
+            };
+            let this_period = match ev.date {
+                Some(date) => self.calendar.format_period(date, Precision::Year),
+                None => "Undated".to_string(),
+            };
+            if period.as_ref() != Some(&this_period) {
+                if period.is_some() {
+                    body.push_str("</ul>\n");
+                }
+                body.push_str(&format!("<h2>{}</h2>\n<ul>\n", escape_html(&this_period)));
+                period = Some(this_period);
+            }
+            let text = self
+                .db
+                .visible_description(entity, self.audience)?
+                .unwrap_or_default();
+            let summary = plain_text(&text).chars().take(120).collect::<String>();
+            body.push_str(&format!(
+                "<li><a href=\"{}\">{}</a> {}</li>\n",
+                self.pages[&entity],
+                escape_html(&label),
+                escape_html(&summary)
+            ));
+        }
+        if period.is_some() {
+            body.push_str("</ul>\n");
+        } else {
+            body.push_str("<p>No events yet.</p>\n");
+        }
+        Ok(page("Timeline", "", &body))
+    }
+
+    /// A script that defines the searchable text of every page, for the search page
+    fn search_index(&mut self) -> Result<String> {
+        let mut entries = vec![];
+        for (entity, label) in self.entries.clone() {
+            let text = self
+                .db
+                .visible_description(entity, self.audience)?
+                .unwrap_or_default();
+            entries.push(format!(
+                "{{\"title\":{},\"kind\":{},\"url\":{},\"text\":{}}}",
+                json_string(&label),
+                json_string(&entity.kind.to_string()),
+                json_string(&self.pages[&entity]),
+                json_string(&plain_text(&text))
+            ));
+        }
+        Ok(format!(
+            "window.SEARCH_INDEX = [\n{}\n];\n",
+            entries.join(",\n")
+        ))
+    }
+}
+
+/// A section with a heading and a list, or nothing if there are no items
+fn list(title: &str, items: &[String]) -> String {
+    if items.is_empty() {
+        return String::new();
+    }
+    format!("<h2>{title}</h2>\n<ul>\n{}</ul>\n", items.concat())
+}
+
+/// A complete html document. `root` leads from the page to the root of the export.
+fn page(title: &str, root: &str, body: &str) -> String {
+    format!(
+        "<!DOCTYPE html>
+<html lang=\"en\">
+<head>
+<meta charset=\"utf-8\">
+<meta name=\"viewport\" content=\"width=device-width, initial-scale=1\">
+<title>{title}</title>
+<link rel=\"stylesheet\" href=\"{root}style.css\">
+</head>
+<body>
+<nav>
+<a href=\"{root}index.html\">Index</a>
+<a href=\"{root}index.html#subjects\">Subjects</a>
+<a href=\"{root}index.html#places\">Places</a>
+<a href=\"{root}index.html#groups\">Groups</a>
+<a href=\"{root}events.html\">Timeline</a>
+<a href=\"{root}search.html\">Search</a>
+</nav>
+<main>
+{body}</main>
+</body>
+</html>
+",
+        title = escape_html(title)
+    )
+}
+
+/// A file name for a page: lower case letters and digits, everything else becomes a dash
+fn slug(name: &str) -> String {
+    let mut res = String::new();
+    for c in crate::schema::v1::search_key(name).chars() {
+        if c.is_ascii_alphanumeric() {
+            res.push(c);
+        } else if !res.is_empty() && !res.ends_with('-') {
+            res.push('-');
+        }
+    }
+    res.trim_end_matches('-').to_string()
+}
+
+/// The text of a description without markup of wiki links, on a single line
+fn plain_text(text: &str) -> String {
+    let mut res = String::with_capacity(text.len());
+    let mut last = 0;
+    for link in parse_links(text) {
+        res.push_str(&text[last..link.range.start]);
+        res.push_str(link.display());
+        last = link.range.end;
+    }
+    res.push_str(&text[last..]);
+    res.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+fn json_string(s: &str) -> String {
+    let mut res = String::with_capacity(s.len() + 2);
+    res.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => res.push_str("\\\""),
+            '\\' => res.push_str("\\\\"),
+            // keeps the script from being ended by text like `</script>`
+            '<' => res.push_str("\\u003c"),
+            c if (c as u32) < 0x20 => res.push_str(&format!("\\u{:04x}", c as u32)),
+            c => res.push(c),
+        }
+    }
+    res.push('"');
+    res
+}
+
+const SEARCH_BODY: &str = r#"<h1>Search</h1>
+<input id="query" type="search" placeholder="Search names and descriptions" autofocus>
+<ul id="results"></ul>
+<script src="search-index.js"></script>
+<script>
+function normalize(s) {
+    return s.normalize('NFD').replace(/[\u0300-\u036f]/g, '').toLowerCase();
+}
+const entries = window.SEARCH_INDEX.map(e =>
+    Object.assign({ key: normalize(e.title + ' ' + e.text) }, e));
+const input = document.getElementById('query');
+const results = document.getElementById('results');
+function search() {
+    const words = normalize(input.value).split(/\s+/).filter(w => w);
+    results.innerHTML = '';
+    if (words.length === 0) return;
+    for (const e of entries.filter(e => words.every(w => e.key.includes(w)))) {
+        const li = document.createElement('li');
+        const a = document.createElement('a');
+        a.href = e.url;
+        a.textContent = e.title;
+        const kind = document.createElement('span');
+        kind.className = 'kind';
+        kind.textContent = ' ' + e.kind;
+        li.append(a, kind);
+        const at = normalize(e.text).indexOf(words[0]);
+        if (at >= 0) {
+            const snippet = document.createElement('div');
+            snippet.className = 'snippet';
+            const start = Math.max(0, at - 60);
+            snippet.textContent = (start > 0 ? '…' : '') + e.text.slice(start, at + 60) + '…';
+            li.append(snippet);
+        }
+        results.append(li);
+    }
+}
+input.addEventListener('input', search);
+</script>
+"#;
+
+const STYLE: &str = "body { margin: 0; font-family: sans-serif; line-height: 1.5; color: #222; }
+nav { background-color: #26B3D1; padding: 0.8em 1em; }
+nav a { color: white; font-weight: bold; margin-right: 1em; text-decoration: none; }
+main { max-width: 50em; margin: 0 auto; padding: 1em; }
+a { color: #1D7C92; }
+.kind { color: grey; font-size: small; }
+.secret-badge { display: inline-block; background-color: #B00020; color: white; padding: 0 0.5em; border-radius: 0.5em; }
+.portrait { max-width: 100%; max-height: 25em; border-radius: 10px; }
+.gallery { display: flex; flex-wrap: wrap; gap: 1em; }
+.gallery figure { margin: 0; width: 10em; }
+.gallery img { max-width: 100%; border-radius: 6px; }
+.gallery figcaption { font-size: small; color: grey; }
+.tags { color: #1D7C92; }
+.facts th { text-align: left; padding-right: 1em; }
+.map { position: relative; display: inline-block; max-width: 100%; }
+.map img { max-width: 100%; display: block; }
+.map .pin { position: absolute; transform: translate(-50%, -100%); background-color: white; border: 1px solid black; border-radius: 1em; padding: 0 0.5em; font-size: small; white-space: nowrap; }
+.snippet { color: grey; font-size: small; }
+#query { width: 100%; font-size: 1.2em; padding: 0.3em 0.6em; border-radius: 20px; border: 1px solid grey; }
+";
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::schema::v1::{Place, Subject};
+    use std::fs;
+
+    fn read_all(dir: &Path) -> String {
+        let mut res = String::new();
+        for entry in fs::read_dir(dir).unwrap() {
+            let path = entry.unwrap().path();
+            if path.is_dir() {
+                res.push_str(&read_all(&path));
+            } else if let Ok(text) = fs::read_to_string(&path) {
+                res.push_str(&text);
+            }
+        }
+        res
+    }
+
+    #[test]
+    fn pages_get_unique_names() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut db = Schema::create_new(dir.path().join("test.db")).unwrap();
+        // the slug of the third is taken by the first, its fallback by the second
+        for name in ["Mira", "Mira 3", "Mira!"] {
+            db.insert_subject(&Subject {
+                name: name.into(),
+                description: "".into(),
+            })
+            .unwrap();
+        }
+
+        let out = dir.path().join("out");
+        assert_eq!(
+            export_html(&mut db, &out, Audience::GameMaster)
+                .unwrap()
+                .pages,
+            3
+        );
+        assert_eq!(fs::read_dir(out.join("subjects")).unwrap().count(), 3);
+        assert!(out.join("subjects/mira-3-2.html").exists());
+    }
+
+    #[test]
+    fn exports_linked_pages_without_secrets_for_players() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut db = Schema::create_new(dir.path().join("test.db")).unwrap();
+        db.insert_subject(&Subject {
+            name: "Mira".into(),
+            description: "Runs [[The Rusty Anchor]].\n:::secret\nA dragon in disguise.\n:::\n"
+                .into(),
+        })
+        .unwrap();
+        let vex = db
+            .insert_subject(&Subject {
+                name: "Vex".into(),
+                description: "The villain".into(),
+            })
+            .unwrap();
+        db.set_secret(EntityRef::new(EntityKind::Subject, vex), true)
+            .unwrap();
+        db.insert_place(&Place {
+            name: "The Rusty Anchor".into(),
+            description: "Visited by [[Vex]].".into(),
+            parent_place: None,
+        })
+        .unwrap();
+
+        let out = dir.path().join("players");
+        let summary = export_html(&mut db, &out, Audience::Players).unwrap();
+        assert_eq!(summary.pages, 2);
+        let mira_page = fs::read_to_string(out.join("subjects/mira.html")).unwrap();
+        assert!(mira_page.contains("<a href=\"../places/the-rusty-anchor.html\">"));
+        assert!(out.join("index.html").exists());
+        assert!(out.join("search.html").exists());
+        let all = read_all(&out);
+        assert!(!all.contains("dragon"));
+        assert!(!all.contains("villain"));
+        assert!(!out.join("subjects/vex.html").exists());
+
+        let out = dir.path().join("gm");
+        let summary = export_html(&mut db, &out, Audience::GameMaster).unwrap();
+        assert_eq!(summary.pages, 3);
+        let all = read_all(&out);
+        assert!(all.contains("dragon"));
+        assert!(all.contains("villain"));
+
+        // exporting for the players over it leaves nothing of the game master's export
+        export_html(&mut db, &out, Audience::Players).unwrap();
+        assert!(!out.join("subjects/vex.html").exists());
+        let all = read_all(&out);
+        assert!(!all.contains("dragon"));
+        assert!(!all.contains("villain"));
+    }
+
+    #[test]
+    fn slugs_and_json() {
+        assert_eq!(slug("Éowyn of Rohan!"), "eowyn-of-rohan");
+        assert_eq!(slug("???"), "");
+        assert_eq!(json_string("a\"b\\c\n</"), "\"a\\\"b\\\\c\\u000a\\u003c/\"");
+        assert_eq!(plain_text("See [[Mira|her]]\n  now"), "See her now");
+    }
+}
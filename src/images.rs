@@ -0,0 +1,86 @@
+//! Checking and scaling of images that are attached to entities, see
+//! [`crate::schema::v1::Schema::attach_image`]
+use std::io::Cursor;
+
+use base64::Engine;
+use image::{ImageFormat, ImageOutputFormat};
+
+use crate::schema::{Error, Result};
+
+/// Thumbnails fit into a square of this many pixels
+pub const THUMBNAIL_SIZE: u32 = 256;
+
+/// What is learned about an image when it is imported
+pub struct ImportedImage {
+    pub mime: &'static str,
+    pub width: u32,
+    pub height: u32,
+    /// PNG encoded
+    pub thumbnail: Vec<u8>,
+}
+
+/// Decodes a PNG, JPEG or WebP image and renders its thumbnail. Fails with
+/// [`Error::UnsupportedImage`] for anything else.
+pub fn import(data: &[u8]) -> Result<ImportedImage> {
+    let unsupported = |e: String| Error::UnsupportedImage(e);
+    let format = image::guess_format(data).map_err(|_| unsupported("unknown format".into()))?;
+    let mime = match format {
+        ImageFormat::Png => "image/png",
+        ImageFormat::Jpeg => "image/jpeg",
+        ImageFormat::WebP => "image/webp",
+        other => {
+            return Err(unsupported(format!(
+                "{other:?} is not supported, use PNG, JPEG or WebP"
+            )))
+        }
+    };
+    let img = image::load_from_memory_with_format(data, format)
+        .map_err(|e| unsupported(e.to_string()))?;
+    let mut thumbnail = vec![];
+    img.thumbnail(THUMBNAIL_SIZE, THUMBNAIL_SIZE)
+        .write_to(&mut Cursor::new(&mut thumbnail), ImageOutputFormat::Png)
+        .map_err(|e| unsupported(e.to_string()))?;
+    Ok(ImportedImage {
+        mime,
+        width: img.width(),
+        height: img.height(),
+        thumbnail,
+    })
+}
+
+/// Embeds an image into html, e.g. as `src` of an `img`
+pub fn data_url(mime: &str, data: &[u8]) -> String {
+    format!(
+        "data:{mime};base64,{}",
+        base64::engine::general_purpose::STANDARD.encode(data)
+    )
+}
+
+/// The usual file extension for images of the given mime type
+pub fn extension(mime: &str) -> &'static str {
+    match mime {
+        "image/jpeg" => "jpg",
+        "image/webp" => "webp",
+        _ => "png",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::png;
+
+    #[test]
+    fn imports_png_and_scales_thumbnail() {
+        let img = import(&png(1024, 512)).unwrap();
+        assert_eq!(img.mime, "image/png");
+        assert_eq!((img.width, img.height), (1024, 512));
+        let thumb = image::load_from_memory(&img.thumbnail).unwrap();
+        assert_eq!((thumb.width(), thumb.height()), (256, 128));
+    }
+
+    #[test]
+    fn rejects_other_files() {
+        assert!(import(b"definitely not an image").is_err());
+    }
+}
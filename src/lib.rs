@@ -1,6 +1,17 @@
 pub mod actions;
+pub mod calendar;
 pub mod components;
+pub mod diff;
+pub mod export_dir;
+pub mod html_export;
+pub mod images;
 pub mod schema;
+#[cfg(test)]
+mod test_util;
+pub mod vault;
+pub mod wiki;
+
+use schema::v1::{Audience, EntityKind, EntityRef};
 
 pub type Schema = schema::v1::Schema;
 
@@ -8,8 +19,16 @@ pub type Schema = schema::v1::Schema;
 pub enum Mode {
     Dashboard,
     Active(ActiveMode),
-    Error { err: String, parent: Box<Mode> },
+    Error {
+        err: String,
+        parent: Box<Mode>,
+    },
     EditingSubject(String),
+    ConfirmDelete {
+        entity: EntityRef,
+        label: String,
+        parent: Box<Mode>,
+    },
     Fatal(String),
 }
 
@@ -17,13 +36,41 @@ pub enum Mode {
 pub enum ActiveMode {
     Events,
     NewSubject,
+    NewPlace,
+    NewGroup,
+    NewEvent,
     Subject(String),
+    Place(String),
+    Group(String),
     Search,
+    Trash,
+    Tags,
+    Templates,
+    Calendar,
+    Sessions,
+    Export,
+    Import,
+}
+
+impl ActiveMode {
+    /// The view that shows the entity with the given kind and name. Events are only shown in the
+    /// list of all events, tags have no view of their own.
+    pub fn view(kind: EntityKind, name: String) -> Option<Self> {
+        match kind {
+            EntityKind::Subject => Some(ActiveMode::Subject(name)),
+            EntityKind::Place => Some(ActiveMode::Place(name)),
+            EntityKind::Group => Some(ActiveMode::Group(name)),
+            EntityKind::Event => Some(ActiveMode::Events),
+            EntityKind::Tag => None,
+        }
+    }
 }
 
 pub struct State {
     pub mode: Mode,
     pub db_path: Option<String>,
+    /// Hides everything secret, so the screen can be shown to the players
+    pub player_view: bool,
     pub user_dirs: directories::UserDirs,
 }
 
@@ -35,6 +82,15 @@ impl State {
     pub fn set_active_mode(&mut self, mode: ActiveMode) {
         self.mode = Mode::Active(mode);
     }
+
+    /// Who the screen currently is for
+    pub fn audience(&self) -> Audience {
+        if self.player_view {
+            Audience::Players
+        } else {
+            Audience::GameMaster
+        }
+    }
 }
 
 #[macro_export]
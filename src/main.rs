@@ -6,7 +6,8 @@ use dioxus_desktop::{use_window, Config, WindowBuilder};
 
 use campman::{
     components::{self, PrimaryButton},
-    ActiveMode, Mode, Schema, State,
+    schema::v1::EntityKind,
+    wiki, ActiveMode, Mode, Schema, State,
 };
 
 fn main() {
@@ -24,6 +25,7 @@ fn App(cx: Scope) -> Element {
     use_shared_state_provider(cx, || State {
         mode: Mode::Dashboard,
         db_path: None,
+        player_view: false,
         user_dirs: directories::UserDirs::new().expect("Couldn't find home dir"),
     });
     let state = use_shared_state::<State>(cx).unwrap();
@@ -40,7 +42,11 @@ fn App(cx: Scope) -> Element {
                     font-family: 'Arial', sans-serif;
                     user-select: none;
                     transition: .2s all;
+                }}
+                a[href^="wiki:missing/"] {{
+                    color: red;
                 }}"},
+            script { "{wiki::LINK_HANDLER_JS}" },
             Main { mode: state.read().mode.clone() }
         }
 
@@ -56,12 +62,31 @@ fn Main(cx: Scope, mode: Mode) -> Element {
         Mode::Error { .. } => {
             render! { components::Error {} }
         }
+        Mode::ConfirmDelete { .. } => {
+            render! { components::ConfirmDelete {} }
+        }
         Mode::Active(sub_mode) => {
             let Child = match sub_mode {
                 ActiveMode::Events => render! { components::Events {} },
                 ActiveMode::Search => render! { components::Search {} },
                 ActiveMode::NewSubject => render! { components::NewSubject {} },
+                ActiveMode::NewPlace => {
+                    render! { components::NewEntity { kind: EntityKind::Place } }
+                }
+                ActiveMode::NewGroup => {
+                    render! { components::NewEntity { kind: EntityKind::Group } }
+                }
+                ActiveMode::NewEvent => render! { components::NewEvent {} },
                 ActiveMode::Subject(name) => render! { components::Subject {name: name.clone()} },
+                ActiveMode::Place(name) => render! { components::Place {name: name.clone()} },
+                ActiveMode::Group(name) => render! { components::Group {name: name.clone()} },
+                ActiveMode::Trash => render! { components::Trash {} },
+                ActiveMode::Tags => render! { components::TagCloud {} },
+                ActiveMode::Templates => render! { components::Templates {} },
+                ActiveMode::Calendar => render! { components::CalendarSettings {} },
+                ActiveMode::Sessions => render! { components::Sessions {} },
+                ActiveMode::Export => render! { components::Export {} },
+                ActiveMode::Import => render! { components::Import {} },
             };
 
             render! {
@@ -0,0 +1,913 @@
+use super::{Error, Result};
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use unicode_normalization::{char::is_combining_mark, UnicodeNormalization};
+
+/// A migration moves the database from one version to the next. It runs inside a transaction
+/// that is opened and committed by [`migrate`], so it must not manage transactions itself.
+type Migration = fn(&sqlite::Connection) -> Result<()>;
+
+/// Ordered list of migrations. The entry at index `i` migrates a database from version `i + 1`
+/// to version `i + 2`. Version 1 is the layout created by [`super::v1::Schema`]'s table
+/// creation functions. Never change or reorder existing entries, only append new ones, and
+/// don't call code of the app from them, that may change later: a migration has to do the
+/// same to every file, no matter which version of campman runs it.
+const MIGRATIONS: &[Migration] = &[
+    v2_event_columns,
+    v3_unique_links,
+    v4_relationships,
+    v5_trash,
+    v6_revisions,
+    v7_full_text_search,
+    v8_name_keys,
+    v9_unique_names,
+    v10_wiki_links,
+    v11_entity_tags,
+    v12_fields,
+    v13_templates,
+    v14_attachments,
+    v15_place_maps,
+    v16_calendar,
+    v17_sessions,
+    v18_secrets,
+];
+
+/// The schema version this build of the app reads and writes
+pub const CURRENT_VERSION: i64 = 1 + MIGRATIONS.len() as i64;
+
+/// Reads the version stamp of the database. Files created before versioning was introduced
+/// have no stamp, but contain the v1 tables, and are reported as version 1. A database without
+/// any tables is reported as version 0.
+pub fn version(conn: &sqlite::Connection) -> Result<i64> {
+    let version = query_i64(conn, "pragma user_version;")?;
+    if version == 0 && has_table(conn, "subjects")? {
+        Ok(1)
+    } else {
+        Ok(version)
+    }
+}
+
+pub fn set_version(conn: &sqlite::Connection, version: i64) -> Result<()> {
+    // pragmas can't take bound parameters, but the version is an integer, so this is safe
+    conn.execute(format!("pragma user_version = {version};"))?;
+    Ok(())
+}
+
+/// Brings the database up to [`CURRENT_VERSION`]. If there is anything to do, a copy of the
+/// file is written next to it first (see [`backup_path`]). Each migration runs in its own
+/// transaction together with the version bump, so a failing migration leaves the database at
+/// the last successfully applied version.
+pub fn migrate(conn: &sqlite::Connection, path: &Path) -> Result<()> {
+    let found = version(conn)?;
+    if found > CURRENT_VERSION {
+        return Err(Error::DbTooNew {
+            found,
+            supported: CURRENT_VERSION,
+        });
+    }
+    if found == CURRENT_VERSION {
+        return Ok(());
+    }
+
+    let backup = backup_path(path, found);
+    log::info!(
+        "Migrating {} from v{found} to v{CURRENT_VERSION}, backup at {}",
+        path.display(),
+        backup.display()
+    );
+    fs::copy(path, &backup)?;
+    apply_from(conn, found)
+}
+
+/// Applies all migrations that come after `from`, without taking a backup. Used directly when
+/// a new database is created.
+pub fn apply_from(conn: &sqlite::Connection, from: i64) -> Result<()> {
+    apply(conn, from, MIGRATIONS)
+}
+
+fn apply(conn: &sqlite::Connection, from: i64, migrations: &[Migration]) -> Result<()> {
+    for (i, migration) in migrations.iter().enumerate().skip((from - 1) as usize) {
+        let target = i as i64 + 2;
+        log::debug!("Applying migration to v{target}");
+        conn.execute("begin transaction;")?;
+        let res = migration(conn).and_then(|_| set_version(conn, target));
+        match res {
+            Ok(()) => conn.execute("commit;")?,
+            Err(e) => {
+                conn.execute("rollback;")?;
+                return Err(Error::Migration {
+                    target,
+                    source: Box::new(e),
+                });
+            }
+        }
+    }
+    Ok(())
+}
+
+/// The v1 events table used column names that didn't match the `Event` struct
+fn v2_event_columns(conn: &sqlite::Connection) -> Result<()> {
+    conn.execute(
+        "
+        alter table events rename column happened_at to refered_date;
+        alter table events rename column edited_at to record_date;
+        create index events_record_date on events(record_date);
+        create index events_refered_date on events(refered_date); ",
+    )?;
+    Ok(())
+}
+
+/// Removes duplicated links, and makes sure no new ones can be created
+fn v3_unique_links(conn: &sqlite::Connection) -> Result<()> {
+    for table in [
+        "mapping_subjects_subjects",
+        "mapping_subjects_groups",
+        "mapping_subjects_places",
+        "mapping_events_subjects",
+        "mapping_events_groups",
+        "mapping_events_places",
+        "mapping_events_tags",
+        "mapping_places_groups",
+    ] {
+        conn.execute(format!(
+            "
+            delete from {table} where rowid not in (
+                select min(rowid) from {table} group by kfrom, kto
+            );
+            create unique index {table}_unique on {table}(kfrom, kto); "
+        ))?;
+    }
+    Ok(())
+}
+
+/// Turns subject to subject links into labelled relationships. Two subjects can now be linked
+/// multiple times, as long as the labels differ.
+fn v4_relationships(conn: &sqlite::Connection) -> Result<()> {
+    conn.execute(
+        "
+        alter table mapping_subjects_subjects add column label text not null default '';
+        alter table mapping_subjects_subjects add column inverse_label text;
+        alter table mapping_subjects_subjects add column note text not null default '';
+        alter table mapping_subjects_subjects add column valid_from text;
+        alter table mapping_subjects_subjects add column valid_to text;
+        drop index mapping_subjects_subjects_unique;
+        create unique index mapping_subjects_subjects_unique
+            on mapping_subjects_subjects(kfrom, kto, label); ",
+    )?;
+    Ok(())
+}
+
+/// Entities are soft deleted by setting `deleted_at`, see [`super::v1::Schema::trash`]
+fn v5_trash(conn: &sqlite::Connection) -> Result<()> {
+    for table in ["subjects", "places", "groups", "events", "tags"] {
+        conn.execute(format!(
+            "alter table {table} add column deleted_at integer;"
+        ))?;
+    }
+    Ok(())
+}
+
+/// History of entity descriptions, see [`super::v1::Revision`]
+fn v6_revisions(conn: &sqlite::Connection) -> Result<()> {
+    conn.execute(
+        "
+        create table revisions(
+            id integer primary key,
+            kind text not null,
+            entity_id integer not null,
+            description text not null,
+            session integer not null,
+            created_at integer not null,
+            updated_at integer not null
+        );
+        create index revisions_entity on revisions(kind, entity_id); ",
+    )?;
+    Ok(())
+}
+
+/// A full text index over names and descriptions, kept in sync by triggers. Trashed entities
+/// are removed from the index, and added again when they are restored.
+fn v7_full_text_search(conn: &sqlite::Connection) -> Result<()> {
+    conn.execute(
+        "
+        create virtual table search_index using fts5(
+            kind unindexed,
+            entity_id unindexed,
+            name,
+            description,
+            tokenize = 'unicode61 remove_diacritics 2'
+        ); ",
+    )?;
+    for (table, name) in [
+        ("subjects", "name"),
+        ("places", "name"),
+        ("groups", "name"),
+        ("events", "refered_date"),
+    ] {
+        conn.execute(format!(
+            "
+            insert into search_index (kind, entity_id, name, description)
+                select '{table}', id, {name}, description from {table}
+                where deleted_at is null;
+
+            create trigger {table}_search_insert after insert on {table}
+            when new.deleted_at is null begin
+                insert into search_index (kind, entity_id, name, description)
+                    values ('{table}', new.id, new.{name}, new.description);
+            end;
+
+            create trigger {table}_search_update after update on {table} begin
+                delete from search_index where kind = '{table}' and entity_id = old.id;
+                insert into search_index (kind, entity_id, name, description)
+                    select '{table}', new.id, new.{name}, new.description
+                    where new.deleted_at is null;
+            end;
+
+            create trigger {table}_search_delete after delete on {table} begin
+                delete from search_index where kind = '{table}' and entity_id = old.id;
+            end; "
+        ))?;
+    }
+    Ok(())
+}
+
+/// Adds a normalized copy of every name, that is used for case and diacritic insensitive
+/// name search, see [`v8_name_key`]
+fn v8_name_keys(conn: &sqlite::Connection) -> Result<()> {
+    for table in ["subjects", "places", "groups", "tags"] {
+        conn.execute(format!(
+            "alter table {table} add column name_key text;
+             create index {table}_name_key on {table}(name_key);"
+        ))?;
+        let names = conn
+            .prepare(format!("select id, name from {table};"))?
+            .into_iter()
+            .map(|r| {
+                let r = r?;
+                Ok((
+                    r.read::<i64, _>("id"),
+                    r.read::<Option<&str>, _>("name").unwrap_or("").to_string(),
+                ))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        let mut stmt = conn.prepare(format!("update {table} set name_key = ? where id == ?;"))?;
+        for (id, name) in names {
+            stmt.reset()?;
+            stmt.bind((1, v8_name_key(&name).as_str()))?;
+            stmt.bind((2, id))?;
+            assert!(stmt.next()? == sqlite::State::Done);
+        }
+    }
+    Ok(())
+}
+
+/// [`super::v1::search_key`] as of v8: lower case, without diacritics
+fn v8_name_key(s: &str) -> String {
+    s.nfd()
+        .filter(|c| !is_combining_mark(*c))
+        .flat_map(char::to_lowercase)
+        .collect()
+}
+
+/// Names are unique per entity kind, ignoring case and diacritics. Existing duplicates get a
+/// number appended, e.g. the second "Bob" becomes "Bob (2)". Trashed entities don't count.
+fn v9_unique_names(conn: &sqlite::Connection) -> Result<()> {
+    for table in ["subjects", "places", "groups", "tags"] {
+        let rows = conn
+            .prepare(format!(
+                "select id, name, name_key from {table} where deleted_at is null order by id;"
+            ))?
+            .into_iter()
+            .map(|r| {
+                let r = r?;
+                Ok((
+                    r.read::<i64, _>("id"),
+                    r.read::<Option<&str>, _>("name").unwrap_or("").to_string(),
+                    r.read::<Option<&str>, _>("name_key")
+                        .unwrap_or("")
+                        .to_string(),
+                ))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        let mut taken = rows
+            .iter()
+            .map(|(_, _, key)| key.clone())
+            .collect::<std::collections::HashSet<_>>();
+        let mut seen = std::collections::HashSet::new();
+        let mut stmt = conn.prepare(format!(
+            "update {table} set name = ?, name_key = ? where id == ?;"
+        ))?;
+        for (id, name, key) in rows {
+            if seen.insert(key) {
+                continue;
+            }
+            let (new_name, new_key) = (2..)
+                .map(|i| {
+                    let n = format!("{name} ({i})");
+                    let k = v8_name_key(&n);
+                    (n, k)
+                })
+                .find(|(_, k)| !taken.contains(k))
+                .unwrap();
+            taken.insert(new_key.clone());
+            stmt.reset()?;
+            stmt.bind((1, new_name.as_str()))?;
+            stmt.bind((2, new_key.as_str()))?;
+            stmt.bind((3, id))?;
+            assert!(stmt.next()? == sqlite::State::Done);
+        }
+        conn.execute(format!(
+            "drop index {table}_name_key;
+             create unique index {table}_name_key on {table}(name_key) where deleted_at is null;"
+        ))?;
+    }
+    Ok(())
+}
+
+/// Marks links that were created from `[[wiki links]]` in descriptions, so they can be updated
+/// when the description changes, without touching links created by hand
+fn v10_wiki_links(conn: &sqlite::Connection) -> Result<()> {
+    for table in [
+        "mapping_subjects_subjects",
+        "mapping_subjects_groups",
+        "mapping_subjects_places",
+        "mapping_events_subjects",
+        "mapping_events_groups",
+        "mapping_events_places",
+        "mapping_events_tags",
+        "mapping_places_groups",
+    ] {
+        conn.execute(format!(
+            "alter table {table} add column wiki integer not null default 0;"
+        ))?;
+    }
+    Ok(())
+}
+
+/// Subjects, places and groups can be tagged, not only events
+fn v11_entity_tags(conn: &sqlite::Connection) -> Result<()> {
+    for from in ["subjects", "places", "groups"] {
+        let table = format!("mapping_{from}_tags");
+        conn.execute(format!(
+            "
+            create table {table}(
+                kfrom integer,
+                kto integer,
+                wiki integer not null default 0,
+                foreign key(kfrom) references {from}(id),
+                foreign key(kto) references tags(id)
+            );
+            create unique index {table}_unique on {table}(kfrom, kto); "
+        ))?;
+    }
+    Ok(())
+}
+
+/// Typed custom fields on entities, see [`super::v1::Field`]. Like in `revisions`, `kind` is
+/// the table of the entity. References to other entities are stored in `ref_kind` and `ref_id`,
+/// all other values as text in `value`.
+fn v12_fields(conn: &sqlite::Connection) -> Result<()> {
+    conn.execute(
+        "
+        create table fields(
+            id integer primary key,
+            kind text not null,
+            entity_id integer not null,
+            name text not null,
+            name_key text not null,
+            field_type text not null,
+            value text,
+            ref_kind text,
+            ref_id integer,
+            position integer not null default 0
+        );
+        create unique index fields_unique on fields(kind, entity_id, name_key);
+        create index fields_name_key on fields(name_key); ",
+    )?;
+    Ok(())
+}
+
+/// Templates for new entities, see [`super::v1::Template`], together with a few built-in ones.
+/// `kind` is the table of the entities the template is for, `tags` has one tag per line.
+fn v13_templates(conn: &sqlite::Connection) -> Result<()> {
+    conn.execute(
+        "
+        create table templates(
+            id integer primary key,
+            kind text not null,
+            name text not null,
+            name_key text not null,
+            body text not null default '',
+            tags text not null default ''
+        );
+        create unique index templates_name_key on templates(kind, name_key);
+        create table template_fields(
+            id integer primary key,
+            template_id integer not null,
+            position integer not null,
+            name text not null,
+            field_type text not null,
+            value text not null default '',
+            foreign key(template_id) references templates(id)
+        );
+        create index template_fields_template on template_fields(template_id); ",
+    )?;
+
+    let mut template = conn.prepare(
+        "insert into templates (kind, name, name_key, body, tags) values (?, ?, ?, ?, ?);",
+    )?;
+    let mut field = conn.prepare(
+        "insert into template_fields (template_id, position, name, field_type, value)
+         values (?, ?, ?, ?, ?);",
+    )?;
+    for (kind, name, body, tags, fields) in BUILT_IN_TEMPLATES {
+        template.reset()?;
+        template.bind((1, kind))?;
+        template.bind((2, name))?;
+        template.bind((3, v8_name_key(name).as_str()))?;
+        template.bind((4, body))?;
+        template.bind((5, tags))?;
+        assert!(template.next()? == sqlite::State::Done);
+        let id = query_i64(conn, "select last_insert_rowid();")?;
+        for (i, (name, field_type, value)) in fields.iter().enumerate() {
+            field.reset()?;
+            field.bind((1, id))?;
+            field.bind((2, i as i64))?;
+            field.bind((3, *name))?;
+            field.bind((4, *field_type))?;
+            field.bind((5, *value))?;
+            assert!(field.next()? == sqlite::State::Done);
+        }
+    }
+    Ok(())
+}
+
+type BuiltInTemplate = (
+    &'static str,
+    &'static str,
+    &'static str,
+    &'static str,
+    &'static [(&'static str, &'static str, &'static str)],
+);
+
+/// kind, name, body, tags and fields (name, type, value) of the templates every campaign
+/// starts with
+const BUILT_IN_TEMPLATES: [BuiltInTemplate; 4] = [
+    (
+        "subjects",
+        "NPC",
+        "## Appearance\n\n## Personality\n\n## Motivation\n\n## Secrets\n\n## Notes\n",
+        "npc",
+        &[
+            ("Race", "text", ""),
+            ("Occupation", "text", ""),
+            ("Age", "number", ""),
+            ("Alignment", "text", ""),
+            ("Alive", "boolean", "yes"),
+        ],
+    ),
+    (
+        "places",
+        "Shop",
+        "## Wares\n\n| Item | Price |\n|------|-------|\n|      |       |\n\n\
+         ## Staff\n\n## Notes\n",
+        "shop",
+        &[
+            ("Owner", "reference", ""),
+            ("Prices", "text", "average"),
+            ("Opening hours", "text", ""),
+        ],
+    ),
+    (
+        "places",
+        "Tavern",
+        "## Atmosphere\n\n## Menu\n\n| Food or drink | Price |\n|---------------|-------|\n\
+         |               |       |\n\n## Regulars\n\n## Rumors\n",
+        "tavern",
+        &[
+            ("Owner", "reference", ""),
+            ("Rooms", "number", ""),
+            ("Price per night", "text", ""),
+        ],
+    ),
+    (
+        "places",
+        "Dungeon room",
+        "## Read aloud\n\n> \n\n## Features\n\n## Creatures\n\n## Treasure\n\n## Traps\n\n\
+         ## Exits\n",
+        "dungeon",
+        &[("Light", "text", ""), ("Cleared", "boolean", "no")],
+    ),
+];
+
+/// Images attached to entities, see [`super::v1::Attachment`]. The image data is kept in the
+/// database so a campaign stays a single file. Images are stored once per content hash and
+/// mapped to entities in `entity_attachments`, where `kind` is the table of the entity.
+fn v14_attachments(conn: &sqlite::Connection) -> Result<()> {
+    conn.execute(
+        "
+        create table attachments(
+            id integer primary key,
+            hash text not null unique,
+            mime text not null,
+            data blob not null,
+            thumbnail blob not null,
+            width integer not null,
+            height integer not null,
+            file_name text not null default '',
+            created_at integer not null
+        );
+        create table entity_attachments(
+            id integer primary key,
+            kind text not null,
+            entity_id integer not null,
+            attachment_id integer not null,
+            portrait integer not null default 0,
+            caption text not null default '',
+            position integer not null default 0,
+            foreign key(attachment_id) references attachments(id)
+        );
+        create unique index entity_attachments_unique
+            on entity_attachments(kind, entity_id, attachment_id); ",
+    )?;
+    Ok(())
+}
+
+/// Map images of places and the pins on them, see [`super::v1::MapPin`]. The map is a stored
+/// image from `attachments`. Pin positions are relative to the image size, so `x` and `y` are
+/// between 0 and 1.
+fn v15_place_maps(conn: &sqlite::Connection) -> Result<()> {
+    conn.execute(
+        "
+        create table place_maps(
+            place_id integer primary key,
+            attachment_id integer not null,
+            foreign key(place_id) references places(id),
+            foreign key(attachment_id) references attachments(id)
+        );
+        create table map_pins(
+            id integer primary key,
+            place_id integer not null,
+            target_kind text not null,
+            target_id integer not null,
+            x real not null,
+            y real not null,
+            foreign key(place_id) references places(id)
+        );
+        create index map_pins_place on map_pins(place_id);
+        create index map_pins_target on map_pins(target_kind, target_id); ",
+    )?;
+    Ok(())
+}
+
+/// The in-world calendar of the campaign, see [`crate::calendar::Calendar`], and structured
+/// in-world dates of events. Months are ordered by `position`, weekdays are stored one per
+/// line. Existing campaigns get the Gregorian calendar, and their event dates are parsed with
+/// it, see [`v16_parse_date`].
+fn v16_calendar(conn: &sqlite::Connection) -> Result<()> {
+    conn.execute(
+        "
+        create table calendar(
+            id integer primary key check (id == 1),
+            name text not null,
+            weekdays text not null default '',
+            before_eras text not null default '',
+            leap_every integer not null default 0,
+            leap_except integer not null default 0,
+            leap_unless integer not null default 0,
+            first_weekday integer not null default 0
+        );
+        create table calendar_months(
+            position integer primary key,
+            name text not null,
+            days integer not null,
+            leap_days integer not null default 0
+        );
+        create table calendar_eras(
+            start_year integer primary key,
+            name text not null
+        );
+        alter table events add column date_year integer;
+        alter table events add column date_month integer;
+        alter table events add column date_day integer;
+        create index events_date on events(date_year, date_month, date_day);
+        insert into calendar_eras (start_year, name) values (1, 'AD'); ",
+    )?;
+    let mut stmt = conn.prepare(
+        "insert into calendar (id, name, weekdays, before_eras, leap_every, leap_except,
+             leap_unless, first_weekday)
+         values (1, 'Gregorian', ?, 'BC', 4, 100, 400, 0);",
+    )?;
+    stmt.bind((1, V16_WEEKDAYS.join("\n").as_str()))?;
+    assert!(stmt.next()? == sqlite::State::Done);
+    let mut stmt = conn.prepare(
+        "insert into calendar_months (position, name, days, leap_days) values (?, ?, ?, ?);",
+    )?;
+    for (i, (name, days, leap_days)) in V16_MONTHS.iter().enumerate() {
+        stmt.reset()?;
+        stmt.bind((1, i as i64))?;
+        stmt.bind((2, *name))?;
+        stmt.bind((3, *days))?;
+        stmt.bind((4, *leap_days))?;
+        assert!(stmt.next()? == sqlite::State::Done);
+    }
+
+    let events = conn
+        .prepare("select id, refered_date from events;")?
+        .into_iter()
+        .map(|r| {
+            let r = r?;
+            Ok((
+                r.read::<i64, _>("id"),
+                r.read::<Option<&str>, _>("refered_date")
+                    .unwrap_or("")
+                    .to_string(),
+            ))
+        })
+        .collect::<Result<Vec<_>>>()?;
+    let mut stmt = conn
+        .prepare("update events set date_year = ?, date_month = ?, date_day = ? where id == ?;")?;
+    for (id, text) in events {
+        let date = v16_parse_date(&text);
+        stmt.reset()?;
+        stmt.bind((1, date.map(|d| d.0)))?;
+        stmt.bind((2, date.map(|d| d.1)))?;
+        stmt.bind((3, date.map(|d| d.2)))?;
+        stmt.bind((4, id))?;
+        assert!(stmt.next()? == sqlite::State::Done);
+    }
+    Ok(())
+}
+
+/// Name, days and leap days of the months of the Gregorian calendar
+const V16_MONTHS: [(&str, i64, i64); 12] = [
+    ("January", 31, 0),
+    ("February", 28, 1),
+    ("March", 31, 0),
+    ("April", 30, 0),
+    ("May", 31, 0),
+    ("June", 30, 0),
+    ("July", 31, 0),
+    ("August", 31, 0),
+    ("September", 30, 0),
+    ("October", 31, 0),
+    ("November", 30, 0),
+    ("December", 31, 0),
+];
+
+const V16_WEEKDAYS: [&str; 7] = [
+    "Monday",
+    "Tuesday",
+    "Wednesday",
+    "Thursday",
+    "Friday",
+    "Saturday",
+    "Sunday",
+];
+
+/// A Gregorian date read like [`crate::calendar::Calendar::parse`] did in v16, as year,
+/// month starting at 0, and day. Either `1492-3-14`, or words like `Tuesday, 14th March 1492`
+/// with an optional `AD` or `BC`.
+fn v16_parse_date(text: &str) -> Option<(i64, i64, i64)> {
+    let text = text.trim();
+    let numeric = || {
+        let (sign, rest) = match text.strip_prefix('-') {
+            Some(rest) => (-1, rest),
+            None => (1, text),
+        };
+        let mut parts = rest.split('-');
+        let year = parts.next()?.parse::<i64>().ok()?;
+        let month = parts.next()?.parse::<u32>().ok()?;
+        let day = parts.next()?.parse::<u32>().ok()?;
+        if parts.next().is_some() || month == 0 {
+            return None;
+        }
+        Some((sign * year, month as i64 - 1, day as i64))
+    };
+    let (year, month, day) = match numeric() {
+        Some(date) => date,
+        None => {
+            let mut numbers = vec![];
+            let mut month = None;
+            let mut before_christ = false;
+            for word in text
+                .split(|c: char| c.is_whitespace() || c == ',')
+                .filter(|w| !w.is_empty())
+            {
+                let digits = ["st", "nd", "rd", "th"]
+                    .iter()
+                    .find_map(|s| word.strip_suffix(s))
+                    .unwrap_or(word);
+                let lower = word.to_lowercase();
+                if let Ok(n) = digits.parse::<i64>() {
+                    numbers.push(n);
+                } else if let Some(m) = V16_MONTHS
+                    .iter()
+                    .position(|(name, ..)| name.to_lowercase() == lower)
+                {
+                    month = Some(m as i64);
+                } else if lower == "bc" {
+                    before_christ = true;
+                } else if lower != "ad" && !V16_WEEKDAYS.iter().any(|d| d.to_lowercase() == lower) {
+                    return None;
+                }
+            }
+            let [day, year] = numbers[..] else {
+                return None;
+            };
+            let year = if before_christ { 1 - year } else { year };
+            (year, month?, day)
+        }
+    };
+    let (_, days, leap_days) = *V16_MONTHS.get(usize::try_from(month).ok()?)?;
+    let leap = year.rem_euclid(4) == 0 && (year.rem_euclid(100) != 0 || year.rem_euclid(400) == 0);
+    let length = if leap { days + leap_days } else { days };
+    (1..=length).contains(&day).then_some((year, month, day))
+}
+
+/// Real-world play sessions, see [`super::v1::Session`], and the session each event happened
+/// in. `played_on` is a date like `2023-07-14`, `players` has one player per line.
+fn v17_sessions(conn: &sqlite::Connection) -> Result<()> {
+    conn.execute(
+        "
+        create table sessions(
+            id integer primary key,
+            number integer not null unique,
+            played_on text not null default '',
+            title text not null default '',
+            recap text not null default '',
+            players text not null default ''
+        );
+        alter table events add column session_id integer references sessions(id);
+        create index events_session on events(session_id); ",
+    )?;
+    Ok(())
+}
+
+/// Entities and links can be hidden from the players. Links to tags get the flag as well, so
+/// all mapping tables have the same columns.
+fn v18_secrets(conn: &sqlite::Connection) -> Result<()> {
+    for table in [
+        "subjects",
+        "places",
+        "groups",
+        "events",
+        "mapping_subjects_subjects",
+        "mapping_subjects_groups",
+        "mapping_subjects_places",
+        "mapping_subjects_tags",
+        "mapping_events_subjects",
+        "mapping_events_groups",
+        "mapping_events_places",
+        "mapping_events_tags",
+        "mapping_places_groups",
+        "mapping_places_tags",
+        "mapping_groups_tags",
+    ] {
+        conn.execute(format!(
+            "alter table {table} add column secret integer not null default 0;"
+        ))?;
+    }
+    Ok(())
+}
+
+/// `campaign.db` at version 1 is backed up as `campaign.db.v1.bak`
+pub fn backup_path(path: &Path, version: i64) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(format!(".v{version}.bak"));
+    path.with_file_name(name)
+}
+
+fn has_table(conn: &sqlite::Connection, name: &str) -> Result<bool> {
+    let mut stmt =
+        conn.prepare("select count(*) from sqlite_master where type = 'table' and name = ?;")?;
+    stmt.bind((1, name))?;
+    assert!(stmt.next()? == sqlite::State::Row);
+    Ok(stmt.read::<i64, _>(0)? > 0)
+}
+
+fn query_i64(conn: &sqlite::Connection, query: &str) -> Result<i64> {
+    let mut stmt = conn.prepare(query)?;
+    assert!(stmt.next()? == sqlite::State::Row);
+    Ok(stmt.read::<i64, _>(0)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::calendar::CalendarDate;
+    use crate::schema::v1::EntityKind;
+    use crate::Schema;
+
+    /// The tables of a file written before migrations existed, without a version stamp
+    const V1_LAYOUT: &str = "
+        create table subjects(id integer primary key, name text, description text);
+        create table events(id integer primary key, description text, happened_at text,
+            edited_at integer);
+        create table places(id integer primary key, name text, description text,
+            parent_place integer);
+        create table groups(id integer primary key, name text, description text,
+            parent_group integer);
+        create table tags(id integer primary key, name text);
+        create table mapping_subjects_subjects(kfrom integer, kto integer);
+        create table mapping_subjects_groups(kfrom integer, kto integer);
+        create table mapping_subjects_places(kfrom integer, kto integer);
+        create table mapping_events_subjects(kfrom integer, kto integer);
+        create table mapping_events_groups(kfrom integer, kto integer);
+        create table mapping_events_places(kfrom integer, kto integer);
+        create table mapping_events_tags(kfrom integer, kto integer);
+        create table mapping_places_groups(kfrom integer, kto integer); ";
+
+    #[test]
+    fn migrates_a_v1_file_and_keeps_a_backup() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("campaign.db");
+        {
+            let conn = sqlite::open(&path).unwrap();
+            conn.execute(V1_LAYOUT).unwrap();
+            conn.execute(
+                "insert into subjects (name, description) values ('Éowyn', 'A shieldmaiden');
+                 insert into subjects (name, description) values ('eowyn', 'Another one');
+                 insert into events (description, happened_at, edited_at)
+                     values ('The feast', 'Tuesday, 14th March 1492', 0);
+                 insert into mapping_subjects_subjects values (1, 2), (1, 2);",
+            )
+            .unwrap();
+            assert_eq!(version(&conn).unwrap(), 1);
+        }
+
+        let mut db = Schema::open(&path).unwrap();
+        assert!(backup_path(&path, 1).exists());
+        let conn = sqlite::open(&path).unwrap();
+        assert_eq!(version(&conn).unwrap(), CURRENT_VERSION);
+        assert_eq!(
+            query_i64(&conn, "select count(*) from mapping_subjects_subjects;").unwrap(),
+            1
+        );
+
+        assert_eq!(
+            db.get_sub_by_name("EOWYN").unwrap().unwrap().t.description,
+            "A shieldmaiden"
+        );
+        assert!(db.get_sub_by_name("eowyn (2)").unwrap().is_some());
+        let events = db.events_in_world_order().unwrap();
+        assert_eq!(
+            events[0].t.date,
+            Some(CalendarDate {
+                year: 1492,
+                month: 2,
+                day: 14
+            })
+        );
+        assert!(!db.list_templates(EntityKind::Subject).unwrap().is_empty());
+    }
+
+    #[test]
+    fn refuses_files_of_newer_versions() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("campaign.db");
+        drop(Schema::create_new(&path).unwrap());
+        set_version(&sqlite::open(&path).unwrap(), CURRENT_VERSION + 1).unwrap();
+        assert!(matches!(
+            Schema::open(&path),
+            Err(Error::DbTooNew { found, supported })
+                if found == CURRENT_VERSION + 1 && supported == CURRENT_VERSION
+        ));
+        assert!(!backup_path(&path, CURRENT_VERSION + 1).exists());
+    }
+
+    #[test]
+    fn a_failing_migration_is_rolled_back() {
+        fn create_a(conn: &sqlite::Connection) -> Result<()> {
+            conn.execute("create table a(x integer);")?;
+            Ok(())
+        }
+        fn create_b_then_fail(conn: &sqlite::Connection) -> Result<()> {
+            conn.execute("create table b(x integer);")?;
+            conn.execute("insert into no_such_table values (1);")?;
+            Ok(())
+        }
+
+        let conn = sqlite::open(":memory:").unwrap();
+        conn.execute("create table subjects(id integer primary key);")
+            .unwrap();
+        let res = apply(&conn, 1, &[create_a, create_b_then_fail]);
+        assert!(matches!(res, Err(Error::Migration { target: 3, .. })));
+        assert_eq!(version(&conn).unwrap(), 2);
+        assert!(has_table(&conn, "a").unwrap());
+        assert!(!has_table(&conn, "b").unwrap());
+    }
+
+    #[test]
+    fn parses_dates_like_v16() {
+        assert_eq!(v16_parse_date("1492-3-14"), Some((1492, 2, 14)));
+        assert_eq!(v16_parse_date("-44-3-15"), Some((-44, 2, 15)));
+        assert_eq!(v16_parse_date("March 15, 44 BC"), Some((-43, 2, 15)));
+        assert_eq!(v16_parse_date("29th February 2000 AD"), Some((2000, 1, 29)));
+        assert_eq!(v16_parse_date("29 February 1900"), None);
+        assert_eq!(v16_parse_date("the day the dragon came"), None);
+        assert_eq!(v16_parse_date("12 March"), None);
+    }
+}
@@ -8,11 +8,57 @@ pub enum Error {
 
     #[error("The file can't be created, it already exists: {0}")]
     FileExists(String),
+
+    #[error("{0} can't be moved below itself or one of its descendants")]
+    ParentCycle(String),
+
+    #[error("There is no way to link a {0} to a {1}")]
+    InvalidLink(String, String),
+
+    #[error("A {0} named \"{1}\" already exists")]
+    NameTaken(String, String),
+
+    #[error("The name of a {0} can't be empty")]
+    EmptyName(String),
+
+    #[error("\"{value}\" is not a valid value for {field}, expected {expected}")]
+    InvalidFieldValue {
+        field: String,
+        value: String,
+        expected: String,
+    },
+
+    #[error("Unknown field type \"{0}\", use text, number, boolean, date or reference")]
+    UnknownFieldType(String),
+
+    #[error("The image can't be imported: {0}")]
+    UnsupportedImage(String),
+
+    #[error("Only subjects and places inside {0} can be pinned on its map")]
+    InvalidPin(String),
+
+    #[error("\"{0}\" is not a valid date: {1}")]
+    InvalidDate(String, String),
+
+    #[error("The calendar is invalid: {0}")]
+    InvalidCalendar(String),
+
+    #[error("There already is a session number {0}")]
+    SessionNumberTaken(i64),
+
+    #[error("IO error: {0}")]
+    Io(#[from] std::io::Error),
+
+    #[error("The campaign file has schema version {found}, but this version of campman only supports up to {supported}. Please update campman.")]
+    DbTooNew { found: i64, supported: i64 },
+
+    #[error("Migrating the campaign file to schema version {target} failed: {source}")]
+    Migration { target: i64, source: Box<Error> },
 }
 
 pub type Result<T> = StdResult<T, Error>;
 
-#[derive(Clone)]
+#[derive(Clone, Debug, PartialEq)]
 pub struct WithId<T> {
     pub t: T,
     pub id: i64,
@@ -31,4 +77,5 @@ impl<T> DerefMut for WithId<T> {
     }
 }
 
+pub mod migrations;
 pub mod v1;
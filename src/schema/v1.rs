@@ -1,7 +1,46 @@
-use super::{Error, Result, WithId};
+use super::{migrations, Error, Result, WithId};
+use crate::calendar::CalendarDate;
+use crate::wiki;
 
-use std::fmt::Display;
 use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+mod attachments;
+mod backlinks;
+mod calendars;
+mod events;
+mod fields;
+mod groups;
+mod links;
+mod maps;
+mod names;
+mod places;
+mod relationships;
+mod revisions;
+mod search;
+mod secrets;
+mod sessions;
+mod tags;
+mod templates;
+mod trash;
+
+pub use attachments::Attachment;
+pub use backlinks::Backlink;
+pub use fields::{
+    parse_bool, split_field_filters, Field, FieldFilter, FieldType, FieldValue, FilterOp, SortKey,
+};
+pub use links::{mapping_table, EntityKind, EntityRef, Linked, MAPPINGS};
+pub use maps::MapPin;
+pub use names::{replace_mentions, rewrite_mentions};
+pub use relationships::{RelationView, Relationship};
+pub use revisions::Revision;
+pub(crate) use search::escape_html;
+pub use search::{search_key, split_tag_filters, SearchHit};
+pub use secrets::Audience;
+pub use sessions::Session;
+pub use tags::TagCount;
+pub use templates::{format_template_fields, parse_template_fields, Template, TemplateField};
+pub use trash::TrashEntry;
 
 pub struct Subject {
     pub name: String,
@@ -18,6 +57,10 @@ pub struct Event {
     pub record_date: u64,
     pub refered_date: String,
     pub description: String,
+    /// `refered_date` read with the campaign's calendar, if it is a valid date
+    pub date: Option<CalendarDate>,
+    /// The play session the event happened in
+    pub session: Option<i64>,
 }
 
 pub struct Group {
@@ -35,10 +78,17 @@ pub struct Schema {
 }
 
 impl Schema {
+    /// Opens an existing campaign file, and migrates it to the current schema version if it
+    /// was written by an older version of campman
     pub fn open<T: AsRef<Path>>(path: T) -> Result<Self> {
-        Ok(Self {
-            conn: sqlite::open(path)?,
-        })
+        let path: &Path = path.as_ref();
+        let mut me = Self::connect(path)?;
+        if migrations::version(&me.conn)? == 0 {
+            me.create()?;
+        } else {
+            migrations::migrate(&me.conn, path)?;
+        }
+        Ok(me)
     }
 
     pub fn create_new<T: AsRef<Path>>(path: T) -> Result<Self> {
@@ -46,12 +96,18 @@ impl Schema {
         if path.exists() {
             Err(Error::FileExists(path.display().to_string()))
         } else {
-            let mut me = Self::open(path)?;
+            let mut me = Self::connect(path)?;
             me.create()?;
             Ok(me)
         }
     }
 
+    fn connect(path: &Path) -> Result<Self> {
+        Ok(Self {
+            conn: sqlite::open(path)?,
+        })
+    }
+
     fn create_subjects_table(&mut self) -> Result<()> {
         let query = "
         create table subjects(
@@ -125,7 +181,19 @@ impl Schema {
         Ok(())
     }
 
+    /// Creates the v1 tables, and then applies all migrations on top, so a new file always has
+    /// the same layout as a migrated one
     fn create(&mut self) -> Result<()> {
+        self.conn.execute("begin transaction;")?;
+        if let Err(e) = self.create_v1() {
+            self.conn.execute("rollback;")?;
+            return Err(e);
+        }
+        self.conn.execute("commit;")?;
+        migrations::apply_from(&self.conn, 1)
+    }
+
+    fn create_v1(&mut self) -> Result<()> {
         self.create_subjects_table()?;
         self.create_events_table()?;
         self.create_places_table()?;
@@ -138,160 +206,242 @@ impl Schema {
             self.create_mapping("events", to)?;
         }
         self.create_mapping("places", "groups")?;
-        Ok(())
+        migrations::set_version(&self.conn, 1)
     }
 
-    pub fn insert_subject(&mut self, x: &Subject) -> Result<()> {
+    /// Inserts the subject and returns its id
+    pub fn insert_subject(&mut self, x: &Subject) -> Result<i64> {
+        self.check_name_free(EntityKind::Subject, &x.name, None)?;
         let mut stmt = self
             .conn
-            .prepare("insert into subjects (name, description) values (?, ?)")?;
-        stmt.bind(&[(1, x.name.as_str()), (2, x.description.as_str())][..])?;
+            .prepare("insert into subjects (name, name_key, description) values (?, ?, ?)")?;
+        stmt.bind(
+            &[
+                (1, x.name.as_str()),
+                (2, search_key(&x.name).as_str()),
+                (3, x.description.as_str()),
+            ][..],
+        )?;
         assert!(stmt.next()? == sqlite::State::Done);
-        Ok(())
+        self.last_insert_id()
     }
 
-    pub fn insert_place(&mut self, x: &Place) -> Result<()> {
-        self.conn
-            .prepare("insert into places (name, description, parent_place) values (?, ?, ?)")?
-            .bind(
-                &[
-                    (1, x.name.as_str()),
-                    (2, x.description.as_str()),
-                    (3, &opt_to_str(&x.parent_place)),
-                ][..],
-            )?;
-        Ok(())
+    /// Inserts the tag and returns its id
+    pub fn insert_tag(&mut self, x: &Tag) -> Result<i64> {
+        self.check_name_free(EntityKind::Tag, &x.name, None)?;
+        let mut stmt = self
+            .conn
+            .prepare("insert into tags (name, name_key) values (?, ?)")?;
+        stmt.bind(&[(1, x.name.as_str()), (2, search_key(&x.name).as_str())][..])?;
+        assert!(stmt.next()? == sqlite::State::Done);
+        self.last_insert_id()
     }
 
-    pub fn insert_event(&mut self, x: &Event) -> Result<()> {
-        self.conn
-            .prepare(
-                "insert into events (record_date, refered_date, description) values (?, ?, ?)",
-            )?
-            .bind(
-                &[
-                    (1, x.record_date.to_string().as_str()),
-                    (2, x.refered_date.as_str()),
-                    (3, x.description.as_str()),
-                ][..],
-            )?;
-        Ok(())
+    /// Deletes the subject together with all its links to other entities
+    pub fn delete_subject(&mut self, id: i64) -> Result<()> {
+        self.delete_entity_row(EntityRef::new(EntityKind::Subject, id))
     }
 
-    pub fn insert_group(&mut self, x: &Group) -> Result<()> {
-        self.conn
-            .prepare("insert into groups (name, description, parent_group) values (?, ?, ?)")?
-            .bind(
-                &[
-                    (1, x.name.as_str()),
-                    (2, x.description.as_str()),
-                    (3, &opt_to_str(&x.parent_group)),
-                ][..],
-            )?;
-        Ok(())
-    }
-
-    pub fn insert_tag(&mut self, x: &Tag) -> Result<()> {
-        self.conn
-            .prepare("insert into tags (name) values (?)")?
-            .bind(&[(1, x.name.as_str())][..])?;
-        Ok(())
+    /// Deletes the tag, and removes it from everything it was attached to
+    pub fn delete_tag(&mut self, id: i64) -> Result<()> {
+        self.delete_entity_row(EntityRef::new(EntityKind::Tag, id))
     }
 
     pub fn update_subject(&mut self, x: &WithId<Subject>) -> Result<()> {
-        let mut stmt = self
-            .conn
-            .prepare("update subjects set name = ?, description = ? where id == ?;")?;
+        self.check_name_free(EntityKind::Subject, &x.name, Some(x.id))?;
+        let mut stmt = self.conn.prepare(
+            "update subjects set name = ?, name_key = ?, description = ? where id == ?;",
+        )?;
         stmt.bind(
             &[
                 (1, x.name.as_str()),
-                (2, x.description.as_str()),
-                (3, x.id.to_string().as_str()),
-            ][..],
-        )?;
-        assert!(stmt.next()? == sqlite::State::Done);
-        Ok(())
-    }
-
-    pub fn update_place(&mut self, x: &WithId<Place>) -> Result<()> {
-        self.conn
-            .prepare("update places set name = ?, description =? , parent_place = ? where id == ?")?
-            .bind(
-                &[
-                    (1, x.name.as_str()),
-                    (2, x.description.as_str()),
-                    (3, &opt_to_str(&x.parent_place)),
-                    (4, x.id.to_string().as_str()),
-                ][..],
-            )?;
-        Ok(())
-    }
-
-    pub fn update_event(&mut self, x: &WithId<Event>) -> Result<()> {
-        self.conn.prepare(
-            "update events set record_date = ?, refered_date = ?, description = ? where id == ?",
-        )?
-        .bind(
-            &[
-                (1, x.record_date.to_string().as_str()),
-                (2, x.refered_date.as_str()),
+                (2, search_key(&x.name).as_str()),
                 (3, x.description.as_str()),
                 (4, x.id.to_string().as_str()),
             ][..],
         )?;
+        assert!(stmt.next()? == sqlite::State::Done);
         Ok(())
     }
 
-    pub fn update_group(&mut self, x: &WithId<Group>) -> Result<()> {
-        self.conn
-            .prepare("update groups set name = ?, description = ?, parent_group = ? where id == ?")?
-            .bind(
-                &[
-                    (1, x.name.as_str()),
-                    (2, x.description.as_str()),
-                    (3, &opt_to_str(&x.parent_group)),
-                    (4, x.id.to_string().as_str()),
-                ][..],
-            )?;
-        Ok(())
+    /// Names of all subjects that contain `query`, see [`Schema::query_names`]
+    pub fn query_subj_names(&mut self, query: &str) -> Result<Vec<String>> {
+        Ok(self
+            .query_names(EntityKind::Subject, query)?
+            .into_iter()
+            .map(|x| x.t)
+            .collect())
     }
 
-    pub fn query_subj_names(&mut self, query: &str) -> Result<Vec<String>> {
-        let res = self
-            .conn
-            .prepare(&format!(
-                "select name from subjects where name like '%{query}%' --case-insensitive"
-            ))?
+    pub fn get_subject(&mut self, id: i64) -> Result<Option<WithId<Subject>>> {
+        self.conn
+            .prepare("select * from subjects where id == ?;")?
             .into_iter()
-            .map(|r| Ok(r?.read::<&str, _>("name").to_string()))
-            .collect::<Result<Vec<_>>>()?;
-        Ok(res)
+            .bind((1, id))?
+            .map(|r| Ok(subject_from_row(&r?)))
+            .next()
+            .transpose()
     }
 
+    /// Looks up a subject by name, ignoring case and diacritics like [`Schema::check_name_free`]
     pub fn get_sub_by_name(&mut self, name: &str) -> Result<Option<WithId<Subject>>> {
-        let query = "select * from subjects where name == ?;";
+        let query = "select * from subjects where name_key == ? and deleted_at is null;";
         let sub = self
             .conn
             .prepare(query)?
             .into_iter()
-            .bind(&[(1, name)][..])?
-            .map(|r| {
-                let r = r?;
-                Ok(WithId {
-                    t: Subject {
-                        name: r.read::<&str, _>("name").to_string(),
-                        description: r.read::<&str, _>("description").to_string(),
-                    },
-                    id: r.read::<i64, _>("id"),
-                })
-            })
+            .bind(&[(1, search_key(name).as_str())][..])?
+            .map(|r| Ok(subject_from_row(&r?)))
             .collect::<Result<Vec<WithId<Subject>>>>()?
             .into_iter()
             .next();
         Ok(sub)
     }
+
+    /// Id of the row inserted last through this connection
+    fn last_insert_id(&self) -> Result<i64> {
+        let mut stmt = self.conn.prepare("select last_insert_rowid();")?;
+        assert!(stmt.next()? == sqlite::State::Row);
+        Ok(stmt.read::<i64, _>(0)?)
+    }
+
+    /// Runs `f` in a transaction that is rolled back if it fails. Transactions nest: inside
+    /// another one, only the changes of `f` are rolled back, and nothing is committed before
+    /// the outermost one is done.
+    pub fn in_transaction<T>(&mut self, f: impl FnOnce(&mut Self) -> Result<T>) -> Result<T> {
+        self.conn.execute("savepoint nested;")?;
+        let res = f(self);
+        match res {
+            Ok(_) => self.conn.execute("release nested;")?,
+            Err(_) => self.conn.execute("rollback to nested; release nested;")?,
+        }
+        res
+    }
+
+    /// Stores a description written in the editor together with the links made from its wiki
+    /// links and a revision, see [`Schema::save_revision`], all or nothing
+    pub fn save_description(
+        &mut self,
+        x: EntityRef,
+        description: &str,
+        session: Option<i64>,
+    ) -> Result<()> {
+        self.in_transaction(|db| {
+            db.set_description(x, description)?;
+            db.save_revision(x, description, session)
+        })
+    }
+
+    /// Replaces the description of `x`, and the links made from the wiki links in it, see
+    /// [`wiki::sync_links`]
+    fn set_description(&mut self, x: EntityRef, description: &str) -> Result<()> {
+        assert!(x.kind != EntityKind::Tag, "Tags have no description");
+        self.in_transaction(|db| {
+            let mut stmt = db.conn.prepare(format!(
+                "update {} set description = ? where id == ?;",
+                x.kind.table()
+            ))?;
+            stmt.bind((1, description))?;
+            stmt.bind((2, x.id))?;
+            assert!(stmt.next()? == sqlite::State::Done);
+            drop(stmt);
+            wiki::sync_links(db, x, description)
+        })
+    }
+
+    /// Removes all links, fields and the history of an entity, and then its row, all or nothing
+    fn delete_entity_row(&mut self, x: EntityRef) -> Result<()> {
+        self.in_transaction(|db| {
+            db.unlink_all(x)?;
+            db.delete_revisions(x)?;
+            db.delete_fields(x)?;
+            db.delete_attachments(x)?;
+            db.delete_map_pins(x)?;
+            let mut stmt = db
+                .conn
+                .prepare(format!("delete from {} where id == ?;", x.kind.table()))?;
+            stmt.bind((1, x.id))?;
+            assert!(stmt.next()? == sqlite::State::Done);
+            Ok(())
+        })
+    }
+
+    /// Adds a row to a mapping table. If the row already exists because of a wiki link, it
+    /// becomes a link made by hand, which stays when the wiki link is removed.
+    fn insert_mapping(&mut self, table: &str, from: i64, to: i64) -> Result<()> {
+        let mut stmt = self.conn.prepare(format!(
+            "insert into {table} (kfrom, kto) values (?, ?)
+             on conflict do update set wiki = 0, secret = 0;"
+        ))?;
+        stmt.bind((1, from))?;
+        stmt.bind((2, to))?;
+        assert!(stmt.next()? == sqlite::State::Done);
+        Ok(())
+    }
+
+    fn delete_mapping(&mut self, table: &str, from: i64, to: i64) -> Result<()> {
+        let mut stmt = self.conn.prepare(format!(
+            "delete from {table} where kfrom == ? and kto == ?;"
+        ))?;
+        stmt.bind((1, from))?;
+        stmt.bind((2, to))?;
+        assert!(stmt.next()? == sqlite::State::Done);
+        Ok(())
+    }
 }
 
-fn opt_to_str<T: Display>(x: &Option<T>) -> String {
-    x.as_ref().map(|x| x.to_string()).unwrap_or("NULL".into())
+fn unix_now() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs() as i64)
+        .unwrap_or(0)
+}
+
+fn subject_from_row(r: &sqlite::Row) -> WithId<Subject> {
+    WithId {
+        t: Subject {
+            name: r.read::<&str, _>("name").to_string(),
+            description: r.read::<&str, _>("description").to_string(),
+        },
+        id: r.read::<i64, _>("id"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn subject(name: &str) -> Subject {
+        Subject {
+            name: name.into(),
+            description: "".into(),
+        }
+    }
+
+    #[test]
+    fn nested_transactions_roll_back_together() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut db = Schema::create_new(dir.path().join("test.db")).unwrap();
+
+        let res: Result<()> = db.in_transaction(|db| {
+            db.insert_subject(&subject("Mira"))?;
+            // the inner failure only undoes the inner changes
+            let inner: Result<()> = db.in_transaction(|db| {
+                db.insert_subject(&subject("Vex"))?;
+                db.insert_subject(&subject("Mira"))?;
+                Ok(())
+            });
+            assert!(inner.is_err());
+            assert!(db.get_sub_by_name("Vex")?.is_none());
+            assert!(db.get_sub_by_name("Mira")?.is_some());
+            db.insert_subject(&subject("Mira"))?;
+            Ok(())
+        });
+        assert!(res.is_err());
+        assert!(db.get_sub_by_name("Mira").unwrap().is_none());
+
+        db.in_transaction(|db| db.insert_subject(&subject("Mira")))
+            .unwrap();
+        assert!(db.get_sub_by_name("Mira").unwrap().is_some());
+    }
 }
@@ -0,0 +1,254 @@
+use sha2::{Digest, Sha256};
+
+use super::{unix_now, EntityRef, Schema};
+use crate::images;
+use crate::schema::Result;
+
+/// An image attached to an entity. The image data itself is loaded separately with
+/// [`Schema::attachment_data`] and [`Schema::thumbnail`].
+#[derive(Clone, Debug, PartialEq)]
+pub struct Attachment {
+    /// Id of the stored image, shared by all entities the same image is attached to
+    pub id: i64,
+    pub mime: String,
+    pub width: u32,
+    pub height: u32,
+    pub file_name: String,
+    pub caption: String,
+    /// Shown at the top of the entity's page
+    pub portrait: bool,
+}
+
+impl Schema {
+    /// Stores the image, unless the same image is already stored, and attaches it to `x`. The
+    /// first image of an entity becomes its portrait. Returns the id of the stored image.
+    /// Fails with [`crate::schema::Error::UnsupportedImage`] if `data` isn't a PNG, JPEG or
+    /// WebP image.
+    pub fn attach_image(&mut self, x: EntityRef, data: &[u8], file_name: &str) -> Result<i64> {
+        self.in_transaction(|db| db.attach_image_in_transaction(x, data, file_name))
+    }
+
+    fn attach_image_in_transaction(
+        &mut self,
+        x: EntityRef,
+        data: &[u8],
+        file_name: &str,
+    ) -> Result<i64> {
+        let id = self.store_image(data, file_name)?;
+        let first = self.attachments_of(x)?.is_empty();
+        let mut stmt = self.conn.prepare(
+            "insert or ignore into entity_attachments (kind, entity_id, attachment_id, portrait,
+                 position)
+             select ?, ?, ?, ?, coalesce(max(position) + 1, 0) from entity_attachments
+             where kind == ? and entity_id == ?;",
+        )?;
+        stmt.bind((1, x.kind.table()))?;
+        stmt.bind((2, x.id))?;
+        stmt.bind((3, id))?;
+        stmt.bind((4, first as i64))?;
+        stmt.bind((5, x.kind.table()))?;
+        stmt.bind((6, x.id))?;
+        assert!(stmt.next()? == sqlite::State::Done);
+        Ok(id)
+    }
+
+    /// Returns the id of the stored image with the same content, or stores a new one
+    pub(super) fn store_image(&mut self, data: &[u8], file_name: &str) -> Result<i64> {
+        let hash = format!("{:x}", Sha256::digest(data));
+        let mut stmt = self
+            .conn
+            .prepare("select id from attachments where hash == ?;")?;
+        stmt.bind((1, hash.as_str()))?;
+        if stmt.next()? == sqlite::State::Row {
+            return Ok(stmt.read::<i64, _>("id")?);
+        }
+
+        let image = images::import(data)?;
+        let mut stmt = self.conn.prepare(
+            "insert into attachments (hash, mime, data, thumbnail, width, height, file_name,
+                 created_at)
+             values (?, ?, ?, ?, ?, ?, ?, ?);",
+        )?;
+        stmt.bind((1, hash.as_str()))?;
+        stmt.bind((2, image.mime))?;
+        stmt.bind((3, data))?;
+        stmt.bind((4, image.thumbnail.as_slice()))?;
+        stmt.bind((5, image.width as i64))?;
+        stmt.bind((6, image.height as i64))?;
+        stmt.bind((7, file_name))?;
+        stmt.bind((8, unix_now()))?;
+        assert!(stmt.next()? == sqlite::State::Done);
+        self.last_insert_id()
+    }
+
+    /// The images attached to `x`, the portrait first, then in the order they were attached
+    pub fn attachments_of(&mut self, x: EntityRef) -> Result<Vec<Attachment>> {
+        self.conn
+            .prepare(
+                "select a.id, a.mime, a.width, a.height, a.file_name, e.caption, e.portrait
+                 from entity_attachments as e join attachments as a on a.id == e.attachment_id
+                 where e.kind == ? and e.entity_id == ?
+                 order by e.portrait desc, e.position;",
+            )?
+            .into_iter()
+            .bind((1, x.kind.table()))?
+            .bind((2, x.id))?
+            .map(|r| {
+                let r = r?;
+                Ok(Attachment {
+                    id: r.read::<i64, _>("id"),
+                    mime: r.read::<&str, _>("mime").to_string(),
+                    width: r.read::<i64, _>("width") as u32,
+                    height: r.read::<i64, _>("height") as u32,
+                    file_name: r.read::<&str, _>("file_name").to_string(),
+                    caption: r.read::<&str, _>("caption").to_string(),
+                    portrait: r.read::<i64, _>("portrait") != 0,
+                })
+            })
+            .collect()
+    }
+
+    /// The portrait of `x`, if it has any images
+    pub fn portrait_of(&mut self, x: EntityRef) -> Result<Option<Attachment>> {
+        Ok(self.attachments_of(x)?.into_iter().find(|a| a.portrait))
+    }
+
+    /// The mime type and full size data of a stored image
+    pub fn attachment_data(&mut self, id: i64) -> Result<Option<(String, Vec<u8>)>> {
+        let mut stmt = self
+            .conn
+            .prepare("select mime, data from attachments where id == ?;")?;
+        stmt.bind((1, id))?;
+        if stmt.next()? != sqlite::State::Row {
+            return Ok(None);
+        }
+        Ok(Some((
+            stmt.read::<String, _>("mime")?,
+            stmt.read::<Vec<u8>, _>("data")?,
+        )))
+    }
+
+    /// The PNG encoded thumbnail of a stored image
+    pub fn thumbnail(&mut self, id: i64) -> Result<Option<Vec<u8>>> {
+        let mut stmt = self
+            .conn
+            .prepare("select thumbnail from attachments where id == ?;")?;
+        stmt.bind((1, id))?;
+        if stmt.next()? != sqlite::State::Row {
+            return Ok(None);
+        }
+        Ok(Some(stmt.read::<Vec<u8>, _>("thumbnail")?))
+    }
+
+    /// Makes the image `id` the portrait of `x`
+    pub fn set_portrait(&mut self, x: EntityRef, id: i64) -> Result<()> {
+        let mut stmt = self.conn.prepare(
+            "update entity_attachments set portrait = (attachment_id == ?)
+             where kind == ? and entity_id == ?;",
+        )?;
+        stmt.bind((1, id))?;
+        stmt.bind((2, x.kind.table()))?;
+        stmt.bind((3, x.id))?;
+        assert!(stmt.next()? == sqlite::State::Done);
+        Ok(())
+    }
+
+    pub fn set_caption(&mut self, x: EntityRef, id: i64, caption: &str) -> Result<()> {
+        let mut stmt = self.conn.prepare(
+            "update entity_attachments set caption = ?
+             where kind == ? and entity_id == ? and attachment_id == ?;",
+        )?;
+        stmt.bind((1, caption))?;
+        stmt.bind((2, x.kind.table()))?;
+        stmt.bind((3, x.id))?;
+        stmt.bind((4, id))?;
+        assert!(stmt.next()? == sqlite::State::Done);
+        Ok(())
+    }
+
+    /// Removes the image `id` from `x`. The image itself is deleted when no other entity uses
+    /// it. If it was the portrait, the next image becomes the portrait.
+    pub fn detach_image(&mut self, x: EntityRef, id: i64) -> Result<()> {
+        let mut stmt = self.conn.prepare(
+            "delete from entity_attachments
+             where kind == ? and entity_id == ? and attachment_id == ?;",
+        )?;
+        stmt.bind((1, x.kind.table()))?;
+        stmt.bind((2, x.id))?;
+        stmt.bind((3, id))?;
+        assert!(stmt.next()? == sqlite::State::Done);
+        if let Some(next) = self.attachments_of(x)?.first() {
+            if !next.portrait {
+                self.set_portrait(x, next.id)?;
+            }
+        }
+        self.delete_unused_images()
+    }
+
+    pub(super) fn delete_attachments(&mut self, x: EntityRef) -> Result<()> {
+        let mut stmt = self
+            .conn
+            .prepare("delete from entity_attachments where kind == ? and entity_id == ?;")?;
+        stmt.bind((1, x.kind.table()))?;
+        stmt.bind((2, x.id))?;
+        assert!(stmt.next()? == sqlite::State::Done);
+        self.delete_unused_images()
+    }
+
+    /// Deletes stored images that are neither attached to an entity nor used as a map
+    pub(super) fn delete_unused_images(&mut self) -> Result<()> {
+        self.conn.execute(
+            "delete from attachments
+             where id not in (select attachment_id from entity_attachments)
+             and id not in (select attachment_id from place_maps);",
+        )?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::schema::v1::{EntityKind, Subject};
+    use crate::test_util::png;
+
+    fn subject(db: &mut Schema, name: &str) -> EntityRef {
+        let id = db
+            .insert_subject(&Subject {
+                name: name.into(),
+                description: "".into(),
+            })
+            .unwrap();
+        EntityRef::new(EntityKind::Subject, id)
+    }
+
+    #[test]
+    fn images_are_shared_and_cleaned_up() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut db = Schema::create_new(dir.path().join("test.db")).unwrap();
+        let mira = subject(&mut db, "Mira");
+        let olaf = subject(&mut db, "Olaf");
+
+        let small = db.attach_image(mira, &png(10, 10), "small.png").unwrap();
+        let big = db.attach_image(mira, &png(20, 10), "big.png").unwrap();
+        assert_eq!(
+            db.attach_image(olaf, &png(10, 10), "copy.png").unwrap(),
+            small
+        );
+        assert!(db.attach_image(olaf, b"no image", "x.png").is_err());
+
+        let images = db.attachments_of(mira).unwrap();
+        assert_eq!(images.len(), 2);
+        assert!(images[0].portrait && images[0].id == small);
+        assert_eq!((images[1].width, images[1].height), (20, 10));
+
+        db.set_portrait(mira, big).unwrap();
+        assert_eq!(db.portrait_of(mira).unwrap().unwrap().id, big);
+
+        db.detach_image(mira, small).unwrap();
+        assert!(db.thumbnail(small).unwrap().is_some());
+        db.detach_image(olaf, small).unwrap();
+        assert!(db.thumbnail(small).unwrap().is_none());
+        assert_eq!(db.attachment_data(big).unwrap().unwrap().0, "image/png");
+    }
+}
@@ -0,0 +1,190 @@
+use std::collections::HashMap;
+use std::ops::Range;
+
+use super::{Audience, EntityKind, EntityRef, Schema};
+use crate::schema::Result;
+use crate::wiki::{parse_links, strip_secrets};
+
+/// Number of bytes shown on each side of a mention in a backlink snippet
+const CONTEXT: usize = 60;
+
+/// An entity that refers to another one, either through a wiki link in its description or a
+/// row in one of the mapping tables
+#[derive(Clone, Debug, PartialEq)]
+pub struct Backlink {
+    pub source: EntityRef,
+    /// Name of the source, or the in-world date for events
+    pub label: String,
+    /// Plain text around the first wiki link to the target. None if the source is only linked
+    /// through a mapping table.
+    pub snippet: Option<String>,
+}
+
+impl Schema {
+    /// Everything that mentions or links to `x`, sorted by kind and label. Each source occurs
+    /// only once, deleted entities are left out, and so is everything `audience` may not see.
+    pub fn backlinks(&mut self, x: EntityRef, audience: Audience) -> Result<Vec<Backlink>> {
+        let mut res = self.wiki_mentions(x, audience)?;
+        for l in self.incoming_links(x)? {
+            if audience.sees(l.secret) && !res.iter().any(|b| b.source == l.entity) {
+                res.push(Backlink {
+                    source: l.entity,
+                    label: l.label,
+                    snippet: None,
+                });
+            }
+        }
+        res.sort_by(|a, b| (a.source.kind, &a.label).cmp(&(b.source.kind, &b.label)));
+        Ok(res)
+    }
+
+    /// Entities whose description contains a wiki link that resolves to `x`. For the players,
+    /// secret entities and links in secret blocks don't count.
+    fn wiki_mentions(&mut self, x: EntityRef, audience: Audience) -> Result<Vec<Backlink>> {
+        let mut candidates = vec![];
+        for kind in [
+            EntityKind::Subject,
+            EntityKind::Place,
+            EntityKind::Group,
+            EntityKind::Event,
+        ] {
+            let query = format!(
+                "select id, {} as label, description, secret from {}
+                 where description like '%[[%' and deleted_at is null;",
+                kind.label_column(),
+                kind.table()
+            );
+            for r in self.conn.prepare(query)?.into_iter() {
+                let r = r?;
+                if !audience.sees(r.read::<i64, _>("secret") != 0) {
+                    continue;
+                }
+                let description = r.read::<Option<&str>, _>("description").unwrap_or("");
+                candidates.push((
+                    EntityRef::new(kind, r.read::<i64, _>("id")),
+                    r.read::<Option<&str>, _>("label").unwrap_or("").to_string(),
+                    match audience {
+                        Audience::GameMaster => description.to_string(),
+                        Audience::Players => strip_secrets(description),
+                    },
+                ));
+            }
+        }
+
+        // the same names show up in many descriptions
+        let mut resolved: HashMap<String, Option<EntityRef>> = HashMap::new();
+        let mut res = vec![];
+        for (source, label, description) in candidates {
+            if source == x {
+                continue;
+            }
+            for link in parse_links(&description) {
+                let target = match resolved.get(&link.target) {
+                    Some(target) => *target,
+                    None => {
+                        let target = self.resolve_name(&link.target)?;
+                        resolved.insert(link.target.clone(), target);
+                        target
+                    }
+                };
+                if target == Some(x) {
+                    res.push(Backlink {
+                        source,
+                        label,
+                        snippet: Some(snippet(&description, link.range, link.display())),
+                    });
+                    break;
+                }
+            }
+        }
+        Ok(res)
+    }
+}
+
+/// The text around `range` on a single line, with the link itself replaced by `display`
+fn snippet(text: &str, range: Range<usize>, display: &str) -> String {
+    let line_start = text[..range.start].rfind('\n').map_or(0, |i| i + 1);
+    let line_end = text[range.end..]
+        .find('\n')
+        .map_or(text.len(), |i| i + range.end);
+    let mut start = range.start.saturating_sub(CONTEXT).max(line_start);
+    while !text.is_char_boundary(start) {
+        start += 1;
+    }
+    let mut end = (range.end + CONTEXT).min(line_end);
+    while !text.is_char_boundary(end) {
+        end -= 1;
+    }
+
+    let mut res = String::new();
+    if start > line_start {
+        res.push('…');
+    }
+    res.push_str(text[start..range.start].trim_start());
+    res.push_str(display);
+    res.push_str(text[range.end..end].trim_end());
+    if end < line_end {
+        res.push('…');
+    }
+    res
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::schema::v1::{Place, Subject};
+
+    #[test]
+    fn finds_wiki_mentions_and_mapped_links() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut db = Schema::create_new(dir.path().join("test.db")).unwrap();
+        for (name, description) in [
+            ("Mira", ""),
+            ("Aldric", "Owes money to [[mira|the innkeeper]]."),
+            ("Bob", "Has never heard of Mira."),
+        ] {
+            db.insert_subject(&Subject {
+                name: name.into(),
+                description: description.into(),
+            })
+            .unwrap();
+        }
+        let inn = db
+            .insert_place(&Place {
+                name: "The Rusty Anchor".into(),
+                description: "Run by [[Mira]]".into(),
+                parent_place: None,
+            })
+            .unwrap();
+        let mira = db.get_sub_by_name("Mira").unwrap().unwrap().id;
+        let bob = db.get_sub_by_name("Bob").unwrap().unwrap().id;
+        let mira = EntityRef::new(EntityKind::Subject, mira);
+        db.link(EntityRef::new(EntityKind::Subject, bob), mira)
+            .unwrap();
+
+        let links = db.backlinks(mira, Audience::GameMaster).unwrap();
+        let labels = links.iter().map(|b| b.label.as_str()).collect::<Vec<_>>();
+        assert_eq!(labels, vec!["Aldric", "Bob", "The Rusty Anchor"]);
+        assert_eq!(
+            links[0].snippet.as_deref(),
+            Some("Owes money to the innkeeper.")
+        );
+        assert_eq!(links[1].snippet, None);
+        assert_eq!(links[2].source, EntityRef::new(EntityKind::Place, inn));
+
+        db.set_secret(EntityRef::new(EntityKind::Place, inn), true)
+            .unwrap();
+        assert_eq!(db.backlinks(mira, Audience::Players).unwrap().len(), 2);
+    }
+
+    #[test]
+    fn snippet_stays_on_the_line_of_the_mention() {
+        let text = "first line\nsee [[X]] here\nlast line";
+        let range = text.find("[[").unwrap()..text.find("]]").unwrap() + 2;
+        assert_eq!(snippet(text, range, "X"), "see X here");
+
+        let text = format!("{}[[X]]", "a".repeat(100));
+        let range = 100..text.len();
+        assert_eq!(snippet(&text, range, "X"), format!("…{}X", "a".repeat(60)));
+    }
+}
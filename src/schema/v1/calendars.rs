@@ -0,0 +1,191 @@
+use super::{events::bind_date, Schema};
+use crate::calendar::{Calendar, Era, LeapRule, Month};
+use crate::schema::Result;
+
+impl Schema {
+    /// The calendar in-world dates of this campaign are written in
+    pub fn calendar(&mut self) -> Result<Calendar> {
+        read_calendar(&self.conn)
+    }
+
+    /// Replaces the calendar, and reads the in-world dates of all events again with the new
+    /// one. Events whose date isn't valid in the new calendar lose their structured date, but
+    /// keep the date text.
+    pub fn set_calendar(&mut self, x: &Calendar) -> Result<()> {
+        x.validate()?;
+        self.in_transaction(|db| {
+            write_calendar(&db.conn, x)?;
+            redate_events(&db.conn, x)
+        })
+    }
+}
+
+fn read_calendar(conn: &sqlite::Connection) -> Result<Calendar> {
+    let mut stmt = conn.prepare("select * from calendar where id == 1;")?;
+    if stmt.next()? != sqlite::State::Row {
+        return Ok(Calendar::gregorian());
+    }
+    let name = stmt.read::<String, _>("name")?;
+    let weekdays = stmt
+        .read::<String, _>("weekdays")?
+        .lines()
+        .map(|d| d.to_string())
+        .collect();
+    let before_eras = stmt.read::<String, _>("before_eras")?;
+    let leap = LeapRule {
+        every: stmt.read::<i64, _>("leap_every")?,
+        except: stmt.read::<i64, _>("leap_except")?,
+        unless: stmt.read::<i64, _>("leap_unless")?,
+    };
+    let first_weekday = stmt.read::<i64, _>("first_weekday")? as usize;
+
+    let months = conn
+        .prepare("select * from calendar_months order by position;")?
+        .into_iter()
+        .map(|r| {
+            let r = r?;
+            Ok(Month {
+                name: r.read::<&str, _>("name").to_string(),
+                days: r.read::<i64, _>("days") as u32,
+                leap_days: r.read::<i64, _>("leap_days") as u32,
+            })
+        })
+        .collect::<Result<Vec<_>>>()?;
+    let eras = conn
+        .prepare("select * from calendar_eras order by start_year;")?
+        .into_iter()
+        .map(|r| {
+            let r = r?;
+            Ok(Era {
+                name: r.read::<&str, _>("name").to_string(),
+                start_year: r.read::<i64, _>("start_year"),
+            })
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    Ok(Calendar {
+        name,
+        months,
+        weekdays,
+        eras,
+        before_eras,
+        leap,
+        first_weekday,
+    })
+}
+
+/// Replaces the stored calendar, inside the transaction of [`Schema::set_calendar`]
+fn write_calendar(conn: &sqlite::Connection, x: &Calendar) -> Result<()> {
+    conn.execute(
+        "delete from calendar;
+         delete from calendar_months;
+         delete from calendar_eras;",
+    )?;
+    let mut stmt = conn.prepare(
+        "insert into calendar (id, name, weekdays, before_eras, leap_every, leap_except,
+             leap_unless, first_weekday)
+         values (1, ?, ?, ?, ?, ?, ?, ?);",
+    )?;
+    stmt.bind((1, x.name.as_str()))?;
+    stmt.bind((2, x.weekdays.join("\n").as_str()))?;
+    stmt.bind((3, x.before_eras.as_str()))?;
+    stmt.bind((4, x.leap.every))?;
+    stmt.bind((5, x.leap.except))?;
+    stmt.bind((6, x.leap.unless))?;
+    stmt.bind((7, x.first_weekday as i64))?;
+    assert!(stmt.next()? == sqlite::State::Done);
+
+    let mut stmt = conn.prepare(
+        "insert into calendar_months (position, name, days, leap_days) values (?, ?, ?, ?);",
+    )?;
+    for (i, m) in x.months.iter().enumerate() {
+        stmt.reset()?;
+        stmt.bind((1, i as i64))?;
+        stmt.bind((2, m.name.as_str()))?;
+        stmt.bind((3, m.days as i64))?;
+        stmt.bind((4, m.leap_days as i64))?;
+        assert!(stmt.next()? == sqlite::State::Done);
+    }
+
+    let mut stmt = conn.prepare("insert into calendar_eras (start_year, name) values (?, ?);")?;
+    for e in &x.eras {
+        stmt.reset()?;
+        stmt.bind((1, e.start_year))?;
+        stmt.bind((2, e.name.as_str()))?;
+        assert!(stmt.next()? == sqlite::State::Done);
+    }
+    Ok(())
+}
+
+/// Parses the date text of every event with the calendar, and stores the result as its
+/// structured date, or nothing if the text isn't a valid date
+fn redate_events(conn: &sqlite::Connection, calendar: &Calendar) -> Result<()> {
+    let events = conn
+        .prepare("select id, refered_date from events;")?
+        .into_iter()
+        .map(|r| {
+            let r = r?;
+            Ok((
+                r.read::<i64, _>("id"),
+                r.read::<&str, _>("refered_date").to_string(),
+            ))
+        })
+        .collect::<Result<Vec<_>>>()?;
+    let mut stmt = conn
+        .prepare("update events set date_year = ?, date_month = ?, date_day = ? where id == ?;")?;
+    for (id, text) in events {
+        stmt.reset()?;
+        bind_date(&mut stmt, 1, calendar.parse(&text).ok())?;
+        stmt.bind((4, id))?;
+        assert!(stmt.next()? == sqlite::State::Done);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::calendar::CalendarDate;
+    use crate::schema::v1::Event;
+
+    #[test]
+    fn events_are_redated_with_the_new_calendar() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut db = Schema::create_new(dir.path().join("test.db")).unwrap();
+        assert_eq!(db.calendar().unwrap(), Calendar::gregorian());
+
+        let mut event = |text: &str| {
+            db.insert_event(&Event {
+                record_date: 0,
+                refered_date: text.into(),
+                description: "".into(),
+                date: None,
+                session: None,
+            })
+            .unwrap()
+        };
+        let midwinter = event("Midwinter 1492 DR");
+        let march = event("14 March 2023");
+
+        let harptos = Calendar::harptos();
+        db.set_calendar(&harptos).unwrap();
+        assert_eq!(db.calendar().unwrap(), harptos);
+        assert_eq!(
+            db.get_event(midwinter).unwrap().unwrap().date,
+            Some(CalendarDate {
+                year: 1492,
+                month: 1,
+                day: 1
+            })
+        );
+        assert_eq!(db.get_event(march).unwrap().unwrap().date, None);
+
+        let order: Vec<_> = db
+            .events_in_world_order()
+            .unwrap()
+            .into_iter()
+            .map(|e| e.id)
+            .collect();
+        assert_eq!(order, vec![midwinter, march]);
+    }
+}
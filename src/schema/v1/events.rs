@@ -0,0 +1,280 @@
+use super::{links::checked_mapping_table, EntityKind, EntityRef, Event, Schema};
+use crate::calendar::CalendarDate;
+use crate::schema::{Result, WithId};
+
+impl Schema {
+    /// Inserts the event and returns its id. Without a structured date, the date text is
+    /// parsed with the campaign's calendar.
+    pub fn insert_event(&mut self, x: &Event) -> Result<i64> {
+        let date = self.structured_date(x)?;
+        let mut stmt = self.conn.prepare(
+            "insert into events (record_date, refered_date, description, date_year, date_month,
+                 date_day, session_id)
+             values (?, ?, ?, ?, ?, ?, ?);",
+        )?;
+        stmt.bind((1, x.record_date as i64))?;
+        stmt.bind((2, x.refered_date.as_str()))?;
+        stmt.bind((3, x.description.as_str()))?;
+        bind_date(&mut stmt, 4, date)?;
+        stmt.bind((7, x.session))?;
+        assert!(stmt.next()? == sqlite::State::Done);
+        self.last_insert_id()
+    }
+
+    /// Like [`Schema::insert_event`], the date text is parsed if there is no structured date
+    pub fn update_event(&mut self, x: &WithId<Event>) -> Result<()> {
+        let date = self.structured_date(x)?;
+        let mut stmt = self.conn.prepare(
+            "update events set record_date = ?, refered_date = ?, description = ?,
+                 date_year = ?, date_month = ?, date_day = ?, session_id = ?
+             where id == ?;",
+        )?;
+        stmt.bind((1, x.record_date as i64))?;
+        stmt.bind((2, x.refered_date.as_str()))?;
+        stmt.bind((3, x.description.as_str()))?;
+        bind_date(&mut stmt, 4, date)?;
+        stmt.bind((7, x.session))?;
+        stmt.bind((8, x.id))?;
+        assert!(stmt.next()? == sqlite::State::Done);
+        Ok(())
+    }
+
+    pub fn get_event(&mut self, id: i64) -> Result<Option<WithId<Event>>> {
+        Ok(self
+            .query_events("select * from events where id == ?;", &[id])?
+            .into_iter()
+            .next())
+    }
+
+    /// All events, the most recently recorded first
+    pub fn list_events(&mut self) -> Result<Vec<WithId<Event>>> {
+        self.query_events(
+            "select * from events where deleted_at is null order by record_date desc, id desc;",
+            &[],
+        )
+    }
+
+    /// All events in the order they happen in the world. Events without a valid in-world date
+    /// come last, in the order they were recorded.
+    pub fn events_in_world_order(&mut self) -> Result<Vec<WithId<Event>>> {
+        self.query_events(
+            "select * from events where deleted_at is null
+             order by date_year is null, date_year, date_month, date_day, record_date, id;",
+            &[],
+        )
+    }
+
+    /// Like [`Schema::events_in_world_order`], but only the events that are linked to all of
+    /// the given entities
+    pub fn events_linked_to(&mut self, entities: &[EntityRef]) -> Result<Vec<WithId<Event>>> {
+        let mut query = "select events.* from events".to_string();
+        for (i, x) in entities.iter().enumerate() {
+            let table = checked_mapping_table(EntityKind::Event, x.kind)?;
+            query.push_str(&format!(
+                " join {table} as m{i} on m{i}.kfrom == events.id and m{i}.kto == ?"
+            ));
+        }
+        query.push_str(
+            " where events.deleted_at is null
+             order by date_year is null, date_year, date_month, date_day, record_date, events.id;",
+        );
+        let ids: Vec<_> = entities.iter().map(|x| x.id).collect();
+        self.query_events(&query, &ids)
+    }
+
+    /// Events recorded in the half open interval `[from, to)`, given as unix timestamps,
+    /// in the order they were recorded
+    pub fn events_recorded_between(&mut self, from: u64, to: u64) -> Result<Vec<WithId<Event>>> {
+        self.query_events(
+            "select * from events
+             where record_date >= ? and record_date < ? and deleted_at is null
+             order by record_date, id;",
+            &[from as i64, to as i64],
+        )
+    }
+
+    /// Events whose in-world date is exactly `date`
+    pub fn events_at(&mut self, date: &str) -> Result<Vec<WithId<Event>>> {
+        self.conn
+            .prepare(
+                "select * from events where refered_date == ? and deleted_at is null
+                 order by record_date, id;",
+            )?
+            .into_iter()
+            .bind((1, date))?
+            .map(|r| Ok(event_from_row(&r?)))
+            .collect()
+    }
+
+    /// Deletes the event together with all its links to other entities
+    pub fn delete_event(&mut self, id: i64) -> Result<()> {
+        self.delete_entity_row(EntityRef::new(EntityKind::Event, id))
+    }
+
+    /// Assigns the event to a play session, or removes it from its session
+    pub fn set_event_session(&mut self, event: i64, session: Option<i64>) -> Result<()> {
+        let mut stmt = self
+            .conn
+            .prepare("update events set session_id = ? where id == ?;")?;
+        stmt.bind((1, session))?;
+        stmt.bind((2, event))?;
+        assert!(stmt.next()? == sqlite::State::Done);
+        Ok(())
+    }
+
+    fn structured_date(&mut self, x: &Event) -> Result<Option<CalendarDate>> {
+        Ok(match x.date {
+            Some(date) => Some(date),
+            None => self.calendar()?.parse(&x.refered_date).ok(),
+        })
+    }
+
+    fn query_events(&mut self, query: &str, args: &[i64]) -> Result<Vec<WithId<Event>>> {
+        let mut cursor = self.conn.prepare(query)?.into_iter();
+        for (i, arg) in args.iter().enumerate() {
+            cursor = cursor.bind((i + 1, *arg))?;
+        }
+        cursor.map(|r| Ok(event_from_row(&r?))).collect()
+    }
+}
+
+/// Binds year, month and day of the date, or nulls, starting at parameter `first`
+pub(super) fn bind_date(
+    stmt: &mut sqlite::Statement,
+    first: usize,
+    date: Option<CalendarDate>,
+) -> Result<()> {
+    stmt.bind((first, date.map(|d| d.year)))?;
+    stmt.bind((first + 1, date.map(|d| d.month as i64)))?;
+    stmt.bind((first + 2, date.map(|d| d.day as i64)))?;
+    Ok(())
+}
+
+fn event_from_row(r: &sqlite::Row) -> WithId<Event> {
+    let date = match (
+        r.read::<Option<i64>, _>("date_year"),
+        r.read::<Option<i64>, _>("date_month"),
+        r.read::<Option<i64>, _>("date_day"),
+    ) {
+        (Some(year), Some(month), Some(day)) => Some(CalendarDate {
+            year,
+            month: month as u32,
+            day: day as u32,
+        }),
+        _ => None,
+    };
+    WithId {
+        t: Event {
+            record_date: r.read::<i64, _>("record_date") as u64,
+            refered_date: r.read::<&str, _>("refered_date").to_string(),
+            description: r.read::<&str, _>("description").to_string(),
+            date,
+            session: r.read::<Option<i64>, _>("session_id"),
+        },
+        id: r.read::<i64, _>("id"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::schema::v1::{Subject, Tag};
+
+    fn event(text: &str, record_date: u64) -> Event {
+        Event {
+            record_date,
+            refered_date: text.into(),
+            description: format!("Something happened on {text}"),
+            date: None,
+            session: None,
+        }
+    }
+
+    #[test]
+    fn events_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut db = Schema::create_new(dir.path().join("test.db")).unwrap();
+        let first = db.insert_event(&event("3 May 1200", 10)).unwrap();
+        let second = db.insert_event(&event("Some day", 20)).unwrap();
+
+        let ev = db.get_event(first).unwrap().unwrap();
+        assert_eq!(ev.refered_date, "3 May 1200");
+        assert_eq!(ev.description, "Something happened on 3 May 1200");
+        assert_eq!(ev.record_date, 10);
+        // the date text is read with the calendar
+        assert_eq!(
+            ev.date,
+            Some(CalendarDate {
+                year: 1200,
+                month: 4,
+                day: 3
+            })
+        );
+        assert_eq!(db.get_event(second).unwrap().unwrap().date, None);
+        assert!(db.get_event(second + 1).unwrap().is_none());
+
+        let ids = |events: Vec<WithId<Event>>| events.into_iter().map(|e| e.id).collect::<Vec<_>>();
+        assert_eq!(ids(db.list_events().unwrap()), vec![second, first]);
+        assert_eq!(ids(db.events_at("3 May 1200").unwrap()), vec![first]);
+        assert!(db.events_at("4 May 1200").unwrap().is_empty());
+
+        db.update_event(&WithId {
+            t: event("4 May 1200", 10),
+            id: first,
+        })
+        .unwrap();
+        let ev = db.get_event(first).unwrap().unwrap();
+        assert_eq!(ev.date.map(|d| d.day), Some(4));
+        assert!(db.events_at("3 May 1200").unwrap().is_empty());
+        assert_eq!(ids(db.events_at("4 May 1200").unwrap()), vec![first]);
+
+        db.delete_event(second).unwrap();
+        assert_eq!(ids(db.list_events().unwrap()), vec![first]);
+    }
+
+    #[test]
+    fn events_are_filtered_by_links() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut db = Schema::create_new(dir.path().join("test.db")).unwrap();
+        let event = |db: &mut Schema, text: &str| {
+            let id = db
+                .insert_event(&Event {
+                    record_date: 0,
+                    refered_date: text.into(),
+                    description: "".into(),
+                    date: None,
+                    session: None,
+                })
+                .unwrap();
+            EntityRef::new(EntityKind::Event, id)
+        };
+        let late = event(&mut db, "3 May 1200");
+        let early = event(&mut db, "1 April 1200");
+        let undated = event(&mut db, "Some day");
+        let mira = db
+            .insert_subject(&Subject {
+                name: "Mira".into(),
+                description: "".into(),
+            })
+            .unwrap();
+        let mira = EntityRef::new(EntityKind::Subject, mira);
+        let tag = EntityRef::new(
+            EntityKind::Tag,
+            db.insert_tag(&Tag { name: "war".into() }).unwrap(),
+        );
+        for ev in [late, early, undated] {
+            db.link(ev, mira).unwrap();
+        }
+        db.link(late, tag).unwrap();
+
+        let ids = |events: Vec<WithId<Event>>| events.into_iter().map(|e| e.id).collect::<Vec<_>>();
+        assert_eq!(
+            ids(db.events_linked_to(&[mira]).unwrap()),
+            vec![early.id, late.id, undated.id]
+        );
+        assert_eq!(
+            ids(db.events_linked_to(&[mira, tag]).unwrap()),
+            vec![late.id]
+        );
+    }
+}
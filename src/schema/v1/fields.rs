@@ -0,0 +1,693 @@
+use std::cmp::Ordering;
+
+use super::{search_key, Audience, EntityKind, EntityRef, Schema};
+use crate::calendar::Calendar;
+use crate::schema::{Error, Result, WithId};
+
+/// The type of a custom field, see [`FieldValue`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FieldType {
+    Text,
+    Number,
+    Boolean,
+    Date,
+    Reference,
+}
+
+impl FieldType {
+    pub const ALL: [FieldType; 5] = [
+        FieldType::Text,
+        FieldType::Number,
+        FieldType::Boolean,
+        FieldType::Date,
+        FieldType::Reference,
+    ];
+
+    /// The name stored in the database
+    pub fn as_str(self) -> &'static str {
+        match self {
+            FieldType::Text => "text",
+            FieldType::Number => "number",
+            FieldType::Boolean => "boolean",
+            FieldType::Date => "date",
+            FieldType::Reference => "reference",
+        }
+    }
+
+    pub fn from_name(s: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|t| t.as_str() == s)
+    }
+}
+
+/// The value of a custom field
+#[derive(Clone, Debug, PartialEq)]
+pub enum FieldValue {
+    Text(String),
+    Number(f64),
+    Boolean(bool),
+    /// An in-world date as written by the user, valid in the campaign calendar when it was set
+    Date(String),
+    /// Another entity, e.g. the owner of a shop
+    Reference(EntityRef),
+}
+
+impl FieldValue {
+    pub fn field_type(&self) -> FieldType {
+        match self {
+            FieldValue::Text(_) => FieldType::Text,
+            FieldValue::Number(_) => FieldType::Number,
+            FieldValue::Boolean(_) => FieldType::Boolean,
+            FieldValue::Date(_) => FieldType::Date,
+            FieldValue::Reference(_) => FieldType::Reference,
+        }
+    }
+}
+
+/// A typed attribute of an entity, like "HP" or "Owner". Names are unique per entity,
+/// ignoring case and diacritics.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Field {
+    pub name: String,
+    pub value: FieldValue,
+}
+
+/// A condition on a custom field, written as e.g. `hp>10`, `alignment=evil` or `notes:dragon`
+/// in a search query. Underscores in the name stand for spaces.
+#[derive(Clone, Debug, PartialEq)]
+pub struct FieldFilter {
+    pub name: String,
+    pub op: FilterOp,
+    pub value: String,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FilterOp {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    Contains,
+}
+
+/// Used to order entities by a field. Numbers sort before text.
+#[derive(Clone, Debug, PartialEq)]
+pub enum SortKey {
+    Number(f64),
+    Text(String),
+}
+
+impl SortKey {
+    pub fn compare(&self, other: &Self) -> Ordering {
+        match (self, other) {
+            (SortKey::Number(a), SortKey::Number(b)) => a.total_cmp(b),
+            (SortKey::Number(_), SortKey::Text(_)) => Ordering::Less,
+            (SortKey::Text(_), SortKey::Number(_)) => Ordering::Greater,
+            (SortKey::Text(a), SortKey::Text(b)) => a.cmp(b),
+        }
+    }
+}
+
+impl Schema {
+    /// The custom fields of `x`, in the order they were added
+    pub fn fields_of(&mut self, x: EntityRef) -> Result<Vec<WithId<Field>>> {
+        self.conn
+            .prepare(
+                "select * from fields where kind == ? and entity_id == ? order by position, id;",
+            )?
+            .into_iter()
+            .bind((1, x.kind.table()))?
+            .bind((2, x.id))?
+            .filter_map(|r| match r {
+                Ok(r) => field_from_row(&r).map(Ok),
+                Err(e) => Some(Err(e.into())),
+            })
+            .collect()
+    }
+
+    /// The fields of `x` that `audience` may see, without references to hidden entities
+    pub fn visible_fields_of(
+        &mut self,
+        x: EntityRef,
+        audience: Audience,
+    ) -> Result<Vec<WithId<Field>>> {
+        let mut res = vec![];
+        for field in self.fields_of(x)? {
+            if let FieldValue::Reference(r) = field.value {
+                if !self.is_visible(r, audience)? {
+                    continue;
+                }
+            }
+            res.push(field);
+        }
+        Ok(res)
+    }
+
+    /// Sets the field with the same name, or adds it at the end. Returns the id of the field.
+    pub fn set_field(&mut self, x: EntityRef, field: &Field) -> Result<i64> {
+        let (value, ref_kind, ref_id) = match &field.value {
+            FieldValue::Text(s) | FieldValue::Date(s) => (Some(s.clone()), None, None),
+            FieldValue::Number(n) => (Some(n.to_string()), None, None),
+            FieldValue::Boolean(b) => (Some((*b as i64).to_string()), None, None),
+            FieldValue::Reference(r) => (None, Some(r.kind.table()), Some(r.id)),
+        };
+        let mut stmt = self.conn.prepare(
+            "insert into fields
+                (kind, entity_id, name, name_key, field_type, value, ref_kind, ref_id, position)
+             values (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8,
+                (select coalesce(max(position), 0) + 1 from fields
+                 where kind == ?1 and entity_id == ?2))
+             on conflict(kind, entity_id, name_key) do update set
+                name = excluded.name,
+                field_type = excluded.field_type,
+                value = excluded.value,
+                ref_kind = excluded.ref_kind,
+                ref_id = excluded.ref_id;",
+        )?;
+        stmt.bind((1, x.kind.table()))?;
+        stmt.bind((2, x.id))?;
+        stmt.bind((3, field.name.as_str()))?;
+        stmt.bind((4, search_key(&field.name).as_str()))?;
+        stmt.bind((5, field.value.field_type().as_str()))?;
+        stmt.bind((6, value.as_deref()))?;
+        stmt.bind((7, ref_kind))?;
+        stmt.bind((8, ref_id))?;
+        assert!(stmt.next()? == sqlite::State::Done);
+
+        let mut stmt = self.conn.prepare(
+            "select id from fields where kind == ? and entity_id == ? and name_key == ?;",
+        )?;
+        stmt.bind((1, x.kind.table()))?;
+        stmt.bind((2, x.id))?;
+        stmt.bind((3, search_key(&field.name).as_str()))?;
+        assert!(stmt.next()? == sqlite::State::Row);
+        Ok(stmt.read::<i64, _>(0)?)
+    }
+
+    pub fn delete_field(&mut self, id: i64) -> Result<()> {
+        let mut stmt = self.conn.prepare("delete from fields where id == ?;")?;
+        stmt.bind((1, id))?;
+        assert!(stmt.next()? == sqlite::State::Done);
+        Ok(())
+    }
+
+    /// All field names that are in use by entities `audience` may see, sorted, each only once.
+    /// Fields of trashed entities don't count.
+    pub fn field_names(&mut self, audience: Audience) -> Result<Vec<String>> {
+        let hidden = match audience {
+            Audience::GameMaster => "",
+            Audience::Players => " and e.secret == 0",
+        };
+        let owners = [
+            EntityKind::Subject,
+            EntityKind::Place,
+            EntityKind::Group,
+            EntityKind::Event,
+        ]
+        .map(|kind| {
+            format!(
+                "exists (select 1 from {0} e where f.kind == '{0}' and e.id == f.entity_id
+                     and e.deleted_at is null{hidden})",
+                kind.table()
+            )
+        })
+        .join(" or ");
+        self.conn
+            .prepare(format!(
+                "select min(f.name) as name from fields f where {owners}
+                 group by f.name_key order by f.name_key;"
+            ))?
+            .into_iter()
+            .map(|r| Ok(r?.read::<&str, _>("name").to_string()))
+            .collect()
+    }
+
+    /// How a value is shown to the user. References show the label of the entity they point
+    /// to, or nothing if it is gone.
+    pub fn field_text(&mut self, value: &FieldValue) -> Result<String> {
+        Ok(match value {
+            FieldValue::Text(s) | FieldValue::Date(s) => s.clone(),
+            FieldValue::Number(n) => n.to_string(),
+            FieldValue::Boolean(true) => "yes".into(),
+            FieldValue::Boolean(false) => "no".into(),
+            FieldValue::Reference(r) => self.entity_label(*r)?.unwrap_or_default(),
+        })
+    }
+
+    /// True if `x` passes all filters. Fields `audience` can't see count as missing.
+    pub fn matches_field_filters(
+        &mut self,
+        x: EntityRef,
+        filters: &[FieldFilter],
+        audience: Audience,
+    ) -> Result<bool> {
+        if filters.is_empty() {
+            return Ok(true);
+        }
+        let fields = self.visible_fields_of(x, audience)?;
+        let calendar = self.calendar()?;
+        for filter in filters {
+            let key = search_key(&filter.name.replace('_', " "));
+            let matches = match fields.iter().find(|f| search_key(&f.name) == key) {
+                Some(f) => {
+                    let text = self.field_text(&f.value)?;
+                    filter.matches(&f.value, &text, &calendar)
+                }
+                None => filter.op == FilterOp::Ne,
+            };
+            if !matches {
+                return Ok(false);
+            }
+        }
+        Ok(true)
+    }
+
+    /// The value of the named field of `x` for sorting, None if `x` doesn't have it or
+    /// `audience` can't see it
+    pub fn field_sort_key(
+        &mut self,
+        x: EntityRef,
+        name: &str,
+        audience: Audience,
+    ) -> Result<Option<SortKey>> {
+        let key = search_key(name);
+        let Some(field) = self
+            .visible_fields_of(x, audience)?
+            .into_iter()
+            .find(|f| search_key(&f.name) == key)
+        else {
+            return Ok(None);
+        };
+        Ok(Some(match &field.value {
+            FieldValue::Number(n) => SortKey::Number(*n),
+            FieldValue::Boolean(b) => SortKey::Number(*b as i64 as f64),
+            // dates that the calendar no longer understands sort after the others
+            FieldValue::Date(s) => {
+                let calendar = self.calendar()?;
+                match calendar.parse(s) {
+                    Ok(date) => SortKey::Number(calendar.day_number(date) as f64),
+                    Err(_) => SortKey::Text(search_key(s)),
+                }
+            }
+            value => SortKey::Text(search_key(&self.field_text(value)?)),
+        }))
+    }
+
+    /// Turns user input into a value of the given type. References are looked up by name with
+    /// [`Schema::resolve_name`], dates have to be valid in the campaign calendar, and numbers
+    /// finite. `name` is the field the value is meant for, it is only used in the error message.
+    pub fn parse_field_value(
+        &mut self,
+        name: &str,
+        t: FieldType,
+        input: &str,
+    ) -> Result<FieldValue> {
+        let input = input.trim();
+        let invalid = |expected: &str| Error::InvalidFieldValue {
+            field: name.to_string(),
+            value: input.to_string(),
+            expected: expected.to_string(),
+        };
+        Ok(match t {
+            FieldType::Text => FieldValue::Text(input.to_string()),
+            FieldType::Date => {
+                self.calendar()?.parse(input)?;
+                FieldValue::Date(input.to_string())
+            }
+            FieldType::Number => FieldValue::Number(
+                input
+                    .parse::<f64>()
+                    .ok()
+                    .filter(|n| n.is_finite())
+                    .ok_or_else(|| invalid("a number"))?,
+            ),
+            FieldType::Boolean => {
+                FieldValue::Boolean(parse_bool(input).ok_or_else(|| invalid("yes or no"))?)
+            }
+            FieldType::Reference => FieldValue::Reference(
+                self.resolve_name(input)?
+                    .ok_or_else(|| invalid("the name of a subject, place or group"))?,
+            ),
+        })
+    }
+
+    /// Removes all fields of `x`
+    pub(super) fn delete_fields(&mut self, x: EntityRef) -> Result<()> {
+        let mut stmt = self
+            .conn
+            .prepare("delete from fields where kind == ? and entity_id == ?;")?;
+        stmt.bind((1, x.kind.table()))?;
+        stmt.bind((2, x.id))?;
+        assert!(stmt.next()? == sqlite::State::Done);
+        Ok(())
+    }
+}
+
+impl FieldFilter {
+    /// Checks the filter against a field value, `text` is the value as shown to the user. Dates
+    /// are compared in `calendar`.
+    pub fn matches(&self, value: &FieldValue, text: &str, calendar: &Calendar) -> bool {
+        if self.op == FilterOp::Contains {
+            return search_key(text).contains(&search_key(&self.value));
+        }
+        let ord = match value {
+            FieldValue::Number(n) => match self.value.parse::<f64>() {
+                Ok(v) => n.total_cmp(&v),
+                Err(_) => return self.op == FilterOp::Ne,
+            },
+            FieldValue::Boolean(b) => match parse_bool(&self.value) {
+                Some(v) if matches!(self.op, FilterOp::Eq | FilterOp::Ne) => b.cmp(&v),
+                _ => return false,
+            },
+            FieldValue::Date(s) => match (calendar.parse(s), calendar.parse(&self.value)) {
+                (Ok(a), Ok(b)) => calendar.day_number(a).cmp(&calendar.day_number(b)),
+                _ => return self.op == FilterOp::Ne,
+            },
+            _ => search_key(text).cmp(&search_key(&self.value)),
+        };
+        match self.op {
+            FilterOp::Eq => ord == Ordering::Equal,
+            FilterOp::Ne => ord != Ordering::Equal,
+            FilterOp::Lt => ord == Ordering::Less,
+            FilterOp::Le => ord != Ordering::Greater,
+            FilterOp::Gt => ord == Ordering::Greater,
+            FilterOp::Ge => ord != Ordering::Less,
+            FilterOp::Contains => unreachable!(),
+        }
+    }
+}
+
+/// Separates field filters like `hp>10` from the rest of a search query. Only words that start
+/// with one of the `known` field names are filters, so `12:30` or `wow!` are searched for.
+pub fn split_field_filters(query: &str, known: &[String]) -> (String, Vec<FieldFilter>) {
+    let known = known.iter().map(|n| search_key(n)).collect::<Vec<_>>();
+    let mut rest = vec![];
+    let mut filters = vec![];
+    for word in query.split_whitespace() {
+        let filter =
+            parse_filter(word).filter(|f| known.contains(&search_key(&f.name.replace('_', " "))));
+        match filter {
+            Some(f) => filters.push(f),
+            None => rest.push(word),
+        }
+    }
+    (rest.join(" "), filters)
+}
+
+fn parse_filter(word: &str) -> Option<FieldFilter> {
+    let start = word.find(['<', '>', '=', '!', ':'])?;
+    let (name, rest) = word.split_at(start);
+    let (op, value) = [
+        ("<=", FilterOp::Le),
+        (">=", FilterOp::Ge),
+        ("!=", FilterOp::Ne),
+        ("=", FilterOp::Eq),
+        ("<", FilterOp::Lt),
+        (">", FilterOp::Gt),
+        (":", FilterOp::Contains),
+    ]
+    .into_iter()
+    .find_map(|(s, op)| rest.strip_prefix(s).map(|v| (op, v)))?;
+    if name.is_empty() || name.starts_with('#') || value.is_empty() {
+        return None;
+    }
+    Some(FieldFilter {
+        name: name.to_string(),
+        op,
+        value: value.to_string(),
+    })
+}
+
+/// Parses the ways a user may write a boolean
+pub fn parse_bool(s: &str) -> Option<bool> {
+    match search_key(s.trim()).as_str() {
+        "yes" | "true" | "1" | "y" => Some(true),
+        "no" | "false" | "0" | "n" => Some(false),
+        _ => None,
+    }
+}
+
+fn field_from_row(r: &sqlite::Row) -> Option<WithId<Field>> {
+    let value = r.read::<Option<&str>, _>("value").unwrap_or("");
+    let value = match FieldType::from_name(r.read::<&str, _>("field_type"))? {
+        FieldType::Text => FieldValue::Text(value.to_string()),
+        FieldType::Number => FieldValue::Number(value.parse().unwrap_or(0.0)),
+        FieldType::Boolean => FieldValue::Boolean(value == "1"),
+        FieldType::Date => FieldValue::Date(value.to_string()),
+        FieldType::Reference => FieldValue::Reference(EntityRef::new(
+            EntityKind::from_table(r.read::<Option<&str>, _>("ref_kind")?)?,
+            r.read::<Option<i64>, _>("ref_id")?,
+        )),
+    };
+    Some(WithId {
+        t: Field {
+            name: r.read::<&str, _>("name").to_string(),
+            value,
+        },
+        id: r.read::<i64, _>("id"),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::schema::v1::Subject;
+
+    #[test]
+    fn parses_field_filters() {
+        let known = ["HP".to_string(), "Alignment".to_string()];
+        let (rest, filters) =
+            split_field_filters("orc hp>=10 alignment=evil #villain 12:30 wow!", &known);
+        assert_eq!(rest, "orc #villain 12:30 wow!");
+        assert_eq!(
+            filters,
+            vec![
+                FieldFilter {
+                    name: "hp".into(),
+                    op: FilterOp::Ge,
+                    value: "10".into()
+                },
+                FieldFilter {
+                    name: "alignment".into(),
+                    op: FilterOp::Eq,
+                    value: "evil".into()
+                },
+            ]
+        );
+        assert_eq!(split_field_filters("hp: x=", &known).1, vec![]);
+    }
+
+    #[test]
+    fn fields_are_stored_filtered_and_sorted() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut db = Schema::create_new(dir.path().join("test.db")).unwrap();
+        let mut subjects = vec![];
+        for name in ["Ogre", "Goblin"] {
+            db.insert_subject(&Subject {
+                name: name.into(),
+                description: "".into(),
+            })
+            .unwrap();
+            let id = db.get_sub_by_name(name).unwrap().unwrap().id;
+            subjects.push(EntityRef::new(EntityKind::Subject, id));
+        }
+        let (ogre, goblin) = (subjects[0], subjects[1]);
+        let hp = |n| Field {
+            name: "HP".into(),
+            value: FieldValue::Number(n),
+        };
+        db.set_field(ogre, &hp(59.0)).unwrap();
+        db.set_field(goblin, &hp(7.0)).unwrap();
+        db.set_field(
+            goblin,
+            &Field {
+                name: "Boss".into(),
+                value: FieldValue::Reference(ogre),
+            },
+        )
+        .unwrap();
+        // same name, different case: replaces the value
+        let id = db
+            .set_field(
+                ogre,
+                &Field {
+                    name: "hp".into(),
+                    value: FieldValue::Number(60.0),
+                },
+            )
+            .unwrap();
+        assert_eq!(db.fields_of(ogre).unwrap().len(), 1);
+        assert_eq!(db.fields_of(ogre).unwrap()[0].id, id);
+
+        let (_, filters) =
+            split_field_filters("hp>10", &db.field_names(Audience::GameMaster).unwrap());
+        assert!(db
+            .matches_field_filters(ogre, &filters, Audience::GameMaster)
+            .unwrap());
+        assert!(!db
+            .matches_field_filters(goblin, &filters, Audience::GameMaster)
+            .unwrap());
+        let (_, filters) =
+            split_field_filters("boss=ogre", &db.field_names(Audience::GameMaster).unwrap());
+        assert!(db
+            .matches_field_filters(goblin, &filters, Audience::GameMaster)
+            .unwrap());
+
+        let a = db
+            .field_sort_key(ogre, "HP", Audience::GameMaster)
+            .unwrap()
+            .unwrap();
+        let b = db
+            .field_sort_key(goblin, "HP", Audience::GameMaster)
+            .unwrap()
+            .unwrap();
+        assert_eq!(b.compare(&a), Ordering::Less);
+        assert_eq!(
+            db.field_names(Audience::GameMaster).unwrap(),
+            vec!["Boss", "HP"]
+        );
+    }
+
+    #[test]
+    fn numbers_have_to_be_finite() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut db = Schema::create_new(dir.path().join("test.db")).unwrap();
+        let mut number = |input: &str| db.parse_field_value("HP", FieldType::Number, input);
+        assert_eq!(number(" 12.5 ").unwrap(), FieldValue::Number(12.5));
+        for input in ["NaN", "inf", "-infinity", "1e999", "twelve"] {
+            assert!(matches!(
+                number(input),
+                Err(Error::InvalidFieldValue { .. })
+            ));
+        }
+    }
+
+    #[test]
+    fn dates_are_checked_sorted_and_filtered_in_the_calendar() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut db = Schema::create_new(dir.path().join("test.db")).unwrap();
+        assert!(matches!(
+            db.parse_field_value("Born", FieldType::Date, "sometime"),
+            Err(Error::InvalidDate(..))
+        ));
+        assert!(db
+            .parse_field_value("Born", FieldType::Date, "30 February 1200")
+            .is_err());
+
+        let mut subjects = vec![];
+        for (name, born) in [("Aldric", "10 March 1200"), ("Vex", "2 March 1200")] {
+            let id = db
+                .insert_subject(&Subject {
+                    name: name.into(),
+                    description: "".into(),
+                })
+                .unwrap();
+            let x = EntityRef::new(EntityKind::Subject, id);
+            let value = db.parse_field_value("Born", FieldType::Date, born).unwrap();
+            db.set_field(
+                x,
+                &Field {
+                    name: "Born".into(),
+                    value,
+                },
+            )
+            .unwrap();
+            subjects.push(x);
+        }
+        let (aldric, vex) = (subjects[0], subjects[1]);
+
+        // as text "10 March" would come first
+        let a = db
+            .field_sort_key(aldric, "Born", Audience::GameMaster)
+            .unwrap()
+            .unwrap();
+        let v = db
+            .field_sort_key(vex, "Born", Audience::GameMaster)
+            .unwrap()
+            .unwrap();
+        assert_eq!(v.compare(&a), Ordering::Less);
+
+        let (_, filters) = split_field_filters(
+            "born<1200-03-05",
+            &db.field_names(Audience::GameMaster).unwrap(),
+        );
+        assert!(db
+            .matches_field_filters(vex, &filters, Audience::GameMaster)
+            .unwrap());
+        assert!(!db
+            .matches_field_filters(aldric, &filters, Audience::GameMaster)
+            .unwrap());
+    }
+
+    #[test]
+    fn references_to_secret_entities_are_hidden_from_players() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut db = Schema::create_new(dir.path().join("test.db")).unwrap();
+        let mut subject = |name: &str| {
+            let id = db
+                .insert_subject(&Subject {
+                    name: name.into(),
+                    description: "".into(),
+                })
+                .unwrap();
+            EntityRef::new(EntityKind::Subject, id)
+        };
+        let aldric = subject("Aldric");
+        let vex = subject("Vex");
+        db.set_secret(vex, true).unwrap();
+        db.set_field(
+            aldric,
+            &Field {
+                name: "Patron".into(),
+                value: FieldValue::Reference(vex),
+            },
+        )
+        .unwrap();
+
+        assert_eq!(
+            db.visible_fields_of(aldric, Audience::GameMaster)
+                .unwrap()
+                .len(),
+            1
+        );
+        assert!(db
+            .visible_fields_of(aldric, Audience::Players)
+            .unwrap()
+            .is_empty());
+
+        let (_, filters) =
+            split_field_filters("patron=vex", &db.field_names(Audience::GameMaster).unwrap());
+        assert!(db
+            .matches_field_filters(aldric, &filters, Audience::GameMaster)
+            .unwrap());
+        assert!(!db
+            .matches_field_filters(aldric, &filters, Audience::Players)
+            .unwrap());
+        assert!(db
+            .field_sort_key(aldric, "Patron", Audience::Players)
+            .unwrap()
+            .is_none());
+
+        // the players don't learn the names of fields of secret entities, nobody those of
+        // trashed ones
+        db.set_field(
+            vex,
+            &Field {
+                name: "Plot".into(),
+                value: FieldValue::Text("Betray Aldric".into()),
+            },
+        )
+        .unwrap();
+        assert_eq!(
+            db.field_names(Audience::GameMaster).unwrap(),
+            vec!["Patron", "Plot"]
+        );
+        assert_eq!(db.field_names(Audience::Players).unwrap(), vec!["Patron"]);
+        db.trash(vex).unwrap();
+        assert_eq!(
+            db.field_names(Audience::GameMaster).unwrap(),
+            vec!["Patron"]
+        );
+    }
+}
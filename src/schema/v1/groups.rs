@@ -0,0 +1,299 @@
+use super::places::place_from_row;
+use super::{search_key, subject_from_row, EntityKind, EntityRef, Group, Place, Schema, Subject};
+use crate::schema::{Error, Result, WithId};
+
+impl Schema {
+    /// Inserts the group and returns its id
+    pub fn insert_group(&mut self, x: &Group) -> Result<i64> {
+        self.check_name_free(EntityKind::Group, &x.name, None)?;
+        let mut stmt = self.conn.prepare(
+            "insert into groups (name, name_key, description, parent_group) values (?, ?, ?, ?);",
+        )?;
+        stmt.bind((1, x.name.as_str()))?;
+        stmt.bind((2, search_key(&x.name).as_str()))?;
+        stmt.bind((3, x.description.as_str()))?;
+        stmt.bind((4, x.parent_group))?;
+        assert!(stmt.next()? == sqlite::State::Done);
+        self.last_insert_id()
+    }
+
+    /// Updates the group. Fails with [`Error::ParentCycle`] if the new parent is the group
+    /// itself or one of its sub groups.
+    pub fn update_group(&mut self, x: &WithId<Group>) -> Result<()> {
+        self.check_name_free(EntityKind::Group, &x.name, Some(x.id))?;
+        if let Some(parent) = x.parent_group {
+            if self.group_path(parent)?.iter().any(|g| g.id == x.id) {
+                return Err(Error::ParentCycle(x.name.clone()));
+            }
+        }
+        let mut stmt = self.conn.prepare(
+            "update groups set name = ?, name_key = ?, description = ?, parent_group = ?
+             where id == ?;",
+        )?;
+        stmt.bind((1, x.name.as_str()))?;
+        stmt.bind((2, search_key(&x.name).as_str()))?;
+        stmt.bind((3, x.description.as_str()))?;
+        stmt.bind((4, x.parent_group))?;
+        stmt.bind((5, x.id))?;
+        assert!(stmt.next()? == sqlite::State::Done);
+        Ok(())
+    }
+
+    pub fn get_group(&mut self, id: i64) -> Result<Option<WithId<Group>>> {
+        Ok(self
+            .query_groups("select * from groups where id == ?;", Some(id))?
+            .into_iter()
+            .next())
+    }
+
+    pub fn get_group_by_name(&mut self, name: &str) -> Result<Option<WithId<Group>>> {
+        self.conn
+            .prepare("select * from groups where name_key == ? and deleted_at is null;")?
+            .into_iter()
+            .bind((1, search_key(name).as_str()))?
+            .map(|r| Ok(group_from_row(&r?)))
+            .next()
+            .transpose()
+    }
+
+    pub fn list_groups(&mut self) -> Result<Vec<WithId<Group>>> {
+        self.query_groups(
+            "select * from groups where deleted_at is null order by name;",
+            None,
+        )
+    }
+
+    /// The direct sub groups of a group
+    pub fn child_groups(&mut self, id: i64) -> Result<Vec<WithId<Group>>> {
+        self.query_groups(
+            "select * from groups where parent_group == ? and deleted_at is null order by name;",
+            Some(id),
+        )
+    }
+
+    /// The group and all the groups it is part of, starting at the top level
+    pub fn group_path(&mut self, id: i64) -> Result<Vec<WithId<Group>>> {
+        let query = "
+            with recursive anc(id, depth) as (
+                select ?, 0
+                union
+                select groups.parent_group, anc.depth + 1
+                from groups join anc on groups.id == anc.id
+                where groups.parent_group is not null
+            )
+            select groups.* from groups join anc on groups.id == anc.id
+            order by anc.depth desc;";
+        self.query_groups(query, Some(id))
+    }
+
+    /// Deletes the group. Its sub groups are moved up to the deleted group's parent, and all
+    /// memberships, territories and other links are removed.
+    pub fn delete_group(&mut self, id: i64) -> Result<()> {
+        let Some(group) = self.get_group(id)? else {
+            return Ok(());
+        };
+        self.in_transaction(|db| {
+            let mut stmt = db
+                .conn
+                .prepare("update groups set parent_group = ? where parent_group == ?;")?;
+            stmt.bind((1, group.parent_group))?;
+            stmt.bind((2, id))?;
+            assert!(stmt.next()? == sqlite::State::Done);
+            drop(stmt);
+            db.delete_entity_row(EntityRef::new(EntityKind::Group, id))
+        })
+    }
+
+    pub fn add_group_member(&mut self, group: i64, subject: i64) -> Result<()> {
+        self.insert_mapping("mapping_subjects_groups", subject, group)
+    }
+
+    pub fn remove_group_member(&mut self, group: i64, subject: i64) -> Result<()> {
+        self.delete_mapping("mapping_subjects_groups", subject, group)
+    }
+
+    /// The subjects that are direct members of the group. A `[[wiki link]]` to the group in a
+    /// description doesn't make a member.
+    pub fn group_members(&mut self, group: i64) -> Result<Vec<WithId<Subject>>> {
+        self.conn
+            .prepare(
+                "select subjects.* from subjects
+                 join mapping_subjects_groups m on m.kfrom == subjects.id
+                 where m.kto == ? and m.wiki == 0 and subjects.deleted_at is null
+                 order by subjects.name;",
+            )?
+            .into_iter()
+            .bind((1, group))?
+            .map(|r| Ok(subject_from_row(&r?)))
+            .collect()
+    }
+
+    /// The groups the subject is a member of. If `inherited` is true, the groups those groups
+    /// are part of are included as well, so a member of the "Thieves guild, Northern chapter"
+    /// is listed as a member of the "Thieves guild" too.
+    pub fn subject_groups(&mut self, subject: i64, inherited: bool) -> Result<Vec<WithId<Group>>> {
+        let query = if inherited {
+            "with recursive anc(id) as (
+                select kto from mapping_subjects_groups where kfrom == ? and wiki == 0
+                union
+                select groups.parent_group from groups join anc on groups.id == anc.id
+                where groups.parent_group is not null
+            )
+            select groups.* from groups join anc on groups.id == anc.id
+            where groups.deleted_at is null order by groups.name;"
+        } else {
+            "select groups.* from groups
+             join mapping_subjects_groups m on m.kto == groups.id
+             where m.kfrom == ? and m.wiki == 0 and groups.deleted_at is null
+             order by groups.name;"
+        };
+        self.query_groups(query, Some(subject))
+    }
+
+    pub fn add_group_territory(&mut self, group: i64, place: i64) -> Result<()> {
+        self.insert_mapping("mapping_places_groups", place, group)
+    }
+
+    pub fn remove_group_territory(&mut self, group: i64, place: i64) -> Result<()> {
+        self.delete_mapping("mapping_places_groups", place, group)
+    }
+
+    /// The places the group controls or is based in, without places that only mention it
+    pub fn group_territories(&mut self, group: i64) -> Result<Vec<WithId<Place>>> {
+        self.conn
+            .prepare(
+                "select places.* from places
+                 join mapping_places_groups m on m.kfrom == places.id
+                 where m.kto == ? and m.wiki == 0 and places.deleted_at is null
+                 order by places.name;",
+            )?
+            .into_iter()
+            .bind((1, group))?
+            .map(|r| Ok(place_from_row(&r?)))
+            .collect()
+    }
+
+    fn query_groups(&mut self, query: &str, arg: Option<i64>) -> Result<Vec<WithId<Group>>> {
+        let mut cursor = self.conn.prepare(query)?.into_iter();
+        if let Some(arg) = arg {
+            cursor = cursor.bind((1, arg))?;
+        }
+        cursor.map(|r| Ok(group_from_row(&r?))).collect()
+    }
+}
+
+pub(super) fn group_from_row(r: &sqlite::Row) -> WithId<Group> {
+    WithId {
+        t: Group {
+            name: r.read::<&str, _>("name").to_string(),
+            description: r.read::<&str, _>("description").to_string(),
+            parent_group: r.read::<Option<i64>, _>("parent_group"),
+        },
+        id: r.read::<i64, _>("id"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn group(db: &mut Schema, name: &str, parent_group: Option<i64>) -> i64 {
+        db.insert_group(&Group {
+            name: name.into(),
+            description: "".into(),
+            parent_group,
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn groups_can_not_be_moved_below_themselves() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut db = Schema::create_new(dir.path().join("test.db")).unwrap();
+        let guild = group(&mut db, "Guild", None);
+        let cell = group(&mut db, "Cell", Some(guild));
+        let path: Vec<_> = db
+            .group_path(cell)
+            .unwrap()
+            .into_iter()
+            .map(|g| g.id)
+            .collect();
+        assert_eq!(path, vec![guild, cell]);
+
+        for parent in [guild, cell] {
+            let mut moved = db.get_group(guild).unwrap().unwrap();
+            moved.t.parent_group = Some(parent);
+            assert!(matches!(
+                db.update_group(&moved),
+                Err(Error::ParentCycle(_))
+            ));
+        }
+        assert_eq!(db.get_group(guild).unwrap().unwrap().t.parent_group, None);
+
+        let mut moved = db.get_group(cell).unwrap().unwrap();
+        moved.t.parent_group = None;
+        db.update_group(&moved).unwrap();
+        assert!(db.child_groups(guild).unwrap().is_empty());
+    }
+
+    #[test]
+    fn members_and_territories_are_managed_and_inherited() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut db = Schema::create_new(dir.path().join("test.db")).unwrap();
+        let guild = group(&mut db, "Guild", None);
+        let chapter = group(&mut db, "Northern chapter", Some(guild));
+        let subject = |db: &mut Schema, name: &str| {
+            db.insert_subject(&Subject {
+                name: name.into(),
+                description: "".into(),
+            })
+            .unwrap()
+        };
+        let mira = subject(&mut db, "Mira");
+        let vex = subject(&mut db, "Vex");
+        let docks = db
+            .insert_place(&Place {
+                name: "Docks".into(),
+                description: "".into(),
+                parent_place: None,
+            })
+            .unwrap();
+        let ids = |groups: Vec<WithId<Group>>| groups.into_iter().map(|g| g.id).collect::<Vec<_>>();
+
+        db.add_group_member(chapter, mira).unwrap();
+        // adding twice does nothing
+        db.add_group_member(chapter, mira).unwrap();
+        assert_eq!(db.group_members(chapter).unwrap().len(), 1);
+        assert!(db.group_members(guild).unwrap().is_empty());
+        assert_eq!(ids(db.subject_groups(mira, false).unwrap()), vec![chapter]);
+        assert_eq!(
+            ids(db.subject_groups(mira, true).unwrap()),
+            vec![guild, chapter]
+        );
+
+        db.add_group_territory(guild, docks).unwrap();
+        assert_eq!(db.group_territories(guild).unwrap()[0].id, docks);
+        assert!(db.group_territories(chapter).unwrap().is_empty());
+
+        // mentioning a group in a description makes no member or territory
+        db.sync_wiki_links(
+            EntityRef::new(EntityKind::Subject, vex),
+            &[(EntityRef::new(EntityKind::Group, guild), false)],
+        )
+        .unwrap();
+        db.sync_wiki_links(
+            EntityRef::new(EntityKind::Place, docks),
+            &[(EntityRef::new(EntityKind::Group, chapter), false)],
+        )
+        .unwrap();
+        assert!(db.group_members(guild).unwrap().is_empty());
+        assert!(db.subject_groups(vex, true).unwrap().is_empty());
+        assert!(db.group_territories(chapter).unwrap().is_empty());
+
+        db.remove_group_member(chapter, mira).unwrap();
+        assert!(db.group_members(chapter).unwrap().is_empty());
+        assert!(db.subject_groups(mira, true).unwrap().is_empty());
+        db.remove_group_territory(guild, docks).unwrap();
+        assert!(db.group_territories(guild).unwrap().is_empty());
+    }
+}
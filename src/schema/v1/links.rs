@@ -0,0 +1,340 @@
+use super::Schema;
+use crate::schema::{Error, Result};
+
+use std::fmt::Display;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum EntityKind {
+    Subject,
+    Place,
+    Group,
+    Event,
+    Tag,
+}
+
+impl EntityKind {
+    pub const ALL: [EntityKind; 5] = [
+        EntityKind::Subject,
+        EntityKind::Place,
+        EntityKind::Group,
+        EntityKind::Event,
+        EntityKind::Tag,
+    ];
+
+    pub fn table(self) -> &'static str {
+        match self {
+            EntityKind::Subject => "subjects",
+            EntityKind::Place => "places",
+            EntityKind::Group => "groups",
+            EntityKind::Event => "events",
+            EntityKind::Tag => "tags",
+        }
+    }
+
+    pub fn from_table(table: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|k| k.table() == table)
+    }
+
+    /// The column that is shown to the user to identify an entry
+    pub(super) fn label_column(self) -> &'static str {
+        match self {
+            EntityKind::Event => "refered_date",
+            _ => "name",
+        }
+    }
+}
+
+impl Display for EntityKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let s = match self {
+            EntityKind::Subject => "Subject",
+            EntityKind::Place => "Place",
+            EntityKind::Group => "Group",
+            EntityKind::Event => "Event",
+            EntityKind::Tag => "Tag",
+        };
+        write!(f, "{s}")
+    }
+}
+
+/// Identifies a row in one of the entity tables
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct EntityRef {
+    pub kind: EntityKind,
+    pub id: i64,
+}
+
+impl EntityRef {
+    pub fn new(kind: EntityKind, id: i64) -> Self {
+        Self { kind, id }
+    }
+}
+
+/// An entity on the other end of a link, together with its display label
+#[derive(Clone, Debug, PartialEq)]
+pub struct Linked {
+    pub entity: EntityRef,
+    pub label: String,
+    /// true if the link or the entity is hidden from the players
+    pub secret: bool,
+}
+
+/// All pairs of entity kinds that can be linked. A link always points from the first kind to
+/// the second, and is stored in the table `mapping_{from}_{to}`.
+pub const MAPPINGS: [(EntityKind, EntityKind); 11] = [
+    (EntityKind::Subject, EntityKind::Subject),
+    (EntityKind::Subject, EntityKind::Group),
+    (EntityKind::Subject, EntityKind::Place),
+    (EntityKind::Subject, EntityKind::Tag),
+    (EntityKind::Event, EntityKind::Subject),
+    (EntityKind::Event, EntityKind::Group),
+    (EntityKind::Event, EntityKind::Place),
+    (EntityKind::Event, EntityKind::Tag),
+    (EntityKind::Place, EntityKind::Group),
+    (EntityKind::Place, EntityKind::Tag),
+    (EntityKind::Group, EntityKind::Tag),
+];
+
+pub fn mapping_table(from: EntityKind, to: EntityKind) -> Option<String> {
+    MAPPINGS
+        .contains(&(from, to))
+        .then(|| format!("mapping_{}_{}", from.table(), to.table()))
+}
+
+impl Schema {
+    /// Links `from` to `to`. Linking two entities that are already linked does nothing.
+    /// Fails with [`Error::InvalidLink`] if there is no mapping table for the two kinds.
+    pub fn link(&mut self, from: EntityRef, to: EntityRef) -> Result<()> {
+        let table = checked_mapping_table(from.kind, to.kind)?;
+        self.insert_mapping(&table, from.id, to.id)
+    }
+
+    /// Removes the link from `from` to `to`. Labelled relationships between two subjects are
+    /// no plain links and stay, see [`Schema::delete_relationship`].
+    pub fn unlink(&mut self, from: EntityRef, to: EntityRef) -> Result<()> {
+        let table = checked_mapping_table(from.kind, to.kind)?;
+        if (from.kind, to.kind) != (EntityKind::Subject, EntityKind::Subject) {
+            return self.delete_mapping(&table, from.id, to.id);
+        }
+        let mut stmt = self.conn.prepare(
+            "delete from mapping_subjects_subjects where kfrom == ? and kto == ? and label == '';",
+        )?;
+        stmt.bind((1, from.id))?;
+        stmt.bind((2, to.id))?;
+        assert!(stmt.next()? == sqlite::State::Done);
+        Ok(())
+    }
+
+    pub fn is_linked(&mut self, from: EntityRef, to: EntityRef) -> Result<bool> {
+        let table = checked_mapping_table(from.kind, to.kind)?;
+        let mut stmt = self.conn.prepare(format!(
+            "select count(*) from {table} where kfrom == ? and kto == ?;"
+        ))?;
+        stmt.bind((1, from.id))?;
+        stmt.bind((2, to.id))?;
+        assert!(stmt.next()? == sqlite::State::Row);
+        Ok(stmt.read::<i64, _>(0)? > 0)
+    }
+
+    /// All entities `x` links to, sorted by kind and label
+    pub fn outgoing_links(&mut self, x: EntityRef) -> Result<Vec<Linked>> {
+        let mut res = vec![];
+        for (from, to) in MAPPINGS.iter().filter(|(from, _)| *from == x.kind) {
+            res.extend(self.linked(*from, *to, x.id, true)?);
+        }
+        res.sort_by(|a, b| (a.entity.kind, &a.label).cmp(&(b.entity.kind, &b.label)));
+        Ok(res)
+    }
+
+    /// All entities that link to `x`, sorted by kind and label
+    pub fn incoming_links(&mut self, x: EntityRef) -> Result<Vec<Linked>> {
+        let mut res = vec![];
+        for (from, to) in MAPPINGS.iter().filter(|(_, to)| *to == x.kind) {
+            res.extend(self.linked(*from, *to, x.id, false)?);
+        }
+        res.sort_by(|a, b| (a.entity.kind, &a.label).cmp(&(b.entity.kind, &b.label)));
+        Ok(res)
+    }
+
+    /// Replaces the links that were created from wiki links in the description of `x` by links
+    /// to `targets`, each with a flag that tells whether the link is secret. Links that were
+    /// created by hand are left alone. Targets for which there is no mapping table from `x`'s
+    /// kind are skipped.
+    pub fn sync_wiki_links(&mut self, x: EntityRef, targets: &[(EntityRef, bool)]) -> Result<()> {
+        for (from, to) in MAPPINGS.iter().filter(|(from, _)| *from == x.kind) {
+            let table = mapping_table(*from, *to).unwrap();
+            let mut stmt = self.conn.prepare(format!(
+                "delete from {table} where kfrom == ? and wiki == 1;"
+            ))?;
+            stmt.bind((1, x.id))?;
+            assert!(stmt.next()? == sqlite::State::Done);
+
+            let mut stmt = self.conn.prepare(format!(
+                "insert or ignore into {table} (kfrom, kto, wiki, secret) values (?, ?, 1, ?);"
+            ))?;
+            for (target, secret) in targets.iter().filter(|(t, _)| t.kind == *to && *t != x) {
+                stmt.reset()?;
+                stmt.bind((1, x.id))?;
+                stmt.bind((2, target.id))?;
+                stmt.bind((3, *secret as i64))?;
+                assert!(stmt.next()? == sqlite::State::Done);
+            }
+        }
+        Ok(())
+    }
+
+    /// The label of `x` as shown to the user, None if there is no such entity or it is in the
+    /// trash
+    pub fn entity_label(&mut self, x: EntityRef) -> Result<Option<String>> {
+        let mut stmt = self.conn.prepare(format!(
+            "select {} from {} where id == ? and deleted_at is null;",
+            x.kind.label_column(),
+            x.kind.table()
+        ))?;
+        stmt.bind((1, x.id))?;
+        if stmt.next()? != sqlite::State::Row {
+            return Ok(None);
+        }
+        Ok(Some(stmt.read::<Option<String>, _>(0)?.unwrap_or_default()))
+    }
+
+    /// Removes every link from or to `x`
+    pub(super) fn unlink_all(&mut self, x: EntityRef) -> Result<()> {
+        for (from, to) in MAPPINGS {
+            let table = mapping_table(from, to).unwrap();
+            for (kind, column) in [(from, "kfrom"), (to, "kto")] {
+                if kind == x.kind {
+                    let mut stmt = self
+                        .conn
+                        .prepare(format!("delete from {table} where {column} == ?;"))?;
+                    stmt.bind((1, x.id))?;
+                    assert!(stmt.next()? == sqlite::State::Done);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Queries one mapping table. If `outgoing` is true, `id` is matched against `kfrom` and
+    /// the entities at `kto` are returned, otherwise the other way around.
+    fn linked(
+        &mut self,
+        from: EntityKind,
+        to: EntityKind,
+        id: i64,
+        outgoing: bool,
+    ) -> Result<Vec<Linked>> {
+        let table = mapping_table(from, to).unwrap();
+        let (other, own_col, other_col) = if outgoing {
+            (to, "kfrom", "kto")
+        } else {
+            (from, "kto", "kfrom")
+        };
+        // tags can't be secret, only the links to them
+        let secret = if other == EntityKind::Tag {
+            "m.secret"
+        } else {
+            "m.secret or e.secret"
+        };
+        let query = format!(
+            "select e.id as id, e.{label} as label, {secret} as secret from {other_table} e
+             join {table} m on m.{other_col} == e.id
+             where m.{own_col} == ? and e.deleted_at is null;",
+            label = other.label_column(),
+            other_table = other.table(),
+        );
+        self.conn
+            .prepare(query)?
+            .into_iter()
+            .bind((1, id))?
+            .map(|r| {
+                let r = r?;
+                Ok(Linked {
+                    entity: EntityRef::new(other, r.read::<i64, _>("id")),
+                    label: r.read::<Option<&str>, _>("label").unwrap_or("").to_string(),
+                    secret: r.read::<i64, _>("secret") != 0,
+                })
+            })
+            .collect()
+    }
+}
+
+pub(super) fn checked_mapping_table(from: EntityKind, to: EntityKind) -> Result<String> {
+    mapping_table(from, to).ok_or_else(|| Error::InvalidLink(from.to_string(), to.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::schema::v1::{Place, Relationship, Subject};
+
+    fn subject(db: &mut Schema, name: &str) -> EntityRef {
+        let id = db
+            .insert_subject(&Subject {
+                name: name.into(),
+                description: "".into(),
+            })
+            .unwrap();
+        EntityRef::new(EntityKind::Subject, id)
+    }
+
+    #[test]
+    fn links_are_made_listed_and_removed() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut db = Schema::create_new(dir.path().join("test.db")).unwrap();
+        let mira = subject(&mut db, "Mira");
+        let vex = subject(&mut db, "Vex");
+        let inn = db
+            .insert_place(&Place {
+                name: "Inn".into(),
+                description: "".into(),
+                parent_place: None,
+            })
+            .unwrap();
+        let inn = EntityRef::new(EntityKind::Place, inn);
+
+        db.link(mira, vex).unwrap();
+        // linking twice makes no second link
+        db.link(mira, vex).unwrap();
+        db.link(mira, inn).unwrap();
+        assert!(db.is_linked(mira, vex).unwrap());
+        assert!(!db.is_linked(vex, mira).unwrap());
+        let entities = |links: Vec<Linked>| links.into_iter().map(|l| l.entity).collect::<Vec<_>>();
+        assert_eq!(entities(db.outgoing_links(mira).unwrap()), vec![vex, inn]);
+        assert_eq!(entities(db.incoming_links(vex).unwrap()), vec![mira]);
+        assert_eq!(entities(db.incoming_links(inn).unwrap()), vec![mira]);
+        assert!(db.outgoing_links(vex).unwrap().is_empty());
+
+        // places can't link to subjects
+        assert!(matches!(db.link(inn, mira), Err(Error::InvalidLink(..))));
+
+        db.unlink(mira, inn).unwrap();
+        assert!(db.incoming_links(inn).unwrap().is_empty());
+        assert_eq!(entities(db.outgoing_links(mira).unwrap()), vec![vex]);
+    }
+
+    #[test]
+    fn unlink_keeps_relationships() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut db = Schema::create_new(dir.path().join("test.db")).unwrap();
+        let mira = subject(&mut db, "Mira");
+        let vex = subject(&mut db, "Vex");
+        db.link(mira, vex).unwrap();
+        db.add_relationship(&Relationship {
+            from: mira.id,
+            to: vex.id,
+            label: "owes money to".into(),
+            inverse_label: None,
+            note: "".into(),
+            valid_from: None,
+            valid_to: None,
+            secret: false,
+        })
+        .unwrap();
+
+        db.unlink(mira, vex).unwrap();
+        let rels = db.relationships_of(mira.id).unwrap();
+        assert_eq!(rels.len(), 1);
+        assert_eq!(rels[0].rel.label, "owes money to");
+    }
+}
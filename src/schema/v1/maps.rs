@@ -0,0 +1,225 @@
+use super::{EntityKind, EntityRef, Schema};
+use crate::schema::{Error, Result, WithId};
+
+/// A pin on the map of a place that points to a subject or to a place inside it. `x` and `y`
+/// are relative to the map's size, `(0, 0)` is the top left corner and `(1, 1)` the bottom
+/// right one.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct MapPin {
+    pub target: EntityRef,
+    pub x: f64,
+    pub y: f64,
+}
+
+impl Schema {
+    /// Uses the image as map of the place, replacing the previous map. Pins keep their relative
+    /// positions. Returns the id of the stored image.
+    pub fn set_place_map(&mut self, place: i64, data: &[u8], file_name: &str) -> Result<i64> {
+        self.in_transaction(|db| db.set_place_map_in_transaction(place, data, file_name))
+    }
+
+    fn set_place_map_in_transaction(
+        &mut self,
+        place: i64,
+        data: &[u8],
+        file_name: &str,
+    ) -> Result<i64> {
+        let id = self.store_image(data, file_name)?;
+        let mut stmt = self.conn.prepare(
+            "insert or replace into place_maps (place_id, attachment_id) values (?, ?);",
+        )?;
+        stmt.bind((1, place))?;
+        stmt.bind((2, id))?;
+        assert!(stmt.next()? == sqlite::State::Done);
+        self.delete_unused_images()?;
+        Ok(id)
+    }
+
+    /// Id of the stored image that is the map of the place
+    pub fn place_map(&mut self, place: i64) -> Result<Option<i64>> {
+        let mut stmt = self
+            .conn
+            .prepare("select attachment_id from place_maps where place_id == ?;")?;
+        stmt.bind((1, place))?;
+        if stmt.next()? != sqlite::State::Row {
+            return Ok(None);
+        }
+        Ok(Some(stmt.read::<i64, _>(0)?))
+    }
+
+    /// Removes the map of the place together with all its pins
+    pub fn remove_place_map(&mut self, place: i64) -> Result<()> {
+        for query in [
+            "delete from map_pins where place_id == ?;",
+            "delete from place_maps where place_id == ?;",
+        ] {
+            let mut stmt = self.conn.prepare(query)?;
+            stmt.bind((1, place))?;
+            assert!(stmt.next()? == sqlite::State::Done);
+        }
+        self.delete_unused_images()
+    }
+
+    /// The pins on the map of the place. Pins pointing to trashed entities are left out.
+    pub fn map_pins(&mut self, place: i64) -> Result<Vec<WithId<MapPin>>> {
+        let pins = self
+            .conn
+            .prepare("select * from map_pins where place_id == ? order by id;")?
+            .into_iter()
+            .bind((1, place))?
+            .filter_map(|r| {
+                let r = match r {
+                    Ok(r) => r,
+                    Err(e) => return Some(Err(e.into())),
+                };
+                let kind = EntityKind::from_table(r.read::<&str, _>("target_kind"))?;
+                Some(Ok(WithId {
+                    t: MapPin {
+                        target: EntityRef::new(kind, r.read::<i64, _>("target_id")),
+                        x: r.read::<f64, _>("x"),
+                        y: r.read::<f64, _>("y"),
+                    },
+                    id: r.read::<i64, _>("id"),
+                }))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        let mut res = vec![];
+        for pin in pins {
+            if self.entity_label(pin.target)?.is_some() {
+                res.push(pin);
+            }
+        }
+        Ok(res)
+    }
+
+    /// Pins `pin.target` on the map of the place and returns the id of the pin. Fails with
+    /// [`Error::InvalidPin`] unless the target is a subject or a place below `place`.
+    /// Positions outside of the map are moved to its edge.
+    pub fn add_map_pin(&mut self, place: i64, pin: &MapPin) -> Result<i64> {
+        self.check_pin_target(place, pin.target)?;
+        let mut stmt = self.conn.prepare(
+            "insert into map_pins (place_id, target_kind, target_id, x, y) values (?, ?, ?, ?, ?);",
+        )?;
+        stmt.bind((1, place))?;
+        stmt.bind((2, pin.target.kind.table()))?;
+        stmt.bind((3, pin.target.id))?;
+        stmt.bind((4, pin.x.clamp(0.0, 1.0)))?;
+        stmt.bind((5, pin.y.clamp(0.0, 1.0)))?;
+        assert!(stmt.next()? == sqlite::State::Done);
+        self.last_insert_id()
+    }
+
+    pub fn move_map_pin(&mut self, id: i64, x: f64, y: f64) -> Result<()> {
+        let mut stmt = self
+            .conn
+            .prepare("update map_pins set x = ?, y = ? where id == ?;")?;
+        stmt.bind((1, x.clamp(0.0, 1.0)))?;
+        stmt.bind((2, y.clamp(0.0, 1.0)))?;
+        stmt.bind((3, id))?;
+        assert!(stmt.next()? == sqlite::State::Done);
+        Ok(())
+    }
+
+    pub fn delete_map_pin(&mut self, id: i64) -> Result<()> {
+        let mut stmt = self.conn.prepare("delete from map_pins where id == ?;")?;
+        stmt.bind((1, id))?;
+        assert!(stmt.next()? == sqlite::State::Done);
+        Ok(())
+    }
+
+    /// Removes the pins pointing to `x`, and if it is a place, its map
+    pub(super) fn delete_map_pins(&mut self, x: EntityRef) -> Result<()> {
+        let mut stmt = self
+            .conn
+            .prepare("delete from map_pins where target_kind == ? and target_id == ?;")?;
+        stmt.bind((1, x.kind.table()))?;
+        stmt.bind((2, x.id))?;
+        assert!(stmt.next()? == sqlite::State::Done);
+        if x.kind == EntityKind::Place {
+            self.remove_place_map(x.id)?;
+        }
+        Ok(())
+    }
+
+    fn check_pin_target(&mut self, place: i64, target: EntityRef) -> Result<()> {
+        let valid = match target.kind {
+            EntityKind::Subject => true,
+            EntityKind::Place => self
+                .place_subtree(place)?
+                .iter()
+                .any(|(_, p)| p.id == target.id),
+            _ => false,
+        };
+        if valid {
+            return Ok(());
+        }
+        let name = self.get_place(place)?.map(|p| p.t.name).unwrap_or_default();
+        Err(Error::InvalidPin(name))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::schema::v1::{Place, Subject};
+    use image::{DynamicImage, ImageOutputFormat, RgbImage};
+    use std::io::Cursor;
+
+    fn place(db: &mut Schema, name: &str, parent_place: Option<i64>) -> i64 {
+        db.insert_place(&Place {
+            name: name.into(),
+            description: "".into(),
+            parent_place,
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn pins_point_inside_the_place() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut db = Schema::create_new(dir.path().join("test.db")).unwrap();
+        let city = place(&mut db, "City", None);
+        let inn = place(&mut db, "Inn", Some(city));
+        let cellar = place(&mut db, "Cellar", Some(inn));
+        let elsewhere = place(&mut db, "Elsewhere", None);
+        let mira = db
+            .insert_subject(&Subject {
+                name: "Mira".into(),
+                description: "".into(),
+            })
+            .unwrap();
+
+        let mut png = vec![];
+        DynamicImage::ImageRgb8(RgbImage::new(40, 30))
+            .write_to(&mut Cursor::new(&mut png), ImageOutputFormat::Png)
+            .unwrap();
+        let map = db.set_place_map(city, &png, "city.png").unwrap();
+        assert_eq!(db.place_map(city).unwrap(), Some(map));
+
+        let pin = |kind, id, x, y| MapPin {
+            target: EntityRef::new(kind, id),
+            x,
+            y,
+        };
+        db.add_map_pin(city, &pin(EntityKind::Place, cellar, 0.5, 0.5))
+            .unwrap();
+        let mira_pin = db
+            .add_map_pin(city, &pin(EntityKind::Subject, mira, 1.5, -1.0))
+            .unwrap();
+        assert!(db
+            .add_map_pin(city, &pin(EntityKind::Place, elsewhere, 0.1, 0.1))
+            .is_err());
+
+        let pins = db.map_pins(city).unwrap();
+        assert_eq!(pins.len(), 2);
+        assert_eq!((pins[1].x, pins[1].y), (1.0, 0.0));
+        db.move_map_pin(mira_pin, 0.25, 0.75).unwrap();
+        assert_eq!(db.map_pins(city).unwrap()[1].y, 0.75);
+
+        db.delete_place(cellar).unwrap();
+        assert_eq!(db.map_pins(city).unwrap().len(), 1);
+        db.remove_place_map(city).unwrap();
+        assert!(db.map_pins(city).unwrap().is_empty());
+        assert!(db.thumbnail(map).unwrap().is_none());
+    }
+}
@@ -0,0 +1,257 @@
+use super::{search_key, EntityKind, EntityRef, Schema};
+use crate::schema::{Error, Result};
+use crate::wiki::parse_links;
+
+impl Schema {
+    /// Fails with [`Error::NameTaken`] if another entity of the same kind already has this
+    /// name, ignoring case and diacritics. `except` is the id of the entity that is about to get
+    /// the name, so it doesn't collide with itself.
+    pub fn check_name_free(
+        &mut self,
+        kind: EntityKind,
+        name: &str,
+        except: Option<i64>,
+    ) -> Result<()> {
+        let mut stmt = self.conn.prepare(format!(
+            "select count(*) from {} where name_key == ? and id != ? and deleted_at is null;",
+            kind.table()
+        ))?;
+        stmt.bind((1, search_key(name).as_str()))?;
+        stmt.bind((2, except.unwrap_or(-1)))?;
+        assert!(stmt.next()? == sqlite::State::Row);
+        if stmt.read::<i64, _>(0)? > 0 {
+            Err(Error::NameTaken(kind.to_string(), name.to_string()))
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Finds the subject, place or group with the given name, ignoring case and diacritics.
+    /// Since names are only unique per kind, subjects win over places, and places over groups.
+    pub fn resolve_name(&mut self, name: &str) -> Result<Option<EntityRef>> {
+        for kind in [EntityKind::Subject, EntityKind::Place, EntityKind::Group] {
+            let mut stmt = self.conn.prepare(format!(
+                "select id from {} where name_key == ? and deleted_at is null;",
+                kind.table()
+            ))?;
+            stmt.bind((1, search_key(name).as_str()))?;
+            if stmt.next()? == sqlite::State::Row {
+                return Ok(Some(EntityRef::new(kind, stmt.read::<i64, _>(0)?)));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Renames the entity, keeping its id. If `propagate` is true, wiki links to the old name in
+    /// the descriptions of all other entities are pointed to the new name, see
+    /// [`rewrite_mentions`], and each changed description gets a new revision. The old name in
+    /// plain text is left alone, it may well mean someone or something else. Returns the number
+    /// of changed descriptions. Fails with [`Error::EmptyName`] if the new name is blank.
+    pub fn rename(&mut self, x: EntityRef, new_name: &str, propagate: bool) -> Result<usize> {
+        assert!(x.kind != EntityKind::Event, "Events have no name");
+        if new_name.trim().is_empty() {
+            return Err(Error::EmptyName(x.kind.to_string()));
+        }
+        self.check_name_free(x.kind, new_name, Some(x.id))?;
+        let old_name = {
+            let mut stmt = self.conn.prepare(format!(
+                "select name from {} where id == ?;",
+                x.kind.table()
+            ))?;
+            stmt.bind((1, x.id))?;
+            if stmt.next()? != sqlite::State::Row {
+                return Ok(0);
+            }
+            stmt.read::<String, _>(0)?
+        };
+
+        self.in_transaction(|db| db.rename_in_transaction(x, &old_name, new_name, propagate))
+    }
+
+    fn rename_in_transaction(
+        &mut self,
+        x: EntityRef,
+        old_name: &str,
+        new_name: &str,
+        propagate: bool,
+    ) -> Result<usize> {
+        // wiki links with the old name may belong to an entity of another kind
+        let owns_links = self.resolve_name(old_name)? == Some(x);
+        let mut stmt = self.conn.prepare(format!(
+            "update {} set name = ?, name_key = ? where id == ?;",
+            x.kind.table()
+        ))?;
+        stmt.bind((1, new_name))?;
+        stmt.bind((2, search_key(new_name).as_str()))?;
+        stmt.bind((3, x.id))?;
+        assert!(stmt.next()? == sqlite::State::Done);
+
+        if !propagate || !owns_links || old_name == new_name {
+            return Ok(0);
+        }
+        let mut changed = 0;
+        for kind in [
+            EntityKind::Subject,
+            EntityKind::Place,
+            EntityKind::Group,
+            EntityKind::Event,
+        ] {
+            for (id, text) in self.descriptions_with_links(kind)? {
+                let entity = EntityRef::new(kind, id);
+                if entity == x {
+                    continue;
+                }
+                let (new_text, n) = rewrite_mentions(&text, old_name, new_name);
+                if n > 0 {
+                    self.set_description(entity, &new_text)?;
+                    self.record_edit(entity, &text, &new_text)?;
+                    changed += 1;
+                }
+            }
+        }
+        Ok(changed)
+    }
+
+    /// Id and description of all entities of a kind whose description may contain a wiki link
+    fn descriptions_with_links(&mut self, kind: EntityKind) -> Result<Vec<(i64, String)>> {
+        self.conn
+            .prepare(format!(
+                "select id, description from {} where instr(description, '[[') > 0;",
+                kind.table()
+            ))?
+            .into_iter()
+            .map(|r| {
+                let r = r?;
+                Ok((
+                    r.read::<i64, _>("id"),
+                    r.read::<&str, _>("description").to_string(),
+                ))
+            })
+            .collect()
+    }
+}
+
+/// Replaces all occurrences of `old` in `text`, that are not part of a longer word, by `new`.
+/// Returns the new text and the number of replacements.
+pub fn replace_mentions(text: &str, old: &str, new: &str) -> (String, usize) {
+    let is_word_char = |c: char| c.is_alphanumeric() || c == '_';
+    let mut res = String::with_capacity(text.len());
+    let mut count = 0;
+    let mut rest = text;
+    while let Some(pos) = rest.find(old) {
+        let before = rest[..pos].chars().next_back().or(res.chars().next_back());
+        let after = rest[pos + old.len()..].chars().next();
+        let starts_word = before.map_or(true, |c| !is_word_char(c));
+        let ends_word = after.map_or(true, |c| !is_word_char(c));
+        res.push_str(&rest[..pos]);
+        if starts_word && ends_word {
+            res.push_str(new);
+            count += 1;
+        } else {
+            res.push_str(old);
+        }
+        rest = &rest[pos + old.len()..];
+    }
+    res.push_str(rest);
+    (res, count)
+}
+
+/// Points the wiki links in `text` whose target is `old`, ignoring case and diacritics, to
+/// `new`, and replaces mentions of `old` in their label with [`replace_mentions`]. Everything
+/// outside of these links is left as it is. Returns the new text and the number of changed
+/// links.
+pub fn rewrite_mentions(text: &str, old: &str, new: &str) -> (String, usize) {
+    let key = search_key(old);
+    let mut res = String::with_capacity(text.len());
+    let mut count = 0;
+    let mut last = 0;
+    for link in parse_links(text) {
+        res.push_str(&text[last..link.range.start]);
+        if search_key(&link.target) == key {
+            res.push_str("[[");
+            res.push_str(new);
+            if let Some(label) = &link.label {
+                res.push('|');
+                res.push_str(&replace_mentions(label, old, new).0);
+            }
+            res.push_str("]]");
+            count += 1;
+        } else {
+            res.push_str(&text[link.range.clone()]);
+        }
+        last = link.range.end;
+    }
+    res.push_str(&text[last..]);
+    (res, count)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn replace_mentions_only_replaces_whole_words() {
+        let (text, n) = replace_mentions("Bob met Bobby and [[Bob]]. Bob_2 Bob", "Bob", "Robert");
+        assert_eq!(text, "Robert met Bobby and [[Robert]]. Bob_2 Robert");
+        assert_eq!(n, 3);
+    }
+
+    #[test]
+    fn rewrite_mentions_follows_wiki_links() {
+        // plain text may be about another Bob, or the word "will" for a Will
+        let text = "Bob and [[bob]], [[Böb|Bob the brave]], [[Bob's Tavern]].";
+        assert_eq!(
+            rewrite_mentions(text, "Bob", "Bob Smith"),
+            (
+                "Bob and [[Bob Smith]], [[Bob Smith|Bob Smith the brave]], [[Bob's Tavern]]."
+                    .into(),
+                2
+            )
+        );
+        assert_eq!(
+            rewrite_mentions("I will ask Will.", "Will", "William"),
+            ("I will ask Will.".into(), 0)
+        );
+    }
+
+    #[test]
+    fn rename_updates_other_descriptions() {
+        use crate::schema::v1::{Place, Subject};
+
+        let dir = tempfile::tempdir().unwrap();
+        let mut db = Schema::create_new(dir.path().join("test.db")).unwrap();
+        let bob = db
+            .insert_subject(&Subject {
+                name: "Bob".into(),
+                description: "Bob is called Bob.".into(),
+            })
+            .unwrap();
+        let bob = EntityRef::new(EntityKind::Subject, bob);
+        let inn = db
+            .insert_place(&Place {
+                name: "Inn".into(),
+                description: "Run by [[bob]]. Bob the baker drinks here.".into(),
+                parent_place: None,
+            })
+            .unwrap();
+
+        assert!(matches!(
+            db.rename(bob, "  ", true),
+            Err(Error::EmptyName(_))
+        ));
+        assert_eq!(db.rename(bob, "Robert", true).unwrap(), 1);
+        assert_eq!(
+            db.get_place(inn).unwrap().unwrap().t.description,
+            "Run by [[Robert]]. Bob the baker drinks here."
+        );
+        assert_eq!(
+            db.get_sub_by_name("Robert").unwrap().unwrap().t.description,
+            "Bob is called Bob."
+        );
+    }
+
+    #[test]
+    fn replace_mentions_handles_adjacent_matches() {
+        assert_eq!(replace_mentions("abab ab", "ab", "x"), ("abab x".into(), 1));
+    }
+}
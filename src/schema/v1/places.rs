@@ -0,0 +1,232 @@
+use super::{search_key, EntityKind, EntityRef, Place, Schema};
+use crate::schema::{Error, Result, WithId};
+
+impl Schema {
+    /// Inserts the place and returns its id
+    pub fn insert_place(&mut self, x: &Place) -> Result<i64> {
+        self.check_name_free(EntityKind::Place, &x.name, None)?;
+        let mut stmt = self.conn.prepare(
+            "insert into places (name, name_key, description, parent_place) values (?, ?, ?, ?);",
+        )?;
+        stmt.bind((1, x.name.as_str()))?;
+        stmt.bind((2, search_key(&x.name).as_str()))?;
+        stmt.bind((3, x.description.as_str()))?;
+        stmt.bind((4, x.parent_place))?;
+        assert!(stmt.next()? == sqlite::State::Done);
+        self.last_insert_id()
+    }
+
+    /// Updates the place. Fails with [`Error::ParentCycle`] if the new parent is the place
+    /// itself or lies below it.
+    pub fn update_place(&mut self, x: &WithId<Place>) -> Result<()> {
+        self.check_name_free(EntityKind::Place, &x.name, Some(x.id))?;
+        if let Some(parent) = x.parent_place {
+            if self.place_ancestor_ids(parent)?.contains(&x.id) {
+                return Err(Error::ParentCycle(x.name.clone()));
+            }
+        }
+        let mut stmt = self.conn.prepare(
+            "update places set name = ?, name_key = ?, description = ?, parent_place = ?
+             where id == ?;",
+        )?;
+        stmt.bind((1, x.name.as_str()))?;
+        stmt.bind((2, search_key(&x.name).as_str()))?;
+        stmt.bind((3, x.description.as_str()))?;
+        stmt.bind((4, x.parent_place))?;
+        stmt.bind((5, x.id))?;
+        assert!(stmt.next()? == sqlite::State::Done);
+        Ok(())
+    }
+
+    pub fn get_place(&mut self, id: i64) -> Result<Option<WithId<Place>>> {
+        Ok(self
+            .query_places("select * from places where id == ?;", Some(id))?
+            .into_iter()
+            .next())
+    }
+
+    pub fn get_place_by_name(&mut self, name: &str) -> Result<Option<WithId<Place>>> {
+        self.conn
+            .prepare("select * from places where name_key == ? and deleted_at is null;")?
+            .into_iter()
+            .bind((1, search_key(name).as_str()))?
+            .map(|r| Ok(place_from_row(&r?)))
+            .next()
+            .transpose()
+    }
+
+    pub fn list_places(&mut self) -> Result<Vec<WithId<Place>>> {
+        self.query_places(
+            "select * from places where deleted_at is null order by name;",
+            None,
+        )
+    }
+
+    /// Places without a parent, i.e. the roots of the place hierarchy
+    pub fn top_level_places(&mut self) -> Result<Vec<WithId<Place>>> {
+        self.query_places(
+            "select * from places where parent_place is null and deleted_at is null order by name;",
+            None,
+        )
+    }
+
+    /// The direct children of a place
+    pub fn child_places(&mut self, id: i64) -> Result<Vec<WithId<Place>>> {
+        self.query_places(
+            "select * from places where parent_place == ? and deleted_at is null order by name;",
+            Some(id),
+        )
+    }
+
+    /// All places below the given one, at any depth, together with their depth relative to it
+    /// (direct children have depth 1). Places are ordered so that every place comes directly
+    /// after its parent, which allows rendering the result as an indented tree.
+    pub fn place_subtree(&mut self, id: i64) -> Result<Vec<(usize, WithId<Place>)>> {
+        let query = "
+            with recursive sub(id, depth, path) as (
+                select id, 1, name from places where parent_place == ? and deleted_at is null
+                union
+                select places.id, sub.depth + 1, sub.path || char(31) || places.name
+                from places join sub on places.parent_place == sub.id
+                where places.deleted_at is null
+            )
+            select places.*, sub.depth from places join sub on places.id == sub.id
+            order by sub.path;";
+        self.conn
+            .prepare(query)?
+            .into_iter()
+            .bind((1, id))?
+            .map(|r| {
+                let r = r?;
+                Ok((r.read::<i64, _>("depth") as usize, place_from_row(&r)))
+            })
+            .collect()
+    }
+
+    /// The breadcrumb path of a place: all its ancestors starting at the top level, with the
+    /// place itself as last element. The path ends at the first ancestor that is in the trash,
+    /// which is left out together with everything above it.
+    pub fn place_path(&mut self, id: i64) -> Result<Vec<WithId<Place>>> {
+        let query = "
+            with recursive anc(id, depth) as (
+                select ?, 0
+                union
+                select parent.id, anc.depth + 1
+                from places join anc on places.id == anc.id
+                join places as parent on parent.id == places.parent_place
+                where parent.deleted_at is null
+            )
+            select places.* from places join anc on places.id == anc.id
+            order by anc.depth desc;";
+        self.query_places(query, Some(id))
+    }
+
+    /// Deletes the place. Its children are moved up to the deleted place's parent, and all
+    /// links to other entities are removed.
+    pub fn delete_place(&mut self, id: i64) -> Result<()> {
+        let Some(place) = self.get_place(id)? else {
+            return Ok(());
+        };
+        self.in_transaction(|db| {
+            let mut stmt = db
+                .conn
+                .prepare("update places set parent_place = ? where parent_place == ?;")?;
+            stmt.bind((1, place.parent_place))?;
+            stmt.bind((2, id))?;
+            assert!(stmt.next()? == sqlite::State::Done);
+            drop(stmt);
+            db.delete_entity_row(EntityRef::new(EntityKind::Place, id))
+        })
+    }
+
+    /// Ids of the place and all its ancestors
+    fn place_ancestor_ids(&mut self, id: i64) -> Result<Vec<i64>> {
+        Ok(self.place_path(id)?.into_iter().map(|p| p.id).collect())
+    }
+
+    fn query_places(&mut self, query: &str, arg: Option<i64>) -> Result<Vec<WithId<Place>>> {
+        let mut cursor = self.conn.prepare(query)?.into_iter();
+        if let Some(arg) = arg {
+            cursor = cursor.bind((1, arg))?;
+        }
+        cursor.map(|r| Ok(place_from_row(&r?))).collect()
+    }
+}
+
+pub(super) fn place_from_row(r: &sqlite::Row) -> WithId<Place> {
+    WithId {
+        t: Place {
+            name: r.read::<&str, _>("name").to_string(),
+            description: r.read::<&str, _>("description").to_string(),
+            parent_place: r.read::<Option<i64>, _>("parent_place"),
+        },
+        id: r.read::<i64, _>("id"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn place(db: &mut Schema, name: &str, parent_place: Option<i64>) -> i64 {
+        db.insert_place(&Place {
+            name: name.into(),
+            description: "".into(),
+            parent_place,
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn hierarchy_is_walked_and_cycles_are_refused() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut db = Schema::create_new(dir.path().join("test.db")).unwrap();
+        let world = place(&mut db, "World", None);
+        let city = place(&mut db, "City", Some(world));
+        let inn = place(&mut db, "Inn", Some(city));
+        let docks = place(&mut db, "Docks", Some(city));
+
+        let path: Vec<_> = db
+            .place_path(inn)
+            .unwrap()
+            .into_iter()
+            .map(|p| p.id)
+            .collect();
+        assert_eq!(path, vec![world, city, inn]);
+        let subtree: Vec<_> = db
+            .place_subtree(world)
+            .unwrap()
+            .into_iter()
+            .map(|(depth, p)| (depth, p.id))
+            .collect();
+        assert_eq!(subtree, vec![(1, city), (2, docks), (2, inn)]);
+
+        for parent in [world, inn] {
+            let mut moved = db.get_place(world).unwrap().unwrap();
+            moved.t.parent_place = Some(parent);
+            assert!(matches!(
+                db.update_place(&moved),
+                Err(Error::ParentCycle(_))
+            ));
+        }
+        assert_eq!(db.get_place(world).unwrap().unwrap().t.parent_place, None);
+
+        // trashed ancestors are no breadcrumbs
+        db.trash(EntityRef::new(EntityKind::Place, city)).unwrap();
+        let path: Vec<_> = db
+            .place_path(inn)
+            .unwrap()
+            .into_iter()
+            .map(|p| p.id)
+            .collect();
+        assert_eq!(path, vec![inn]);
+        db.restore(EntityRef::new(EntityKind::Place, city)).unwrap();
+        assert_eq!(db.place_path(inn).unwrap().len(), 3);
+
+        db.delete_place(city).unwrap();
+        assert_eq!(
+            db.get_place(inn).unwrap().unwrap().t.parent_place,
+            Some(world)
+        );
+    }
+}
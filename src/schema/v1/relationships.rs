@@ -0,0 +1,284 @@
+use super::Schema;
+use crate::schema::{Result, WithId};
+
+/// A labelled, directed relationship between two subjects, e.g. "Aldric" is "brother of"
+/// "Mira". Stored in `mapping_subjects_subjects`, the id is the row id of the mapping.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Relationship {
+    pub from: i64,
+    pub to: i64,
+    /// How `from` relates to `to`. Empty for plain links created through [`Schema::link`]
+    pub label: String,
+    /// How `to` relates to `from`, e.g. "child of" for "parent of"
+    pub inverse_label: Option<String>,
+    pub note: String,
+    /// In-world date from which on the relationship exists
+    pub valid_from: Option<String>,
+    /// In-world date at which the relationship ended
+    pub valid_to: Option<String>,
+    /// Hidden from the players
+    pub secret: bool,
+}
+
+/// A relationship as seen from one of the two subjects
+#[derive(Clone, Debug, PartialEq)]
+pub struct RelationView {
+    pub rel: WithId<Relationship>,
+    pub other_id: i64,
+    pub other_name: String,
+    /// true if the viewing subject is the target of the relationship
+    pub incoming: bool,
+}
+
+impl RelationView {
+    /// The label that describes how the viewing subject relates to the other one. For incoming
+    /// relationships without an inverse label, the original label is marked with an arrow.
+    pub fn label(&self) -> String {
+        match (self.incoming, &self.rel.inverse_label) {
+            (false, _) => self.rel.label.clone(),
+            (true, Some(inverse)) => inverse.clone(),
+            (true, None) => format!("← {}", self.rel.label),
+        }
+    }
+}
+
+impl Schema {
+    /// Adds the relationship and returns its id. If the two subjects already have a relationship
+    /// with the same label, it is updated instead.
+    pub fn add_relationship(&mut self, x: &Relationship) -> Result<i64> {
+        let mut stmt = self.conn.prepare(
+            "insert into mapping_subjects_subjects
+                (kfrom, kto, label, inverse_label, note, valid_from, valid_to, secret)
+             values (?, ?, ?, ?, ?, ?, ?, ?)
+             on conflict(kfrom, kto, label) do update set
+                inverse_label = excluded.inverse_label,
+                note = excluded.note,
+                valid_from = excluded.valid_from,
+                valid_to = excluded.valid_to,
+                secret = excluded.secret;",
+        )?;
+        bind_relationship(&mut stmt, x)?;
+        assert!(stmt.next()? == sqlite::State::Done);
+
+        let mut stmt = self.conn.prepare(
+            "select rowid from mapping_subjects_subjects
+             where kfrom == ? and kto == ? and label == ?;",
+        )?;
+        stmt.bind((1, x.from))?;
+        stmt.bind((2, x.to))?;
+        stmt.bind((3, x.label.as_str()))?;
+        assert!(stmt.next()? == sqlite::State::Row);
+        Ok(stmt.read::<i64, _>(0)?)
+    }
+
+    pub fn update_relationship(&mut self, x: &WithId<Relationship>) -> Result<()> {
+        let mut stmt = self.conn.prepare(
+            "update mapping_subjects_subjects set
+                kfrom = ?, kto = ?, label = ?, inverse_label = ?, note = ?,
+                valid_from = ?, valid_to = ?, secret = ?
+             where rowid == ?;",
+        )?;
+        bind_relationship(&mut stmt, x)?;
+        stmt.bind((9, x.id))?;
+        assert!(stmt.next()? == sqlite::State::Done);
+        Ok(())
+    }
+
+    pub fn delete_relationship(&mut self, id: i64) -> Result<()> {
+        let mut stmt = self
+            .conn
+            .prepare("delete from mapping_subjects_subjects where rowid == ?;")?;
+        stmt.bind((1, id))?;
+        assert!(stmt.next()? == sqlite::State::Done);
+        Ok(())
+    }
+
+    /// All relationships the subject takes part in, in either direction, sorted by the label
+    /// as seen from the subject and then by the other subject's name
+    pub fn relationships_of(&mut self, subject: i64) -> Result<Vec<RelationView>> {
+        let query = "
+            select m.rowid as id, m.*, s.id as other_id, s.name as other_name, 0 as incoming
+            from mapping_subjects_subjects m join subjects s on s.id == m.kto
+            where m.kfrom == ?1 and s.deleted_at is null
+            union all
+            select m.rowid as id, m.*, s.id as other_id, s.name as other_name, 1 as incoming
+            from mapping_subjects_subjects m join subjects s on s.id == m.kfrom
+            where m.kto == ?1 and s.deleted_at is null;";
+        let mut res = self
+            .conn
+            .prepare(query)?
+            .into_iter()
+            .bind((1, subject))?
+            .map(|r| {
+                let r = r?;
+                Ok(RelationView {
+                    rel: WithId {
+                        t: Relationship {
+                            from: r.read::<i64, _>("kfrom"),
+                            to: r.read::<i64, _>("kto"),
+                            label: r.read::<&str, _>("label").to_string(),
+                            inverse_label: read_opt_string(&r, "inverse_label"),
+                            note: r.read::<Option<&str>, _>("note").unwrap_or("").to_string(),
+                            valid_from: read_opt_string(&r, "valid_from"),
+                            valid_to: read_opt_string(&r, "valid_to"),
+                            secret: r.read::<i64, _>("secret") != 0,
+                        },
+                        id: r.read::<i64, _>("id"),
+                    },
+                    other_id: r.read::<i64, _>("other_id"),
+                    other_name: r.read::<&str, _>("other_name").to_string(),
+                    incoming: r.read::<i64, _>("incoming") != 0,
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+        res.sort_by(|a, b| (a.label(), &a.other_name).cmp(&(b.label(), &b.other_name)));
+        Ok(res)
+    }
+}
+
+fn bind_relationship(stmt: &mut sqlite::Statement, x: &Relationship) -> Result<()> {
+    stmt.bind((1, x.from))?;
+    stmt.bind((2, x.to))?;
+    stmt.bind((3, x.label.as_str()))?;
+    stmt.bind((4, x.inverse_label.as_deref()))?;
+    stmt.bind((5, x.note.as_str()))?;
+    stmt.bind((6, x.valid_from.as_deref()))?;
+    stmt.bind((7, x.valid_to.as_deref()))?;
+    stmt.bind((8, x.secret as i64))?;
+    Ok(())
+}
+
+/// Reads a nullable text column, treating empty strings like NULL
+fn read_opt_string(r: &sqlite::Row, column: &str) -> Option<String> {
+    r.read::<Option<&str>, _>(column)
+        .filter(|s| !s.is_empty())
+        .map(|s| s.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::schema::v1::Subject;
+
+    fn subject(db: &mut Schema, name: &str) -> i64 {
+        db.insert_subject(&Subject {
+            name: name.into(),
+            description: "".into(),
+        })
+        .unwrap()
+    }
+
+    fn relationship(from: i64, to: i64, label: &str, inverse_label: Option<&str>) -> Relationship {
+        Relationship {
+            from,
+            to,
+            label: label.into(),
+            inverse_label: inverse_label.map(|l| l.into()),
+            note: "".into(),
+            valid_from: None,
+            valid_to: None,
+            secret: false,
+        }
+    }
+
+    #[test]
+    fn relationships_are_seen_from_both_sides() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut db = Schema::create_new(dir.path().join("test.db")).unwrap();
+        let aldric = subject(&mut db, "Aldric");
+        let mira = subject(&mut db, "Mira");
+        let vex = subject(&mut db, "Vex");
+
+        let mut brother = relationship(aldric, mira, "brother of", Some("sister of"));
+        brother.note = "They don't talk".into();
+        brother.valid_from = Some("1180".into());
+        brother.valid_to = Some("1200".into());
+        let id = db.add_relationship(&brother).unwrap();
+        db.add_relationship(&relationship(aldric, vex, "owes money to", None))
+            .unwrap();
+
+        let rels = db.relationships_of(aldric).unwrap();
+        let labels: Vec<_> = rels.iter().map(|r| r.label()).collect();
+        assert_eq!(labels, vec!["brother of", "owes money to"]);
+        assert_eq!(rels[0].rel.id, id);
+        assert_eq!(rels[0].rel.t, brother);
+        assert_eq!(rels[0].other_name, "Mira");
+        assert!(!rels[0].incoming);
+
+        let rels = db.relationships_of(mira).unwrap();
+        assert_eq!(rels.len(), 1);
+        assert!(rels[0].incoming);
+        assert_eq!(rels[0].label(), "sister of");
+        assert_eq!(rels[0].other_name, "Aldric");
+        // without an inverse label, the label is shown with an arrow
+        assert_eq!(
+            db.relationships_of(vex).unwrap()[0].label(),
+            "← owes money to"
+        );
+    }
+
+    #[test]
+    fn relationships_with_the_same_label_are_next_to_each_other() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut db = Schema::create_new(dir.path().join("test.db")).unwrap();
+        let aldric = subject(&mut db, "Aldric");
+        let others: Vec<_> = ["Vex", "Mira", "Olaf"]
+            .into_iter()
+            .map(|name| subject(&mut db, name))
+            .collect();
+        db.add_relationship(&relationship(aldric, others[0], "rival of", None))
+            .unwrap();
+        db.add_relationship(&relationship(aldric, others[1], "friend of", None))
+            .unwrap();
+        db.add_relationship(&relationship(aldric, others[2], "rival of", None))
+            .unwrap();
+        // the same label again updates the relationship instead of adding a second one
+        let mut again = relationship(aldric, others[2], "rival of", None);
+        again.note = "since the duel".into();
+        db.add_relationship(&again).unwrap();
+
+        let rels: Vec<_> = db
+            .relationships_of(aldric)
+            .unwrap()
+            .into_iter()
+            .map(|r| (r.label(), r.other_name, r.rel.t.note))
+            .collect();
+        assert_eq!(
+            rels,
+            vec![
+                ("friend of".to_string(), "Mira".to_string(), "".to_string()),
+                (
+                    "rival of".to_string(),
+                    "Olaf".to_string(),
+                    "since the duel".to_string()
+                ),
+                ("rival of".to_string(), "Vex".to_string(), "".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn relationships_are_updated_and_deleted() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut db = Schema::create_new(dir.path().join("test.db")).unwrap();
+        let aldric = subject(&mut db, "Aldric");
+        let mira = subject(&mut db, "Mira");
+        let id = db
+            .add_relationship(&relationship(aldric, mira, "friend of", None))
+            .unwrap();
+
+        let changed = relationship(aldric, mira, "enemy of", Some("enemy of"));
+        db.update_relationship(&WithId {
+            t: changed.clone(),
+            id,
+        })
+        .unwrap();
+        let rels = db.relationships_of(aldric).unwrap();
+        assert_eq!(rels.len(), 1);
+        assert_eq!(rels[0].rel.t, changed);
+
+        db.delete_relationship(id).unwrap();
+        assert!(db.relationships_of(aldric).unwrap().is_empty());
+        assert!(db.relationships_of(mira).unwrap().is_empty());
+    }
+}
@@ -0,0 +1,240 @@
+use super::{unix_now, EntityRef, Schema};
+use crate::schema::{Result, WithId};
+
+/// Saves of the same editing session that are less than this many seconds apart are merged
+/// into a single revision
+const COALESCE_SECS: i64 = 120;
+
+/// Stored in the `session` column for revisions that were not made in an editor, see
+/// [`Revision::session`]. Editor sessions are numbered by their start time, so they never
+/// get this id.
+const NO_SESSION: i64 = 0;
+
+/// A snapshot of an entity's description
+#[derive(Clone, Debug, PartialEq)]
+pub struct Revision {
+    pub entity: EntityRef,
+    pub description: String,
+    /// Identifies the editing session the revision was created in. None for reverts, renames
+    /// and the state before the first edit, which are never merged with other saves.
+    pub session: Option<i64>,
+    /// unix timestamp of the first save that went into this revision
+    pub created_at: i64,
+    /// unix timestamp of the last save that went into this revision
+    pub updated_at: i64,
+}
+
+impl Schema {
+    /// Records the new description of an entity. If the latest revision of the entity belongs
+    /// to the same editing session, and was saved recently, it is overwritten instead of
+    /// creating a new one, so an editor that saves every few seconds doesn't flood the history.
+    pub fn save_revision(
+        &mut self,
+        entity: EntityRef,
+        description: &str,
+        session: Option<i64>,
+    ) -> Result<()> {
+        let now = unix_now();
+        if let Some(last) = self.list_revisions(entity)?.into_iter().next() {
+            if last.description == description {
+                return Ok(());
+            }
+            if session.is_some() && last.session == session && now - last.updated_at < COALESCE_SECS
+            {
+                let mut stmt = self.conn.prepare(
+                    "update revisions set description = ?, updated_at = ? where id == ?;",
+                )?;
+                stmt.bind((1, description))?;
+                stmt.bind((2, now))?;
+                stmt.bind((3, last.id))?;
+                assert!(stmt.next()? == sqlite::State::Done);
+                return Ok(());
+            }
+        }
+        self.insert_revision(&Revision {
+            entity,
+            description: description.to_string(),
+            session,
+            created_at: now,
+            updated_at: now,
+        })
+    }
+
+    /// Stores the current description as first revision, if the entity has no history yet.
+    /// Call this before editing, so the state before the first edit can be restored.
+    pub fn ensure_revision(&mut self, entity: EntityRef, description: &str) -> Result<()> {
+        if self.list_revisions(entity)?.is_empty() {
+            let now = unix_now();
+            self.insert_revision(&Revision {
+                entity,
+                description: description.to_string(),
+                session: None,
+                created_at: now,
+                updated_at: now,
+            })?;
+        }
+        Ok(())
+    }
+
+    /// All revisions of the entity, the newest first
+    pub fn list_revisions(&mut self, entity: EntityRef) -> Result<Vec<WithId<Revision>>> {
+        self.conn
+            .prepare(
+                "select * from revisions where kind == ? and entity_id == ?
+                 order by updated_at desc, id desc;",
+            )?
+            .into_iter()
+            .bind((1, entity.kind.table()))?
+            .bind((2, entity.id))?
+            .map(|r| {
+                let r = r?;
+                Ok(WithId {
+                    t: Revision {
+                        entity,
+                        description: r.read::<&str, _>("description").to_string(),
+                        session: Some(r.read::<i64, _>("session")).filter(|s| *s != NO_SESSION),
+                        created_at: r.read::<i64, _>("created_at"),
+                        updated_at: r.read::<i64, _>("updated_at"),
+                    },
+                    id: r.read::<i64, _>("id"),
+                })
+            })
+            .collect()
+    }
+
+    /// Sets the description of the entity back to the one stored in the revision. The revert
+    /// itself is recorded as a new revision, so it can be undone.
+    pub fn revert_to_revision(&mut self, rev: &WithId<Revision>) -> Result<()> {
+        self.set_description(rev.entity, &rev.description)?;
+        self.save_revision(rev.entity, &rev.description, None)
+    }
+
+    /// Records a change of the description that was not made in the editor, e.g. by a rename.
+    /// The edit always gets a revision of its own.
+    pub(super) fn record_edit(&mut self, entity: EntityRef, old: &str, new: &str) -> Result<()> {
+        self.ensure_revision(entity, old)?;
+        let now = unix_now();
+        self.insert_revision(&Revision {
+            entity,
+            description: new.to_string(),
+            session: None,
+            created_at: now,
+            updated_at: now,
+        })
+    }
+
+    pub(super) fn delete_revisions(&mut self, entity: EntityRef) -> Result<()> {
+        let mut stmt = self
+            .conn
+            .prepare("delete from revisions where kind == ? and entity_id == ?;")?;
+        stmt.bind((1, entity.kind.table()))?;
+        stmt.bind((2, entity.id))?;
+        assert!(stmt.next()? == sqlite::State::Done);
+        Ok(())
+    }
+
+    fn insert_revision(&mut self, x: &Revision) -> Result<()> {
+        let mut stmt = self.conn.prepare(
+            "insert into revisions (kind, entity_id, description, session, created_at, updated_at)
+             values (?, ?, ?, ?, ?, ?);",
+        )?;
+        stmt.bind((1, x.entity.kind.table()))?;
+        stmt.bind((2, x.entity.id))?;
+        stmt.bind((3, x.description.as_str()))?;
+        stmt.bind((4, x.session.unwrap_or(NO_SESSION)))?;
+        stmt.bind((5, x.created_at))?;
+        stmt.bind((6, x.updated_at))?;
+        assert!(stmt.next()? == sqlite::State::Done);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::schema::v1::{EntityKind, Subject};
+
+    #[test]
+    fn saves_of_one_session_are_coalesced() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut db = Schema::create_new(dir.path().join("test.db")).unwrap();
+        let id = db
+            .insert_subject(&Subject {
+                name: "Mira".into(),
+                description: "".into(),
+            })
+            .unwrap();
+        let mira = EntityRef::new(EntityKind::Subject, id);
+        let descriptions = |db: &mut Schema| -> Vec<String> {
+            db.list_revisions(mira)
+                .unwrap()
+                .into_iter()
+                .map(|r| r.t.description)
+                .collect()
+        };
+
+        db.ensure_revision(mira, "").unwrap();
+        db.save_revision(mira, "R", Some(1)).unwrap();
+        db.save_revision(mira, "Ru", Some(1)).unwrap();
+        db.save_revision(mira, "Runs", Some(1)).unwrap();
+        db.save_revision(mira, "Runs", Some(2)).unwrap();
+        assert_eq!(descriptions(&mut db), vec!["Runs", ""]);
+
+        // another session, or the same one after a break, starts a new revision
+        db.save_revision(mira, "Runs the inn", Some(2)).unwrap();
+        assert_eq!(descriptions(&mut db), vec!["Runs the inn", "Runs", ""]);
+        db.conn
+            .execute("update revisions set updated_at = updated_at - 3600;")
+            .unwrap();
+        db.save_revision(mira, "Runs the inn.", Some(2)).unwrap();
+        assert_eq!(
+            descriptions(&mut db),
+            vec!["Runs the inn.", "Runs the inn", "Runs", ""]
+        );
+
+        let oldest = db.list_revisions(mira).unwrap().pop().unwrap();
+        db.revert_to_revision(&oldest).unwrap();
+        assert_eq!(
+            db.get_sub_by_name("Mira").unwrap().unwrap().t.description,
+            ""
+        );
+        assert_eq!(descriptions(&mut db).len(), 5);
+        // reverts belong to no editing session, so they are never merged
+        let runs = db.list_revisions(mira).unwrap().swap_remove(3);
+        assert_eq!(runs.description, "Runs");
+        assert_eq!(runs.session, Some(1));
+        db.revert_to_revision(&runs).unwrap();
+        assert_eq!(descriptions(&mut db).len(), 6);
+        assert_eq!(db.list_revisions(mira).unwrap()[0].session, None);
+    }
+
+    #[test]
+    fn descriptions_are_saved_with_their_links() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut db = Schema::create_new(dir.path().join("test.db")).unwrap();
+        let mut subject = |name: &str| {
+            let id = db
+                .insert_subject(&Subject {
+                    name: name.into(),
+                    description: "".into(),
+                })
+                .unwrap();
+            EntityRef::new(EntityKind::Subject, id)
+        };
+        let mira = subject("Mira");
+        let bob = subject("Bob");
+
+        db.save_description(mira, "Married to [[Bob]]", Some(1))
+            .unwrap();
+        assert!(db.is_linked(mira, bob).unwrap());
+        assert_eq!(db.list_revisions(mira).unwrap().len(), 1);
+
+        db.save_description(mira, "Unmarried", Some(2)).unwrap();
+        assert!(!db.is_linked(mira, bob).unwrap());
+
+        // reverting brings the links of the old description back
+        let married = db.list_revisions(mira).unwrap().swap_remove(1);
+        db.revert_to_revision(&married).unwrap();
+        assert!(db.is_linked(mira, bob).unwrap());
+    }
+}
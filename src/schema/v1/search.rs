@@ -0,0 +1,344 @@
+use super::{Audience, EntityKind, EntityRef, Schema};
+use crate::schema::{Result, WithId};
+use crate::wiki::strip_secrets;
+
+use unicode_normalization::{char::is_combining_mark, UnicodeNormalization};
+
+/// Marks the start of a match in the snippets produced by sqlite, replaced by `<mark>` after
+/// the snippet was html escaped
+const MATCH_START: char = '\u{2}';
+const MATCH_END: char = '\u{3}';
+
+/// A full text search result
+#[derive(Clone, Debug, PartialEq)]
+pub struct SearchHit {
+    pub entity: EntityRef,
+    pub name: String,
+    /// Html escaped excerpt of the description around the match, with the matching words
+    /// wrapped in `<mark>`
+    pub snippet: String,
+}
+
+impl Schema {
+    /// Entities of the given kind whose name contains `query`, ignoring case and diacritics,
+    /// so "eowyn" finds "Éowyn". `query` is matched literally, `%` and `_` are no wildcards.
+    /// Returns id and name, sorted by name.
+    pub fn query_names(&mut self, kind: EntityKind, query: &str) -> Result<Vec<WithId<String>>> {
+        assert!(kind != EntityKind::Event, "Events have no name");
+        let pattern = format!("%{}%", escape_like(&search_key(query)));
+        self.conn
+            .prepare(format!(
+                "select id, name from {} where name_key like ? escape '\\' and deleted_at is null
+                 order by name_key, name;",
+                kind.table()
+            ))?
+            .into_iter()
+            .bind((1, pattern.as_str()))?
+            .map(|r| {
+                let r = r?;
+                Ok(WithId {
+                    t: r.read::<&str, _>("name").to_string(),
+                    id: r.read::<i64, _>("id"),
+                })
+            })
+            .collect()
+    }
+
+    /// Searches names and descriptions of subjects, places, groups and events. Every word of
+    /// `query` must occur, the last one may be incomplete. Results are ordered by relevance.
+    /// Players neither find secret entities, nor entities that only match in secret blocks, and
+    /// get no snippets of descriptions with secret blocks. At most `limit` hits are returned,
+    /// counted after the hidden ones were left out.
+    pub fn full_text_search(
+        &mut self,
+        query: &str,
+        limit: usize,
+        audience: Audience,
+    ) -> Result<Vec<SearchHit>> {
+        if audience == Audience::GameMaster {
+            return self.search_index(query, limit, 0);
+        }
+        let mut res = vec![];
+        let mut offset = 0;
+        while res.len() < limit {
+            // hidden hits don't count, so read the index page by page until enough are left
+            let hits = self.search_index(query, limit, offset)?;
+            offset += hits.len();
+            let last_page = hits.len() < limit;
+            for mut hit in hits {
+                if res.len() == limit || !self.is_visible(hit.entity, audience)? {
+                    continue;
+                }
+                let full = self
+                    .visible_description(hit.entity, Audience::GameMaster)?
+                    .unwrap_or_default();
+                let visible = strip_secrets(&full);
+                if visible != full {
+                    // the index knows the whole description, so check the rest again
+                    if !matches_words(query, &format!("{} {visible}", hit.name)) {
+                        continue;
+                    }
+                    hit.snippet = String::new();
+                }
+                res.push(hit);
+            }
+            if last_page {
+                break;
+            }
+        }
+        Ok(res)
+    }
+
+    /// The hits `offset..offset + limit` of the index, by relevance
+    fn search_index(&mut self, query: &str, limit: usize, offset: usize) -> Result<Vec<SearchHit>> {
+        let Some(fts_query) = to_fts_query(query) else {
+            return Ok(vec![]);
+        };
+        let stmt = self.conn.prepare(format!(
+            "select kind, entity_id, name,
+                snippet(search_index, 3, '{MATCH_START}', '{MATCH_END}', '…', 16) as snippet
+             from search_index where search_index match ?
+             order by bm25(search_index, 0, 0, 10, 1) limit ? offset ?;"
+        ))?;
+        stmt.into_iter()
+            .bind((1, fts_query.as_str()))?
+            .bind((2, limit as i64))?
+            .bind((3, offset as i64))?
+            .filter_map(|r| {
+                let r = match r {
+                    Ok(r) => r,
+                    Err(e) => return Some(Err(e.into())),
+                };
+                let kind = EntityKind::from_table(r.read::<&str, _>("kind"))?;
+                Some(Ok(SearchHit {
+                    entity: EntityRef::new(kind, r.read::<i64, _>("entity_id")),
+                    name: r.read::<&str, _>("name").to_string(),
+                    snippet: highlight(r.read::<&str, _>("snippet")),
+                }))
+            })
+            .collect()
+    }
+}
+
+/// The normalized form of a name that is used for searching and comparing names: lower case,
+/// with all diacritics removed
+pub fn search_key(s: &str) -> String {
+    s.nfd()
+        .filter(|c| !is_combining_mark(*c))
+        .flat_map(char::to_lowercase)
+        .collect()
+}
+
+/// Separates tag filters like `#villain` from the rest of a search query. Returns the query
+/// without the filters, and the tag names without `#`.
+pub fn split_tag_filters(query: &str) -> (String, Vec<String>) {
+    let mut rest = vec![];
+    let mut tags = vec![];
+    for word in query.split_whitespace() {
+        match word.strip_prefix('#') {
+            Some(tag) if !tag.is_empty() => tags.push(tag.to_string()),
+            Some(_) => {}
+            None => rest.push(word),
+        }
+    }
+    (rest.join(" "), tags)
+}
+
+/// Escapes the wildcards of a like pattern, for use with `escape '\'`
+fn escape_like(s: &str) -> String {
+    let mut res = String::with_capacity(s.len());
+    for c in s.chars() {
+        if matches!(c, '\\' | '%' | '_') {
+            res.push('\\');
+        }
+        res.push(c);
+    }
+    res
+}
+
+/// Turns user input into an fts5 query. Every word is quoted, so characters with a special
+/// meaning in the query syntax are matched literally, and the last word is used as prefix.
+fn to_fts_query(query: &str) -> Option<String> {
+    let words = query
+        .split_whitespace()
+        // words without any letters or digits would end up as empty phrases
+        .filter(|w| w.chars().any(char::is_alphanumeric))
+        .map(|w| format!("\"{}\"", w.replace('"', "\"\"")))
+        .collect::<Vec<_>>();
+    if words.is_empty() {
+        None
+    } else {
+        Some(words.join(" ") + "*")
+    }
+}
+
+/// Whether `text` contains every word of `query` like the full text index sees it: ignoring
+/// case and diacritics, with the last word only as prefix
+fn matches_words(query: &str, text: &str) -> bool {
+    let words = |s: &str| -> Vec<String> {
+        search_key(s)
+            .split(|c: char| !c.is_alphanumeric())
+            .filter(|w| !w.is_empty())
+            .map(|w| w.to_string())
+            .collect()
+    };
+    let text = words(text);
+    let query = words(query);
+    query.iter().enumerate().all(|(i, q)| {
+        text.iter()
+            .any(|t| t == q || (i + 1 == query.len() && t.starts_with(q.as_str())))
+    })
+}
+
+fn highlight(snippet: &str) -> String {
+    escape_html(snippet)
+        .replace(MATCH_START, "<mark>")
+        .replace(MATCH_END, "</mark>")
+}
+
+pub(crate) fn escape_html(s: &str) -> String {
+    let mut res = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => res.push_str("&amp;"),
+            '<' => res.push_str("&lt;"),
+            '>' => res.push_str("&gt;"),
+            '"' => res.push_str("&quot;"),
+            '\'' => res.push_str("&#39;"),
+            c => res.push(c),
+        }
+    }
+    res
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::schema::v1::Subject;
+
+    fn db_with_subjects(names: &[&str]) -> (tempfile::TempDir, Schema) {
+        let dir = tempfile::tempdir().unwrap();
+        let mut db = Schema::create_new(dir.path().join("test.db")).unwrap();
+        for name in names {
+            db.insert_subject(&Subject {
+                name: name.to_string(),
+                description: "".into(),
+            })
+            .unwrap();
+        }
+        (dir, db)
+    }
+
+    #[test]
+    fn search_key_folds_case_and_diacritics() {
+        assert_eq!(search_key("Éowyn"), "eowyn");
+        assert_eq!(search_key("ÅNGSTRÖM"), "angstrom");
+        assert_eq!(search_key("plain"), "plain");
+    }
+
+    #[test]
+    fn escape_like_escapes_wildcards() {
+        assert_eq!(escape_like(r"100%_\x"), r"100\%\_\\x");
+    }
+
+    #[test]
+    fn split_tag_filters_removes_tags_from_query() {
+        assert_eq!(
+            split_tag_filters("old #villain man #Orc #"),
+            (
+                "old man".to_string(),
+                vec!["villain".to_string(), "Orc".to_string()]
+            )
+        );
+        assert_eq!(split_tag_filters("plain"), ("plain".to_string(), vec![]));
+    }
+
+    #[test]
+    fn query_names_is_case_and_diacritic_insensitive() {
+        let (_dir, mut db) = db_with_subjects(&["Éowyn", "Eomer", "Aragorn"]);
+        assert_eq!(db.query_subj_names("eowyn").unwrap(), vec!["Éowyn"]);
+        assert_eq!(db.query_subj_names("ÉO").unwrap(), vec!["Eomer", "Éowyn"]);
+    }
+
+    #[test]
+    fn query_names_handles_hostile_input() {
+        let (_dir, mut db) = db_with_subjects(&["O'Brien", "100% Orc", "Under_score", "Bob"]);
+        assert_eq!(db.query_subj_names("'").unwrap(), vec!["O'Brien"]);
+        assert_eq!(db.query_subj_names("%").unwrap(), vec!["100% Orc"]);
+        assert_eq!(db.query_subj_names("_").unwrap(), vec!["Under_score"]);
+        assert!(db.query_subj_names(r"\").unwrap().is_empty());
+        assert!(db
+            .query_subj_names("'; drop table subjects; --")
+            .unwrap()
+            .is_empty());
+        assert!(db.query_subj_names("\" or 1=1 --").unwrap().is_empty());
+        assert_eq!(db.query_subj_names("").unwrap().len(), 4);
+    }
+
+    #[test]
+    fn full_text_search_handles_hostile_input() {
+        let (_dir, mut db) = db_with_subjects(&["Bob"]);
+        for query in ["\"", "*", "NEAR(", "a OR", "'", "-", "^", ":"] {
+            db.full_text_search(query, 10, Audience::Players).unwrap();
+        }
+    }
+
+    #[test]
+    fn players_dont_find_secrets() {
+        let (_dir, mut db) = db_with_subjects(&["Vex"]);
+        db.insert_subject(&Subject {
+            name: "Aldric".into(),
+            description: "A baker.\n:::secret\nSecretly a dragon.\n:::\n".into(),
+        })
+        .unwrap();
+        let vex = db.get_sub_by_name("Vex").unwrap().unwrap().id;
+        db.set_secret(EntityRef::new(EntityKind::Subject, vex), true)
+            .unwrap();
+
+        let names = |hits: Vec<SearchHit>| hits.into_iter().map(|h| h.name).collect::<Vec<_>>();
+        assert_eq!(
+            names(
+                db.full_text_search("drag", 10, Audience::GameMaster)
+                    .unwrap()
+            ),
+            vec!["Aldric"]
+        );
+        assert!(db
+            .full_text_search("drag", 10, Audience::Players)
+            .unwrap()
+            .is_empty());
+        let hits = db.full_text_search("bak", 10, Audience::Players).unwrap();
+        assert_eq!(names(hits.clone()), vec!["Aldric"]);
+        assert_eq!(hits[0].snippet, "");
+        assert!(db
+            .full_text_search("vex", 10, Audience::Players)
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn hidden_hits_dont_use_up_the_limit() {
+        let (_dir, mut db) = db_with_subjects(&["Orc Chief", "Orc Shaman", "Orc Scout"]);
+        for name in ["Orc Chief", "Orc Shaman", "Orc Scout"] {
+            let id = db.get_sub_by_name(name).unwrap().unwrap().id;
+            db.set_secret(EntityRef::new(EntityKind::Subject, id), true)
+                .unwrap();
+        }
+        // matches in the description only, so it ranks below the secret ones
+        db.insert_subject(&Subject {
+            name: "Mira".into(),
+            description: "Hunts an orc now and then.".into(),
+        })
+        .unwrap();
+
+        assert_eq!(
+            db.full_text_search("orc", 2, Audience::GameMaster)
+                .unwrap()
+                .len(),
+            2
+        );
+        let hits = db.full_text_search("orc", 2, Audience::Players).unwrap();
+        assert_eq!(hits.len(), 1);
+        assert_eq!(hits[0].name, "Mira");
+    }
+}
@@ -0,0 +1,159 @@
+use super::{EntityKind, EntityRef, Schema};
+use crate::schema::Result;
+use crate::wiki::strip_secrets;
+
+/// Who is looking. Players don't get to see secret entities, secret links and relationships,
+/// or the secret blocks in descriptions.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Audience {
+    GameMaster,
+    Players,
+}
+
+impl Audience {
+    /// Whether something with the given secret flag is shown to this audience
+    pub fn sees(self, secret: bool) -> bool {
+        self == Audience::GameMaster || !secret
+    }
+}
+
+impl Schema {
+    /// Whether `x` is hidden from the players. Tags are never secret.
+    pub fn is_secret(&mut self, x: EntityRef) -> Result<bool> {
+        if x.kind == EntityKind::Tag {
+            return Ok(false);
+        }
+        let mut stmt = self.conn.prepare(format!(
+            "select secret from {} where id == ?;",
+            x.kind.table()
+        ))?;
+        stmt.bind((1, x.id))?;
+        if stmt.next()? != sqlite::State::Row {
+            return Ok(false);
+        }
+        Ok(stmt.read::<i64, _>(0)? != 0)
+    }
+
+    pub fn set_secret(&mut self, x: EntityRef, secret: bool) -> Result<()> {
+        assert!(x.kind != EntityKind::Tag, "Tags can't be secret");
+        let mut stmt = self.conn.prepare(format!(
+            "update {} set secret = ? where id == ?;",
+            x.kind.table()
+        ))?;
+        stmt.bind((1, secret as i64))?;
+        stmt.bind((2, x.id))?;
+        assert!(stmt.next()? == sqlite::State::Done);
+        Ok(())
+    }
+
+    /// Whether `x` may be shown to `audience`
+    pub fn is_visible(&mut self, x: EntityRef, audience: Audience) -> Result<bool> {
+        Ok(audience == Audience::GameMaster || !self.is_secret(x)?)
+    }
+
+    /// The description of `x` as `audience` may see it, None for tags and missing entities
+    pub fn visible_description(
+        &mut self,
+        x: EntityRef,
+        audience: Audience,
+    ) -> Result<Option<String>> {
+        if x.kind == EntityKind::Tag || !self.is_visible(x, audience)? {
+            return Ok(None);
+        }
+        let mut stmt = self.conn.prepare(format!(
+            "select description from {} where id == ?;",
+            x.kind.table()
+        ))?;
+        stmt.bind((1, x.id))?;
+        if stmt.next()? != sqlite::State::Row {
+            return Ok(None);
+        }
+        let description = stmt.read::<Option<String>, _>(0)?.unwrap_or_default();
+        Ok(Some(match audience {
+            Audience::GameMaster => description,
+            Audience::Players => strip_secrets(&description),
+        }))
+    }
+
+    pub fn set_relationship_secret(&mut self, id: i64, secret: bool) -> Result<()> {
+        let mut stmt = self
+            .conn
+            .prepare("update mapping_subjects_subjects set secret = ? where rowid == ?;")?;
+        stmt.bind((1, secret as i64))?;
+        stmt.bind((2, id))?;
+        assert!(stmt.next()? == sqlite::State::Done);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::schema::v1::{Relationship, Subject};
+    use crate::wiki;
+
+    #[test]
+    fn players_miss_secret_entities_links_and_blocks() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut db = Schema::create_new(dir.path().join("test.db")).unwrap();
+        let mut subject = |name: &str, description: &str| {
+            let id = db
+                .insert_subject(&Subject {
+                    name: name.into(),
+                    description: description.into(),
+                })
+                .unwrap();
+            EntityRef::new(EntityKind::Subject, id)
+        };
+        let mira = subject("Mira", "");
+        let vex = subject("Vex", "");
+        let aldric = subject(
+            "Aldric",
+            "Friend of [[Mira]].\n:::secret\nWorks for [[Vex]].\n:::\n",
+        );
+        db.set_secret(vex, true).unwrap();
+        assert!(db.is_secret(vex).unwrap());
+        assert!(db.is_visible(vex, Audience::GameMaster).unwrap());
+        assert!(!db.is_visible(vex, Audience::Players).unwrap());
+
+        let description = db.get_subject(aldric.id).unwrap().unwrap().t.description;
+        wiki::sync_links(&mut db, aldric, &description).unwrap();
+        let links = db.outgoing_links(aldric).unwrap();
+        assert_eq!(links.len(), 2);
+        assert!(!links.iter().find(|l| l.entity == mira).unwrap().secret);
+        assert!(links.iter().find(|l| l.entity == vex).unwrap().secret);
+
+        let html = wiki::render_markdown(&mut db, &description, Audience::Players).unwrap();
+        assert!(html.contains("Mira"));
+        assert!(!html.contains("Vex"));
+        let html = wiki::render_markdown(&mut db, &description, Audience::GameMaster).unwrap();
+        assert!(html.contains("class=\"secret\""));
+        assert!(html.contains("wiki:subject/"));
+        assert_eq!(
+            db.visible_description(aldric, Audience::Players)
+                .unwrap()
+                .as_deref(),
+            Some("Friend of [[Mira]].\n")
+        );
+        assert_eq!(
+            db.visible_description(vex, Audience::Players).unwrap(),
+            None
+        );
+
+        let rel = db
+            .add_relationship(&Relationship {
+                from: aldric.id,
+                to: mira.id,
+                label: "rival of".into(),
+                inverse_label: None,
+                note: "".into(),
+                valid_from: None,
+                valid_to: None,
+                secret: false,
+            })
+            .unwrap();
+        db.set_relationship_secret(rel, true).unwrap();
+        let rels = db.relationships_of(aldric.id).unwrap();
+        assert!(rels.iter().find(|r| r.rel.id == rel).unwrap().rel.secret);
+    }
+}
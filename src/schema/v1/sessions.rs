@@ -0,0 +1,219 @@
+use super::{Audience, EntityKind, EntityRef, Event, Linked, Schema};
+use crate::schema::{Error, Result, WithId};
+
+/// A real-world play session
+#[derive(Clone, Debug, PartialEq)]
+pub struct Session {
+    pub number: i64,
+    /// The real-world date, like `2023-07-14`
+    pub played_on: String,
+    pub title: String,
+    /// Markdown
+    pub recap: String,
+    pub players: Vec<String>,
+}
+
+impl Schema {
+    /// All sessions, the latest first
+    pub fn list_sessions(&mut self) -> Result<Vec<WithId<Session>>> {
+        self.conn
+            .prepare("select * from sessions order by number desc;")?
+            .into_iter()
+            .map(|r| Ok(session_from_row(&r?)))
+            .collect()
+    }
+
+    pub fn get_session(&mut self, id: i64) -> Result<Option<WithId<Session>>> {
+        Ok(self
+            .conn
+            .prepare("select * from sessions where id == ?;")?
+            .into_iter()
+            .bind((1, id))?
+            .map(|r| Ok(session_from_row(&r?)))
+            .collect::<Result<Vec<_>>>()?
+            .into_iter()
+            .next())
+    }
+
+    /// One more than the highest session number so far
+    pub fn next_session_number(&mut self) -> Result<i64> {
+        let mut stmt = self
+            .conn
+            .prepare("select coalesce(max(number), 0) + 1 from sessions;")?;
+        assert!(stmt.next()? == sqlite::State::Row);
+        Ok(stmt.read::<i64, _>(0)?)
+    }
+
+    /// Inserts the session and returns its id. Fails with [`Error::SessionNumberTaken`] if
+    /// there already is a session with that number.
+    pub fn insert_session(&mut self, x: &Session) -> Result<i64> {
+        self.check_session_number_free(x.number, None)?;
+        let mut stmt = self.conn.prepare(
+            "insert into sessions (number, played_on, title, recap, players)
+             values (?, ?, ?, ?, ?);",
+        )?;
+        bind_session(&mut stmt, x)?;
+        assert!(stmt.next()? == sqlite::State::Done);
+        self.last_insert_id()
+    }
+
+    pub fn update_session(&mut self, x: &WithId<Session>) -> Result<()> {
+        self.check_session_number_free(x.number, Some(x.id))?;
+        let mut stmt = self.conn.prepare(
+            "update sessions set number = ?, played_on = ?, title = ?, recap = ?, players = ?
+             where id == ?;",
+        )?;
+        bind_session(&mut stmt, x)?;
+        stmt.bind((6, x.id))?;
+        assert!(stmt.next()? == sqlite::State::Done);
+        Ok(())
+    }
+
+    /// Deletes the session. Its events are kept, but no longer belong to a session.
+    pub fn delete_session(&mut self, id: i64) -> Result<()> {
+        for query in [
+            "update events set session_id = null where session_id == ?;",
+            "delete from sessions where id == ?;",
+        ] {
+            let mut stmt = self.conn.prepare(query)?;
+            stmt.bind((1, id))?;
+            assert!(stmt.next()? == sqlite::State::Done);
+        }
+        Ok(())
+    }
+
+    /// The events that happened in the session, in world order
+    pub fn session_events(&mut self, id: i64) -> Result<Vec<WithId<Event>>> {
+        Ok(self
+            .events_in_world_order()?
+            .into_iter()
+            .filter(|e| e.session == Some(id))
+            .collect())
+    }
+
+    /// The subjects, places and groups the session's events are linked to, sorted by kind and
+    /// name, each only once. Secret events and links only count if `audience` may see them.
+    pub fn session_participants(&mut self, id: i64, audience: Audience) -> Result<Vec<Linked>> {
+        let mut res: Vec<Linked> = vec![];
+        for ev in self.session_events(id)? {
+            let event = EntityRef::new(EntityKind::Event, ev.id);
+            if !self.is_visible(event, audience)? {
+                continue;
+            }
+            for l in self.outgoing_links(event)? {
+                if l.entity.kind != EntityKind::Tag
+                    && audience.sees(l.secret)
+                    && !res.iter().any(|r| r.entity == l.entity)
+                {
+                    res.push(l);
+                }
+            }
+        }
+        res.sort_by(|a, b| (a.entity.kind, &a.label).cmp(&(b.entity.kind, &b.label)));
+        Ok(res)
+    }
+
+    fn check_session_number_free(&mut self, number: i64, except: Option<i64>) -> Result<()> {
+        let mut stmt = self
+            .conn
+            .prepare("select count(*) from sessions where number == ? and id != ?;")?;
+        stmt.bind((1, number))?;
+        stmt.bind((2, except.unwrap_or(-1)))?;
+        assert!(stmt.next()? == sqlite::State::Row);
+        if stmt.read::<i64, _>(0)? > 0 {
+            Err(Error::SessionNumberTaken(number))
+        } else {
+            Ok(())
+        }
+    }
+}
+
+fn bind_session(stmt: &mut sqlite::Statement, x: &Session) -> Result<()> {
+    stmt.bind((1, x.number))?;
+    stmt.bind((2, x.played_on.as_str()))?;
+    stmt.bind((3, x.title.as_str()))?;
+    stmt.bind((4, x.recap.as_str()))?;
+    stmt.bind((5, x.players.join("\n").as_str()))?;
+    Ok(())
+}
+
+fn session_from_row(r: &sqlite::Row) -> WithId<Session> {
+    WithId {
+        t: Session {
+            number: r.read::<i64, _>("number"),
+            played_on: r.read::<&str, _>("played_on").to_string(),
+            title: r.read::<&str, _>("title").to_string(),
+            recap: r.read::<&str, _>("recap").to_string(),
+            players: r
+                .read::<&str, _>("players")
+                .lines()
+                .map(|p| p.to_string())
+                .collect(),
+        },
+        id: r.read::<i64, _>("id"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::schema::v1::Place;
+
+    #[test]
+    fn sessions_collect_events_and_participants() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut db = Schema::create_new(dir.path().join("test.db")).unwrap();
+        assert_eq!(db.next_session_number().unwrap(), 1);
+        let session = Session {
+            number: 1,
+            played_on: "2023-07-14".into(),
+            title: "The heist".into(),
+            recap: "".into(),
+            players: vec!["Anna".into(), "Ben".into()],
+        };
+        let id = db.insert_session(&session).unwrap();
+        assert!(db.insert_session(&session).is_err());
+        assert_eq!(db.next_session_number().unwrap(), 2);
+        assert_eq!(db.get_session(id).unwrap().unwrap().t, session);
+
+        let bank = db
+            .insert_place(&Place {
+                name: "Bank".into(),
+                description: "".into(),
+                parent_place: None,
+            })
+            .unwrap();
+        let bank = EntityRef::new(EntityKind::Place, bank);
+        let mut events = vec![];
+        for date in ["2 May 1200", "1 May 1200"] {
+            let ev = db
+                .insert_event(&Event {
+                    record_date: 0,
+                    refered_date: date.into(),
+                    description: "".into(),
+                    date: None,
+                    session: Some(id),
+                })
+                .unwrap();
+            db.link(EntityRef::new(EntityKind::Event, ev), bank)
+                .unwrap();
+            events.push(ev);
+        }
+
+        let in_session: Vec<_> = db
+            .session_events(id)
+            .unwrap()
+            .into_iter()
+            .map(|e| e.id)
+            .collect();
+        assert_eq!(in_session, vec![events[1], events[0]]);
+        let participants = db.session_participants(id, Audience::GameMaster).unwrap();
+        assert_eq!(participants.len(), 1);
+        assert_eq!(participants[0].entity, bank);
+
+        db.set_event_session(events[0], None).unwrap();
+        assert_eq!(db.session_events(id).unwrap().len(), 1);
+        db.delete_session(id).unwrap();
+        assert_eq!(db.get_event(events[1]).unwrap().unwrap().session, None);
+    }
+}
@@ -0,0 +1,244 @@
+use super::{
+    mapping_table, search_key, Audience, EntityKind, EntityRef, Linked, Schema, Tag, MAPPINGS,
+};
+use crate::schema::{Result, WithId};
+
+/// A tag together with the number of entities it is attached to
+#[derive(Clone, Debug, PartialEq)]
+pub struct TagCount {
+    pub id: i64,
+    pub name: String,
+    pub count: i64,
+}
+
+impl Schema {
+    /// All tags with their usage counts, sorted by name. Trashed entities are not counted. For
+    /// the players, neither are secret entities and secret links, and tags without any other
+    /// uses are left out.
+    pub fn list_tags(&mut self, audience: Audience) -> Result<Vec<TagCount>> {
+        let (hidden, unused) = match audience {
+            Audience::GameMaster => ("", ""),
+            Audience::Players => (
+                " and m.secret == 0 and e.secret == 0",
+                " having count(u.tag) > 0",
+            ),
+        };
+        let uses = tag_mappings()
+            .map(|(from, table)| {
+                format!(
+                    "select m.kto as tag from {table} m join {} e on e.id == m.kfrom
+                     where e.deleted_at is null{hidden}",
+                    from.table()
+                )
+            })
+            .collect::<Vec<_>>()
+            .join(" union all ");
+        let query = format!(
+            "select t.id as id, t.name as name, count(u.tag) as count
+             from tags t left join ({uses}) u on u.tag == t.id
+             where t.deleted_at is null
+             group by t.id{unused} order by t.name_key, t.name;"
+        );
+        self.conn
+            .prepare(query)?
+            .into_iter()
+            .map(|r| {
+                let r = r?;
+                Ok(TagCount {
+                    id: r.read::<i64, _>("id"),
+                    name: r.read::<&str, _>("name").to_string(),
+                    count: r.read::<i64, _>("count"),
+                })
+            })
+            .collect()
+    }
+
+    /// Looks up a tag by name, ignoring case and diacritics. A leading `#` is ignored.
+    pub fn get_tag_by_name(&mut self, name: &str) -> Result<Option<WithId<Tag>>> {
+        let mut stmt = self
+            .conn
+            .prepare("select id, name from tags where name_key == ? and deleted_at is null;")?;
+        stmt.bind((1, search_key(tag_name(name)).as_str()))?;
+        if stmt.next()? != sqlite::State::Row {
+            return Ok(None);
+        }
+        Ok(Some(WithId {
+            t: Tag {
+                name: stmt.read::<String, _>("name")?,
+            },
+            id: stmt.read::<i64, _>("id")?,
+        }))
+    }
+
+    /// The tags attached to `x`, sorted by name
+    pub fn tags_of(&mut self, x: EntityRef) -> Result<Vec<Linked>> {
+        Ok(self
+            .outgoing_links(x)?
+            .into_iter()
+            .filter(|l| l.entity.kind == EntityKind::Tag)
+            .collect())
+    }
+
+    /// Attaches the tag called `name` to `x`, creating the tag if there is none with that name
+    /// yet. Returns the id of the tag.
+    pub fn add_tag(&mut self, x: EntityRef, name: &str) -> Result<i64> {
+        let id = match self.get_tag_by_name(name)? {
+            Some(tag) => tag.id,
+            None => self.insert_tag(&Tag {
+                name: tag_name(name).to_string(),
+            })?,
+        };
+        self.link(x, EntityRef::new(EntityKind::Tag, id))?;
+        Ok(id)
+    }
+
+    pub fn remove_tag(&mut self, x: EntityRef, tag: i64) -> Result<()> {
+        self.unlink(x, EntityRef::new(EntityKind::Tag, tag))
+    }
+
+    /// Attaches `into` to everything tagged with `from`, and deletes `from`
+    pub fn merge_tags(&mut self, from: i64, into: i64) -> Result<()> {
+        if from == into {
+            return Ok(());
+        }
+        self.in_transaction(|db| db.merge_tags_in_transaction(from, into))
+    }
+
+    fn merge_tags_in_transaction(&mut self, from: i64, into: i64) -> Result<()> {
+        for (_, table) in tag_mappings() {
+            // a link made by hand, or visible to the players, wins over one that isn't
+            let mut stmt = self.conn.prepare(format!(
+                "insert into {table} (kfrom, kto, wiki, secret)
+                 select kfrom, ?, wiki, secret from {table} where kto == ?
+                 on conflict do update set wiki = min(wiki, excluded.wiki),
+                     secret = min(secret, excluded.secret);"
+            ))?;
+            stmt.bind((1, into))?;
+            stmt.bind((2, from))?;
+            assert!(stmt.next()? == sqlite::State::Done);
+        }
+        self.delete_tag(from)
+    }
+
+    /// Entities that have all of the named tags, sorted by kind and label. Empty if one of the
+    /// tags doesn't exist. For the players, secret tags and secret entities don't count.
+    pub fn entities_with_tags<S: AsRef<str>>(
+        &mut self,
+        names: &[S],
+        audience: Audience,
+    ) -> Result<Vec<Linked>> {
+        let mut res: Option<Vec<Linked>> = None;
+        for name in names {
+            let Some(tag) = self.get_tag_by_name(name.as_ref())? else {
+                return Ok(vec![]);
+            };
+            let tagged = self
+                .incoming_links(EntityRef::new(EntityKind::Tag, tag.id))?
+                .into_iter()
+                .filter(|l| audience.sees(l.secret))
+                .collect::<Vec<_>>();
+            res = Some(match res {
+                None => tagged,
+                Some(prev) => prev
+                    .into_iter()
+                    .filter(|l| tagged.iter().any(|t| t.entity == l.entity))
+                    .collect(),
+            });
+        }
+        Ok(res.unwrap_or_default())
+    }
+}
+
+/// The kinds that can be tagged, with their mapping tables
+fn tag_mappings() -> impl Iterator<Item = (EntityKind, String)> {
+    MAPPINGS
+        .into_iter()
+        .filter(|(_, to)| *to == EntityKind::Tag)
+        .map(|(from, to)| (from, mapping_table(from, to).unwrap()))
+}
+
+fn tag_name(name: &str) -> &str {
+    name.trim().trim_start_matches('#').trim()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::schema::v1::{Place, Subject};
+
+    #[test]
+    fn tags_can_be_attached_counted_and_merged() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut db = Schema::create_new(dir.path().join("test.db")).unwrap();
+        db.insert_subject(&Subject {
+            name: "Vex".into(),
+            description: "".into(),
+        })
+        .unwrap();
+        let vex = EntityRef::new(
+            EntityKind::Subject,
+            db.get_sub_by_name("Vex").unwrap().unwrap().id,
+        );
+        let lair = EntityRef::new(
+            EntityKind::Place,
+            db.insert_place(&Place {
+                name: "Lair".into(),
+                description: "".into(),
+                parent_place: None,
+            })
+            .unwrap(),
+        );
+
+        let villain = db.add_tag(vex, "#Villain").unwrap();
+        assert_eq!(db.add_tag(lair, "villain").unwrap(), villain);
+        let evil = db.add_tag(lair, "evil").unwrap();
+
+        let counts = db
+            .list_tags(Audience::GameMaster)
+            .unwrap()
+            .into_iter()
+            .map(|t| (t.name, t.count))
+            .collect::<Vec<_>>();
+        assert_eq!(counts, vec![("evil".into(), 1), ("Villain".into(), 2)]);
+
+        let gm = Audience::GameMaster;
+        let both = db.entities_with_tags(&["villain", "evil"], gm).unwrap();
+        assert_eq!(both.len(), 1);
+        assert_eq!(both[0].entity, lair);
+        assert!(db.entities_with_tags(&["unknown"], gm).unwrap().is_empty());
+
+        // the players neither find nor count secret entities by their tags
+        db.set_secret(lair, true).unwrap();
+        let villains = |db: &mut Schema, audience| {
+            db.entities_with_tags(&["villain"], audience)
+                .unwrap()
+                .into_iter()
+                .map(|l| l.entity)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(villains(&mut db, gm), vec![vex, lair]);
+        assert_eq!(villains(&mut db, Audience::Players), vec![vex]);
+        let tag_counts = |db: &mut Schema, audience| {
+            db.list_tags(audience)
+                .unwrap()
+                .into_iter()
+                .map(|t| (t.name, t.count))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            tag_counts(&mut db, gm),
+            vec![("evil".into(), 1), ("Villain".into(), 2)]
+        );
+        assert_eq!(
+            tag_counts(&mut db, Audience::Players),
+            vec![("Villain".into(), 1)]
+        );
+        db.set_secret(lair, false).unwrap();
+
+        db.merge_tags(evil, villain).unwrap();
+        let tags = db.list_tags(gm).unwrap();
+        assert_eq!(tags.len(), 1);
+        assert_eq!(tags[0].count, 2);
+        assert_eq!(db.tags_of(lair).unwrap().len(), 1);
+    }
+}
@@ -0,0 +1,340 @@
+use super::{search_key, EntityKind, EntityRef, Field, FieldType, Schema};
+use crate::schema::{Error, Result, WithId};
+
+/// A starting point for new entities of one kind: a markdown skeleton for the description,
+/// custom fields and tags
+#[derive(Clone, Debug, PartialEq)]
+pub struct Template {
+    pub name: String,
+    pub kind: EntityKind,
+    pub body: String,
+    pub fields: Vec<TemplateField>,
+    pub tags: Vec<String>,
+}
+
+/// A field that is added to entities created from a template. The value is kept as the user
+/// wrote it, and only parsed when the template is applied, so references can point to entities
+/// by name.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TemplateField {
+    pub name: String,
+    pub field_type: FieldType,
+    pub value: String,
+}
+
+impl Schema {
+    /// All templates for entities of the given kind, sorted by name
+    pub fn list_templates(&mut self, kind: EntityKind) -> Result<Vec<WithId<Template>>> {
+        let ids = self
+            .conn
+            .prepare("select id from templates where kind == ? order by name_key, name;")?
+            .into_iter()
+            .bind((1, kind.table()))?
+            .map(|r| Ok(r?.read::<i64, _>("id")))
+            .collect::<Result<Vec<_>>>()?;
+        let mut res = vec![];
+        for id in ids {
+            res.extend(self.get_template(id)?);
+        }
+        Ok(res)
+    }
+
+    pub fn get_template(&mut self, id: i64) -> Result<Option<WithId<Template>>> {
+        let mut stmt = self
+            .conn
+            .prepare("select * from templates where id == ?;")?;
+        stmt.bind((1, id))?;
+        if stmt.next()? != sqlite::State::Row {
+            return Ok(None);
+        }
+        let Some(kind) = EntityKind::from_table(&stmt.read::<String, _>("kind")?) else {
+            return Ok(None);
+        };
+        let name = stmt.read::<String, _>("name")?;
+        let body = stmt.read::<String, _>("body")?;
+        let tags = stmt
+            .read::<String, _>("tags")?
+            .lines()
+            .map(|t| t.to_string())
+            .collect();
+
+        let fields = self
+            .conn
+            .prepare("select * from template_fields where template_id == ? order by position;")?
+            .into_iter()
+            .bind((1, id))?
+            .filter_map(|r| {
+                let r = match r {
+                    Ok(r) => r,
+                    Err(e) => return Some(Err(e.into())),
+                };
+                Some(Ok(TemplateField {
+                    name: r.read::<&str, _>("name").to_string(),
+                    field_type: FieldType::from_name(r.read::<&str, _>("field_type"))?,
+                    value: r.read::<&str, _>("value").to_string(),
+                }))
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        Ok(Some(WithId {
+            t: Template {
+                name,
+                kind,
+                body,
+                fields,
+                tags,
+            },
+            id,
+        }))
+    }
+
+    /// Inserts the template and returns its id. Fails with [`Error::NameTaken`] if there
+    /// already is a template with that name for the same kind.
+    pub fn insert_template(&mut self, x: &Template) -> Result<i64> {
+        self.in_transaction(|db| db.insert_template_in_transaction(x))
+    }
+
+    fn insert_template_in_transaction(&mut self, x: &Template) -> Result<i64> {
+        self.check_template_name_free(x, None)?;
+        let mut stmt = self.conn.prepare(
+            "insert into templates (kind, name, name_key, body, tags) values (?, ?, ?, ?, ?);",
+        )?;
+        bind_template(&mut stmt, x)?;
+        assert!(stmt.next()? == sqlite::State::Done);
+        let id = self.last_insert_id()?;
+        self.insert_template_fields(id, &x.fields)?;
+        Ok(id)
+    }
+
+    pub fn update_template(&mut self, x: &WithId<Template>) -> Result<()> {
+        self.in_transaction(|db| db.update_template_in_transaction(x))
+    }
+
+    fn update_template_in_transaction(&mut self, x: &WithId<Template>) -> Result<()> {
+        self.check_template_name_free(x, Some(x.id))?;
+        let mut stmt = self.conn.prepare(
+            "update templates set kind = ?, name = ?, name_key = ?, body = ?, tags = ?
+             where id == ?;",
+        )?;
+        bind_template(&mut stmt, x)?;
+        stmt.bind((6, x.id))?;
+        assert!(stmt.next()? == sqlite::State::Done);
+        self.delete_template_fields(x.id)?;
+        self.insert_template_fields(x.id, &x.fields)
+    }
+
+    pub fn delete_template(&mut self, id: i64) -> Result<()> {
+        self.in_transaction(|db| {
+            db.delete_template_fields(id)?;
+            let mut stmt = db.conn.prepare("delete from templates where id == ?;")?;
+            stmt.bind((1, id))?;
+            assert!(stmt.next()? == sqlite::State::Done);
+            Ok(())
+        })
+    }
+
+    /// Replaces the description of `x` by the body of the template, with the links made from it,
+    /// and adds the template's fields and tags. Text fields without a value are added empty, other fields without a value
+    /// are left out rather than set to a made up 0 or "no".
+    pub fn apply_template(&mut self, x: EntityRef, template: &Template) -> Result<()> {
+        self.in_transaction(|db| db.apply_template_in_transaction(x, template))
+    }
+
+    fn apply_template_in_transaction(&mut self, x: EntityRef, template: &Template) -> Result<()> {
+        self.set_description(x, &template.body)?;
+        for f in &template.fields {
+            let value = match (f.field_type, f.value.trim()) {
+                (t, "") if t != FieldType::Text => continue,
+                (t, value) => self.parse_field_value(&f.name, t, value)?,
+            };
+            self.set_field(
+                x,
+                &Field {
+                    name: f.name.clone(),
+                    value,
+                },
+            )?;
+        }
+        for tag in &template.tags {
+            self.add_tag(x, tag)?;
+        }
+        Ok(())
+    }
+
+    fn check_template_name_free(&mut self, x: &Template, except: Option<i64>) -> Result<()> {
+        let mut stmt = self.conn.prepare(
+            "select count(*) from templates where kind == ? and name_key == ? and id != ?;",
+        )?;
+        stmt.bind((1, x.kind.table()))?;
+        stmt.bind((2, search_key(&x.name).as_str()))?;
+        stmt.bind((3, except.unwrap_or(-1)))?;
+        assert!(stmt.next()? == sqlite::State::Row);
+        if stmt.read::<i64, _>(0)? > 0 {
+            Err(Error::NameTaken(
+                format!("{} template", x.kind),
+                x.name.clone(),
+            ))
+        } else {
+            Ok(())
+        }
+    }
+
+    fn insert_template_fields(&mut self, template: i64, fields: &[TemplateField]) -> Result<()> {
+        let mut stmt = self.conn.prepare(
+            "insert into template_fields (template_id, position, name, field_type, value)
+             values (?, ?, ?, ?, ?);",
+        )?;
+        for (i, f) in fields.iter().enumerate() {
+            stmt.reset()?;
+            stmt.bind((1, template))?;
+            stmt.bind((2, i as i64))?;
+            stmt.bind((3, f.name.as_str()))?;
+            stmt.bind((4, f.field_type.as_str()))?;
+            stmt.bind((5, f.value.as_str()))?;
+            assert!(stmt.next()? == sqlite::State::Done);
+        }
+        Ok(())
+    }
+
+    fn delete_template_fields(&mut self, template: i64) -> Result<()> {
+        let mut stmt = self
+            .conn
+            .prepare("delete from template_fields where template_id == ?;")?;
+        stmt.bind((1, template))?;
+        assert!(stmt.next()? == sqlite::State::Done);
+        Ok(())
+    }
+}
+
+fn bind_template(stmt: &mut sqlite::Statement, x: &Template) -> Result<()> {
+    stmt.bind((1, x.kind.table()))?;
+    stmt.bind((2, x.name.as_str()))?;
+    stmt.bind((3, search_key(&x.name).as_str()))?;
+    stmt.bind((4, x.body.as_str()))?;
+    stmt.bind((5, x.tags.join("\n").as_str()))?;
+    Ok(())
+}
+
+/// Parses one field per line, written as `Name: type = value`. The type defaults to text, and
+/// the value to nothing, so `Name`, `Name: number` and `Name = value` are fine as well.
+pub fn parse_template_fields(text: &str) -> Result<Vec<TemplateField>> {
+    let mut res = vec![];
+    for line in text.lines().map(str::trim).filter(|l| !l.is_empty()) {
+        let (head, value) = line.split_once('=').unwrap_or((line, ""));
+        let (name, field_type) = match head.split_once(':') {
+            Some((name, t)) => {
+                let t = t.trim();
+                let field_type = FieldType::from_name(&t.to_lowercase())
+                    .ok_or_else(|| Error::UnknownFieldType(t.to_string()))?;
+                (name, field_type)
+            }
+            None => (head, FieldType::Text),
+        };
+        res.push(TemplateField {
+            name: name.trim().to_string(),
+            field_type,
+            value: value.trim().to_string(),
+        });
+    }
+    Ok(res)
+}
+
+/// The inverse of [`parse_template_fields`]
+pub fn format_template_fields(fields: &[TemplateField]) -> String {
+    fields
+        .iter()
+        .map(|f| {
+            let mut line = format!("{}: {}", f.name, f.field_type.as_str());
+            if !f.value.is_empty() {
+                line.push_str(&format!(" = {}", f.value));
+            }
+            line
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::schema::v1::{FieldValue, Subject};
+
+    #[test]
+    fn template_fields_round_trip() {
+        let fields =
+            parse_template_fields("Age: number = 30\n\nAlive: Boolean = yes\nRace\n").unwrap();
+        assert_eq!(fields.len(), 3);
+        assert_eq!(fields[1].field_type, FieldType::Boolean);
+        assert_eq!(fields[2].field_type, FieldType::Text);
+        assert_eq!(
+            parse_template_fields(&format_template_fields(&fields)).unwrap(),
+            fields
+        );
+        assert!(parse_template_fields("HP: hitpoints").is_err());
+    }
+
+    #[test]
+    fn built_in_templates_can_be_applied() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut db = Schema::create_new(dir.path().join("test.db")).unwrap();
+        let npc = db
+            .list_templates(EntityKind::Subject)
+            .unwrap()
+            .into_iter()
+            .find(|t| t.name == "NPC")
+            .unwrap();
+        assert_eq!(db.list_templates(EntityKind::Place).unwrap().len(), 3);
+
+        db.insert_subject(&Subject {
+            name: "Mira".into(),
+            description: "".into(),
+        })
+        .unwrap();
+        let id = db.get_sub_by_name("Mira").unwrap().unwrap().id;
+        let mira = EntityRef::new(EntityKind::Subject, id);
+        db.apply_template(mira, &npc).unwrap();
+
+        assert_eq!(db.get_subject(id).unwrap().unwrap().description, npc.body);
+        // the age has no default, so it isn't set to 0
+        let fields = db.fields_of(mira).unwrap();
+        let field = |name: &str| fields.iter().find(|f| f.name == name).map(|f| &f.value);
+        assert_eq!(field("Age"), None);
+        assert_eq!(field("Alive"), Some(&FieldValue::Boolean(true)));
+        assert_eq!(
+            fields.len(),
+            npc.fields
+                .iter()
+                .filter(|f| f.field_type == FieldType::Text || !f.value.is_empty())
+                .count()
+        );
+        assert_eq!(db.tags_of(mira).unwrap().len(), npc.tags.len());
+    }
+
+    #[test]
+    fn a_template_that_doesnt_apply_leaves_no_entity_behind() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut db = Schema::create_new(dir.path().join("test.db")).unwrap();
+        let template = Template {
+            name: "Horde".into(),
+            kind: EntityKind::Subject,
+            body: "".into(),
+            fields: parse_template_fields("Size: number = many").unwrap(),
+            tags: vec![],
+        };
+        let id = db.insert_template(&template).unwrap();
+        let template = db.get_template(id).unwrap().unwrap();
+
+        let res = db.in_transaction(|db| {
+            let id = db.insert_subject(&Subject {
+                name: "Orcs".into(),
+                description: "".into(),
+            })?;
+            db.apply_template(EntityRef::new(EntityKind::Subject, id), &template)
+        });
+        assert!(matches!(res, Err(Error::InvalidFieldValue { .. })));
+        assert!(db.get_sub_by_name("Orcs").unwrap().is_none());
+
+        db.delete_template(id).unwrap();
+        assert!(db.get_template(id).unwrap().is_none());
+    }
+}
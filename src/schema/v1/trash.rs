@@ -0,0 +1,170 @@
+use super::{unix_now, EntityKind, EntityRef, Linked, Schema};
+use crate::schema::Result;
+
+/// An entity that was moved to the trash
+#[derive(Clone, Debug, PartialEq)]
+pub struct TrashEntry {
+    pub entity: EntityRef,
+    pub label: String,
+    /// unix timestamp
+    pub deleted_at: i64,
+}
+
+impl Schema {
+    /// Moves the entity to the trash. It disappears from all lists and searches, but it is kept
+    /// together with all its links, until the trash is emptied, so it can be restored.
+    pub fn trash(&mut self, x: EntityRef) -> Result<()> {
+        self.set_deleted_at(x, Some(unix_now()))
+    }
+
+    /// Takes the entity out of the trash. Fails with [`crate::schema::Error::NameTaken`] if
+    /// another entity got its name in the meantime.
+    pub fn restore(&mut self, x: EntityRef) -> Result<()> {
+        if x.kind != EntityKind::Event {
+            let mut stmt = self.conn.prepare(format!(
+                "select name from {} where id == ?;",
+                x.kind.table()
+            ))?;
+            stmt.bind((1, x.id))?;
+            let name = match stmt.next()? {
+                sqlite::State::Row => Some(stmt.read::<String, _>(0)?),
+                sqlite::State::Done => None,
+            };
+            drop(stmt);
+            if let Some(name) = name {
+                self.check_name_free(x.kind, &name, Some(x.id))?;
+            }
+        }
+        self.set_deleted_at(x, None)
+    }
+
+    /// All trashed entities, the most recently deleted first
+    pub fn list_trash(&mut self) -> Result<Vec<TrashEntry>> {
+        let mut res = vec![];
+        for kind in EntityKind::ALL {
+            let query = format!(
+                "select id, {label} as label, deleted_at from {table}
+                 where deleted_at is not null;",
+                label = kind.label_column(),
+                table = kind.table()
+            );
+            for r in self.conn.prepare(query)?.into_iter() {
+                let r = r?;
+                res.push(TrashEntry {
+                    entity: EntityRef::new(kind, r.read::<i64, _>("id")),
+                    label: r.read::<Option<&str>, _>("label").unwrap_or("").to_string(),
+                    deleted_at: r.read::<i64, _>("deleted_at"),
+                });
+            }
+        }
+        res.sort_by(|a, b| b.deleted_at.cmp(&a.deleted_at));
+        Ok(res)
+    }
+
+    /// The links that are removed when the entity is deleted for good
+    pub fn links_removed_on_delete(&mut self, x: EntityRef) -> Result<Vec<Linked>> {
+        let mut res = self.outgoing_links(x)?;
+        res.extend(self.incoming_links(x)?);
+        Ok(res)
+    }
+
+    /// Deletes the entity and all its links for good, without going through the trash
+    pub fn delete_permanently(&mut self, x: EntityRef) -> Result<()> {
+        match x.kind {
+            EntityKind::Subject => self.delete_subject(x.id),
+            EntityKind::Place => self.delete_place(x.id),
+            EntityKind::Group => self.delete_group(x.id),
+            EntityKind::Event => self.delete_event(x.id),
+            EntityKind::Tag => self.delete_tag(x.id),
+        }
+    }
+
+    /// Permanently deletes everything in the trash
+    pub fn empty_trash(&mut self) -> Result<()> {
+        self.in_transaction(|db| {
+            db.list_trash()?
+                .into_iter()
+                .try_for_each(|e| db.delete_permanently(e.entity))
+        })
+    }
+
+    fn set_deleted_at(&mut self, x: EntityRef, ts: Option<i64>) -> Result<()> {
+        let mut stmt = self.conn.prepare(format!(
+            "update {} set deleted_at = ? where id == ?;",
+            x.kind.table()
+        ))?;
+        stmt.bind((1, ts))?;
+        stmt.bind((2, x.id))?;
+        assert!(stmt.next()? == sqlite::State::Done);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::schema::v1::{Field, FieldValue, Place, Subject};
+    use crate::schema::Error;
+
+    fn subject(db: &mut Schema, name: &str) -> EntityRef {
+        let id = db
+            .insert_subject(&Subject {
+                name: name.into(),
+                description: "".into(),
+            })
+            .unwrap();
+        EntityRef::new(EntityKind::Subject, id)
+    }
+
+    #[test]
+    fn trash_keeps_links_until_it_is_emptied() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut db = Schema::create_new(dir.path().join("test.db")).unwrap();
+        let mira = subject(&mut db, "Mira");
+        let anchor = db
+            .insert_place(&Place {
+                name: "The Rusty Anchor".into(),
+                description: "".into(),
+                parent_place: None,
+            })
+            .unwrap();
+        let anchor = EntityRef::new(EntityKind::Place, anchor);
+        db.link(mira, anchor).unwrap();
+        db.set_field(
+            mira,
+            &Field {
+                name: "HP".into(),
+                value: FieldValue::Number(12.0),
+            },
+        )
+        .unwrap();
+
+        db.trash(mira).unwrap();
+        assert!(db.get_sub_by_name("Mira").unwrap().is_none());
+        let trash = db.list_trash().unwrap();
+        assert_eq!(trash.len(), 1);
+        assert_eq!((trash[0].entity, trash[0].label.as_str()), (mira, "Mira"));
+
+        // the name is free while Mira is in the trash
+        let other = subject(&mut db, "mira");
+        assert!(matches!(db.restore(mira), Err(Error::NameTaken(..))));
+        db.delete_permanently(other).unwrap();
+        db.restore(mira).unwrap();
+        assert!(db.get_sub_by_name("Mira").unwrap().is_some());
+        assert!(db.is_linked(mira, anchor).unwrap());
+        assert!(db.list_trash().unwrap().is_empty());
+
+        db.trash(anchor).unwrap();
+        let removed = db.links_removed_on_delete(anchor).unwrap();
+        assert_eq!(removed.len(), 1);
+        assert_eq!(removed[0].entity, mira);
+        db.empty_trash().unwrap();
+        assert!(db.list_trash().unwrap().is_empty());
+        assert!(db.get_place(anchor.id).unwrap().is_none());
+        assert!(!db.is_linked(mira, anchor).unwrap());
+
+        db.delete_permanently(mira).unwrap();
+        assert!(db.fields_of(mira).unwrap().is_empty());
+        assert!(db.get_sub_by_name("Mira").unwrap().is_none());
+    }
+}
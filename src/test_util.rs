@@ -0,0 +1,13 @@
+//! Helpers shared by the tests of several modules
+use std::io::Cursor;
+
+use image::{DynamicImage, ImageOutputFormat, RgbImage};
+
+/// A black png image of the given size
+pub fn png(width: u32, height: u32) -> Vec<u8> {
+    let mut data = vec![];
+    DynamicImage::ImageRgb8(RgbImage::new(width, height))
+        .write_to(&mut Cursor::new(&mut data), ImageOutputFormat::Png)
+        .unwrap();
+    data
+}
@@ -0,0 +1,1572 @@
+//! Markdown vaults, the format of Obsidian and similar note taking apps: a folder with a `.md`
+//! file for every subject, place, group and event, that link to each other with `[[wikilinks]]`.
+//! Everything that isn't part of the description, like tags, custom fields and relationships,
+//! goes into the YAML front matter at the top of a note.
+//!
+//! Vaults can also be imported, see [`import_vault`]. Notes written by hand don't need to follow
+//! the layout of an export, the importer makes do with file names, folders and whatever it finds
+//! in the front matter, and reports what it couldn't place.
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+use std::result::Result as StdResult;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use yaml_rust2::YamlLoader;
+
+use crate::export_dir::ExportDir;
+use crate::images;
+use crate::schema::{
+    v1::{
+        mapping_table, search_key, Audience, EntityKind, EntityRef, Event, Field, FieldValue,
+        Group, Place, Relationship, Subject,
+    },
+    Error, Result,
+};
+use crate::wiki::{self, parse_links, split_secrets};
+use crate::Schema;
+
+/// The folder of every kind of entity in a vault
+pub const FOLDERS: [(EntityKind, &str); 4] = [
+    (EntityKind::Subject, "Subjects"),
+    (EntityKind::Place, "Places"),
+    (EntityKind::Group, "Groups"),
+    (EntityKind::Event, "Events"),
+];
+
+/// The folder images are copied to
+pub const ATTACHMENTS: &str = "Attachments";
+
+/// Front matter keys with a meaning of their own. Custom fields with one of these names are
+/// written below `fields` instead of at the top level.
+pub const RESERVED_KEYS: [&str; 14] = [
+    "kind",
+    "type",
+    "secret",
+    "tags",
+    "aliases",
+    "parent",
+    "date",
+    "session",
+    "relationships",
+    "links",
+    "portrait",
+    "images",
+    "map",
+    "fields",
+];
+
+/// What [`export_vault`] has written
+#[derive(Clone, Debug, PartialEq)]
+pub struct VaultSummary {
+    pub notes: usize,
+    pub images: usize,
+}
+
+/// Writes the vault into `dir`, which is created if it doesn't exist. The notes and images of an
+/// earlier export into `dir` are removed first, see [`ExportDir`].
+pub fn export_vault(db: &mut Schema, dir: &Path, audience: Audience) -> Result<VaultSummary> {
+    let mut exporter = VaultExporter::new(db, dir, audience)?;
+    exporter.write_all()?;
+    Ok(VaultSummary {
+        notes: exporter.notes.len(),
+        images: exporter.images.len(),
+    })
+}
+
+struct VaultExporter<'a> {
+    db: &'a mut Schema,
+    out: ExportDir<'a>,
+    audience: Audience,
+    /// Everything that gets a note, in a stable order
+    entries: Vec<EntityRef>,
+    /// Path of every note relative to `dir`, without the `.md`
+    notes: HashMap<EntityRef, String>,
+    /// What goes between the brackets of a wikilink to a note. Just the file name, unless
+    /// notes of different kinds have the same one.
+    link_targets: HashMap<EntityRef, String>,
+    /// File name of every image that was copied
+    images: HashMap<i64, String>,
+}
+
+impl<'a> VaultExporter<'a> {
+    fn new(db: &'a mut Schema, dir: &'a Path, audience: Audience) -> Result<Self> {
+        let mut candidates = vec![];
+        for kind in [EntityKind::Subject, EntityKind::Place, EntityKind::Group] {
+            for x in db.query_names(kind, "")? {
+                candidates.push((EntityRef::new(kind, x.id), x.t));
+            }
+        }
+        for ev in db.events_in_world_order()? {
+            candidates.push((EntityRef::new(EntityKind::Event, ev.id), ev.t.refered_date));
+        }
+
+        let mut entries = vec![];
+        let mut stems = HashMap::new();
+        let mut taken = HashSet::new();
+        for (entity, label) in candidates {
+            if !db.is_visible(entity, audience)? {
+                continue;
+            }
+            let mut stem = file_stem(&label);
+            if stem.is_empty() {
+                stem = format!("{} {}", entity.kind, entity.id);
+            }
+            // events don't need distinct dates, and names that only differ in characters
+            // that can't be used in file names end up the same
+            if !taken.insert((entity.kind, stem.to_lowercase())) {
+                stem = format!("{stem} ({})", entity.id);
+            }
+            entries.push(entity);
+            stems.insert(entity, stem);
+        }
+
+        let mut counts: HashMap<String, usize> = HashMap::new();
+        for stem in stems.values() {
+            *counts.entry(stem.to_lowercase()).or_default() += 1;
+        }
+        let mut notes = HashMap::new();
+        let mut link_targets = HashMap::new();
+        for (entity, stem) in stems {
+            let path = format!("{}/{stem}", folder(entity.kind));
+            let target = match counts[&stem.to_lowercase()] {
+                1 => stem,
+                _ => path.clone(),
+            };
+            notes.insert(entity, path);
+            link_targets.insert(entity, target);
+        }
+
+        Ok(Self {
+            db,
+            out: ExportDir::open(dir)?,
+            audience,
+            entries,
+            notes,
+            link_targets,
+            images: HashMap::new(),
+        })
+    }
+
+    fn write_all(&mut self) -> Result<()> {
+        self.out.create_dir(ATTACHMENTS)?;
+        for (_, name) in FOLDERS {
+            self.out.create_dir(name)?;
+        }
+        for entity in self.entries.clone() {
+            let note = format!("{}{}", self.front_matter(entity)?, self.body(entity)?);
+            let path = format!("{}.md", self.notes[&entity]);
+            self.out.write(&path, note)?;
+        }
+        Ok(())
+    }
+
+    /// A wikilink to the note of `entity` that shows `label`
+    fn wikilink(&self, entity: EntityRef, label: &str) -> Option<String> {
+        let target = self.link_targets.get(&entity)?;
+        if target == label {
+            Some(format!("[[{target}]]"))
+        } else {
+            Some(format!("[[{target}|{label}]]"))
+        }
+    }
+
+    /// A wikilink to `entity` for the front matter, with its name as the label
+    fn property_link(&mut self, entity: EntityRef) -> Result<Option<String>> {
+        let Some(label) = self.db.entity_label(entity)? else {
+            return Ok(None);
+        };
+        Ok(self.wikilink(entity, &label).map(|l| yaml_string(&l)))
+    }
+
+    fn front_matter(&mut self, entity: EntityRef) -> Result<String> {
+        let mut res = String::from("---\n");
+        res.push_str(&format!(
+            "kind: {}\n",
+            entity.kind.to_string().to_lowercase()
+        ));
+        if self.audience == Audience::GameMaster && self.db.is_secret(entity)? {
+            res.push_str("secret: true\n");
+        }
+
+        let tags: Vec<_> = self
+            .db
+            .tags_of(entity)?
+            .into_iter()
+            .filter(|t| self.audience.sees(t.secret))
+            .map(|t| t.label)
+            .collect();
+        if !tags.is_empty() {
+            res.push_str("tags:\n");
+            for tag in tags {
+                // Obsidian doesn't allow spaces in tags
+                let tag = tag.split_whitespace().collect::<Vec<_>>().join("-");
+                res.push_str(&format!("  - {}\n", yaml_string(&tag)));
+            }
+        }
+
+        let parent = match entity.kind {
+            EntityKind::Place => self
+                .db
+                .get_place(entity.id)?
+                .and_then(|p| p.t.parent_place)
+                .map(|id| EntityRef::new(EntityKind::Place, id)),
+            EntityKind::Group => self
+                .db
+                .get_group(entity.id)?
+                .and_then(|g| g.t.parent_group)
+                .map(|id| EntityRef::new(EntityKind::Group, id)),
+            _ => None,
+        };
+        if let Some(parent) = parent {
+            if let Some(link) = self.property_link(parent)? {
+                res.push_str(&format!("parent: {link}\n"));
+            }
+        }
+
+        if entity.kind == EntityKind::Event {
+            if let Some(ev) = self.db.get_event(entity.id)? {
+                res.push_str(&format!("date: {}\n", yaml_string(&ev.t.refered_date)));
+                if let Some(session) = ev.t.session {
+                    if let Some(session) = self.db.get_session(session)? {
+                        res.push_str(&format!("session: {}\n", session.t.number));
+                    }
+                }
+            }
+        }
+
+        let mut links = vec![];
+        if entity.kind == EntityKind::Subject {
+            res.push_str(&self.relationships(entity.id, &mut links)?);
+        }
+        for l in self.db.outgoing_links(entity)? {
+            // links between subjects are relationships
+            if l.entity.kind == EntityKind::Tag
+                || l.entity.kind == EntityKind::Subject && entity.kind == EntityKind::Subject
+                || !self.audience.sees(l.secret)
+            {
+                continue;
+            }
+            if let Some(link) = self.wikilink(l.entity, &l.label) {
+                links.push(link);
+            }
+        }
+        if !links.is_empty() {
+            res.push_str("links:\n");
+            for link in links {
+                res.push_str(&format!("  - {}\n", yaml_string(&link)));
+            }
+        }
+
+        res.push_str(&self.images(entity)?);
+        res.push_str(&self.fields(entity)?);
+        res.push_str("---\n");
+        Ok(res)
+    }
+
+    /// The relationships that start at the subject. Plain links are added to `links` instead.
+    fn relationships(&mut self, subject: i64, links: &mut Vec<String>) -> Result<String> {
+        let mut res = String::new();
+        for rel in self.db.relationships_of(subject)? {
+            let other = EntityRef::new(EntityKind::Subject, rel.other_id);
+            if rel.incoming || !self.audience.sees(rel.rel.secret) {
+                continue;
+            }
+            let Some(link) = self.wikilink(other, &rel.other_name) else {
+                continue;
+            };
+            if rel.rel.label.is_empty() {
+                links.push(link);
+                continue;
+            }
+            if res.is_empty() {
+                res.push_str("relationships:\n");
+            }
+            let r = &rel.rel.t;
+            res.push_str(&format!("  - label: {}\n", yaml_string(&r.label)));
+            res.push_str(&format!("    target: {}\n", yaml_string(&link)));
+            if let Some(inverse) = &r.inverse_label {
+                res.push_str(&format!("    inverse: {}\n", yaml_string(inverse)));
+            }
+            if !r.note.is_empty() {
+                res.push_str(&format!("    note: {}\n", yaml_string(&r.note)));
+            }
+            if let Some(from) = &r.valid_from {
+                res.push_str(&format!("    since: {}\n", yaml_string(from)));
+            }
+            if let Some(to) = &r.valid_to {
+                res.push_str(&format!("    until: {}\n", yaml_string(to)));
+            }
+            if r.secret {
+                res.push_str("    secret: true\n");
+            }
+        }
+        Ok(res)
+    }
+
+    /// The portrait, the other images, and the map of a place, as links to the copied files
+    fn images(&mut self, entity: EntityRef) -> Result<String> {
+        let mut res = String::new();
+        let mut gallery = vec![];
+        for a in self.db.attachments_of(entity)? {
+            let Some(file) = self.image_file(a.id, &a.file_name)? else {
+                continue;
+            };
+            let link = yaml_string(&format!("[[{file}]]"));
+            if a.portrait {
+                res.push_str(&format!("portrait: {link}\n"));
+            } else {
+                gallery.push(link);
+            }
+        }
+        if !gallery.is_empty() {
+            res.push_str("images:\n");
+            for link in gallery {
+                res.push_str(&format!("  - {link}\n"));
+            }
+        }
+        if entity.kind == EntityKind::Place {
+            if let Some(map) = self.db.place_map(entity.id)? {
+                if let Some(file) = self.image_file(map, "map")? {
+                    res.push_str(&format!("map: {}\n", yaml_string(&format!("[[{file}]]"))));
+                }
+            }
+        }
+        Ok(res)
+    }
+
+    /// The custom fields, at the top level unless their name is one of [`RESERVED_KEYS`].
+    /// References to entities without a note are left out.
+    fn fields(&mut self, entity: EntityRef) -> Result<String> {
+        let mut top = String::new();
+        let mut nested = String::new();
+        for field in self.db.fields_of(entity)? {
+            let value = match &field.t.value {
+                FieldValue::Text(s) | FieldValue::Date(s) => yaml_string(s),
+                FieldValue::Number(n) => n.to_string(),
+                FieldValue::Boolean(b) => b.to_string(),
+                FieldValue::Reference(r) => match self.property_link(*r)? {
+                    Some(link) => link,
+                    None => continue,
+                },
+            };
+            let line = format!("{}: {value}\n", yaml_key(&field.t.name));
+            if RESERVED_KEYS.contains(&field.t.name.to_lowercase().as_str()) {
+                nested.push_str(&format!("  {line}"));
+            } else {
+                top.push_str(&line);
+            }
+        }
+        if !nested.is_empty() {
+            top.push_str(&format!("fields:\n{nested}"));
+        }
+        Ok(top)
+    }
+
+    /// The description, with wikilinks that point to the notes, and secret blocks as callouts
+    fn body(&mut self, entity: EntityRef) -> Result<String> {
+        let text = self
+            .db
+            .visible_description(entity, self.audience)?
+            .unwrap_or_default();
+        let mut res = String::with_capacity(text.len());
+        let mut last = 0;
+        for link in parse_links(&text) {
+            res.push_str(&text[last..link.range.start]);
+            match self.db.resolve_name(&link.target)? {
+                // names may be written differently than the file name
+                Some(target) => match self.wikilink(target, link.display()) {
+                    Some(l) => res.push_str(&l),
+                    // an entity the players may not see
+                    None => res.push_str(link.display()),
+                },
+                None => res.push_str(&text[link.range.clone()]),
+            }
+            last = link.range.end;
+        }
+        res.push_str(&text[last..]);
+        Ok(secret_callouts(&res))
+    }
+
+    /// Copies the image once, and returns its file name
+    fn image_file(&mut self, id: i64, file_name: &str) -> Result<Option<String>> {
+        if let Some(name) = self.images.get(&id) {
+            return Ok(Some(name.clone()));
+        }
+        let Some((mime, data)) = self.db.attachment_data(id)? else {
+            return Ok(None);
+        };
+        let stem = file_name
+            .rsplit_once('.')
+            .map_or(file_name, |(stem, _)| stem);
+        let stem = match file_stem(stem) {
+            s if s.is_empty() => format!("image {id}"),
+            s => s,
+        };
+        let mut name = format!("{stem}.{}", images::extension(&mime));
+        if self.images.values().any(|n| n.eq_ignore_ascii_case(&name)) {
+            name = format!("{stem} ({id}).{}", images::extension(&mime));
+        }
+        self.out.write(&format!("{ATTACHMENTS}/{name}"), data)?;
+        self.images.insert(id, name.clone());
+        Ok(Some(name))
+    }
+}
+
+/// The folder notes of `kind` are written to
+pub fn folder(kind: EntityKind) -> &'static str {
+    FOLDERS
+        .iter()
+        .find(|(k, _)| *k == kind)
+        .map_or("Tags", |(_, name)| *name)
+}
+
+/// A file name without the characters that file systems or wikilinks don't allow
+fn file_stem(name: &str) -> String {
+    let res: String = name
+        .chars()
+        .map(|c| match c {
+            '\\' | '/' | ':' | '*' | '?' | '"' | '<' | '>' | '|' | '#' | '^' | '[' | ']' => '-',
+            c if c.is_control() => ' ',
+            c => c,
+        })
+        .collect();
+    res.trim_matches(|c: char| c == '.' || c.is_whitespace())
+        .to_string()
+}
+
+/// Turns secret blocks into `> [!secret]` callouts, which Obsidian shows as boxes
+fn secret_callouts(text: &str) -> String {
+    let mut res = String::with_capacity(text.len());
+    for section in split_secrets(text) {
+        if !section.secret {
+            res.push_str(section.text);
+            continue;
+        }
+        if !res.is_empty() && !res.ends_with('\n') {
+            res.push('\n');
+        }
+        res.push_str("> [!secret]\n");
+        for line in section.text.lines() {
+            match line.trim_end() {
+                "" => res.push_str(">\n"),
+                line => res.push_str(&format!("> {line}\n")),
+            }
+        }
+        // a line right after the callout would still belong to it
+        res.push('\n');
+    }
+    res
+}
+
+/// A key of the front matter, quoted if it isn't just words
+fn yaml_key(name: &str) -> String {
+    let plain = name.starts_with(|c: char| c.is_alphanumeric())
+        && name.ends_with(|c: char| c.is_alphanumeric())
+        && name
+            .chars()
+            .all(|c| c.is_alphanumeric() || c == ' ' || c == '_' || c == '-');
+    if plain {
+        name.to_string()
+    } else {
+        yaml_string(name)
+    }
+}
+
+/// A double quoted YAML string
+fn yaml_string(s: &str) -> String {
+    let mut res = String::with_capacity(s.len() + 2);
+    res.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => res.push_str("\\\""),
+            '\\' => res.push_str("\\\\"),
+            '\n' => res.push_str("\\n"),
+            '\t' => res.push_str("\\t"),
+            c if c.is_control() => res.push_str(&format!("\\u{:04x}", c as u32)),
+            c => res.push(c),
+        }
+    }
+    res.push('"');
+    res
+}
+
+/// What [`import_vault`] has done, and what it had to leave out
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ImportReport {
+    /// Number of subjects, places, groups and events that were created
+    pub created: usize,
+    pub images: usize,
+    /// Links and images that point to nothing, as the path of the note and the target
+    pub unresolved: Vec<(String, String)>,
+    /// Notes and properties that were not imported, as the path of the note and the reason
+    pub conflicts: Vec<(String, String)>,
+}
+
+/// Creates an entity for every `.md` file in `dir` and its subfolders, named like the file.
+/// The kind comes from the `kind` or `type` key of the front matter, or else from the name of
+/// a folder, and defaults to subject. Notes whose name is already taken are not imported, they
+/// end up in the report, and so does everything that can't be resolved. Everything is imported
+/// in one transaction, so an error leaves the campaign as it was.
+pub fn import_vault(db: &mut Schema, dir: &Path) -> Result<ImportReport> {
+    let mut files = vec![];
+    find_files(dir, &mut files)?;
+    let mut notes = vec![];
+    let mut image_files = HashMap::new();
+    for file in files {
+        let Some(name) = file.file_name().and_then(|n| n.to_str()) else {
+            continue;
+        };
+        if name.to_lowercase().ends_with(".md") {
+            notes.push(read_note(dir, &file)?);
+        } else if is_image(name) {
+            image_files.insert(name.to_lowercase(), file);
+        }
+    }
+    let event_names = notes
+        .iter()
+        .filter(|n| n.kind == EntityKind::Event)
+        .map(|n| n.name.to_lowercase())
+        .collect();
+
+    db.in_transaction(|db| {
+        let mut importer = VaultImporter {
+            db,
+            dir,
+            image_files,
+            event_names,
+            events: HashMap::new(),
+            report: ImportReport::default(),
+        };
+        let mut created = vec![];
+        for note in &notes {
+            let (description, images) = convert_body(&note.body, &importer.event_names);
+            if let Some(entity) = importer.create(note, &description)? {
+                created.push((note, entity, description, images));
+            }
+        }
+        for (note, entity, description, images) in created {
+            importer.fill(note, entity, &description, &images)?;
+        }
+        Ok(importer.report)
+    })
+}
+
+/// A markdown file of a vault
+struct Note {
+    /// Relative to the vault, for the report
+    path: String,
+    file: PathBuf,
+    /// The file name without `.md`
+    name: String,
+    kind: EntityKind,
+    front: Vec<(String, Yaml)>,
+    /// Why the front matter couldn't be read, the note is imported without it
+    front_error: Option<String>,
+    body: String,
+}
+
+struct VaultImporter<'a> {
+    db: &'a mut Schema,
+    dir: &'a Path,
+    /// Images anywhere in the vault by their lower case file name, which is how Obsidian finds
+    /// them
+    image_files: HashMap<String, PathBuf>,
+    /// Lower case names of the notes that become events. Events have no name in the campaign,
+    /// so links to them can't stay links.
+    event_names: HashSet<String>,
+    /// The events that were created, by the lower case name of their note
+    events: HashMap<String, EntityRef>,
+    report: ImportReport,
+}
+
+impl<'a> VaultImporter<'a> {
+    /// Creates the entity of a note with its description, or notes the conflict
+    fn create(&mut self, note: &Note, description: &str) -> Result<Option<EntityRef>> {
+        let name = note.name.clone();
+        let description = description.to_string();
+        let res = match note.kind {
+            EntityKind::Subject => self.db.insert_subject(&Subject { name, description }),
+            EntityKind::Place => self.db.insert_place(&Place {
+                name,
+                description,
+                parent_place: None,
+            }),
+            EntityKind::Group => self.db.insert_group(&Group {
+                name,
+                description,
+                parent_group: None,
+            }),
+            EntityKind::Event | EntityKind::Tag => {
+                let refered_date = get(&note.front, "date")
+                    .and_then(Yaml::scalar)
+                    .filter(|d| !d.is_empty())
+                    .unwrap_or(note.name.as_str())
+                    .to_string();
+                self.db.insert_event(&Event {
+                    record_date: SystemTime::now()
+                        .duration_since(UNIX_EPOCH)
+                        .map(|d| d.as_secs())
+                        .unwrap_or(0),
+                    refered_date,
+                    description,
+                    date: None,
+                    session: None,
+                })
+            }
+        };
+        match res {
+            Ok(id) => {
+                self.report.created += 1;
+                let entity = EntityRef::new(note.kind, id);
+                if note.kind == EntityKind::Event {
+                    self.events.insert(note.name.to_lowercase(), entity);
+                }
+                Ok(Some(entity))
+            }
+            Err(e @ Error::NameTaken(..)) => {
+                self.conflict(note, e.to_string());
+                Ok(None)
+            }
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Applies the front matter of a note to its entity, attaches the images, and turns the
+    /// wikilinks of the description into links
+    fn fill(
+        &mut self,
+        note: &Note,
+        entity: EntityRef,
+        description: &str,
+        images: &[String],
+    ) -> Result<()> {
+        if let Some(e) = &note.front_error {
+            self.conflict(note, format!("The front matter can't be read: {e}"));
+        }
+        if get(&note.front, "secret").map_or(false, is_true) {
+            self.db.set_secret(entity, true)?;
+        }
+        let tags = get(&note.front, "tags")
+            .map(Yaml::items)
+            .unwrap_or_default();
+        // tags written as one value are separated by commas or spaces
+        for tag in tags.into_iter().flat_map(|t| t.split([',', ' '])) {
+            let tag = tag.trim_start_matches('#').trim();
+            if !tag.is_empty() {
+                self.db.add_tag(entity, tag)?;
+            }
+        }
+        if let Some(parent) = get(&note.front, "parent").and_then(Yaml::scalar) {
+            self.set_parent(note, entity, parent)?;
+        }
+        if let Some(number) = get(&note.front, "session").and_then(Yaml::scalar) {
+            self.set_session(note, entity, number)?;
+        }
+        if let Some(Yaml::List(relationships)) = get(&note.front, "relationships") {
+            for rel in relationships {
+                self.add_relationship(note, entity, rel)?;
+            }
+        }
+        for link in get(&note.front, "links")
+            .map(Yaml::items)
+            .unwrap_or_default()
+        {
+            match self.resolve(link)? {
+                Some(target) => self.connect(note, entity, target)?,
+                None => self.unresolved(note, link),
+            }
+        }
+
+        // the first image becomes the portrait
+        let mut attached: Vec<&str> = vec![];
+        for key in ["portrait", "images"] {
+            attached.extend(get(&note.front, key).map(Yaml::items).unwrap_or_default());
+        }
+        attached.extend(images.iter().map(|i| i.as_str()));
+        for reference in attached {
+            self.attach(note, entity, reference, false)?;
+        }
+        if entity.kind == EntityKind::Place {
+            if let Some(map) = get(&note.front, "map").and_then(Yaml::scalar) {
+                self.attach(note, entity, map, true)?;
+            }
+        }
+
+        for (key, value) in &note.front {
+            if key.eq_ignore_ascii_case("fields") {
+                if let Yaml::Map(fields) = value {
+                    for (key, value) in fields {
+                        self.set_field(note, entity, key, value)?;
+                    }
+                }
+            } else if !RESERVED_KEYS.contains(&key.to_lowercase().as_str()) {
+                self.set_field(note, entity, key, value)?;
+            }
+        }
+
+        wiki::sync_links(self.db, entity, description)?;
+        for link in parse_links(description) {
+            if self.db.resolve_name(&link.target)?.is_none() {
+                self.unresolved(note, &link.target);
+            }
+        }
+        Ok(())
+    }
+
+    /// The entity a wikilink or plain name in the front matter points to
+    fn resolve(&mut self, link: &str) -> Result<Option<EntityRef>> {
+        let name = link_name(link);
+        if let Some(event) = self.events.get(&name.to_lowercase()) {
+            return Ok(Some(*event));
+        }
+        self.db.resolve_name(&name)
+    }
+
+    /// Links `entity` to `target`, in whichever direction the two kinds can be linked
+    fn connect(&mut self, note: &Note, entity: EntityRef, target: EntityRef) -> Result<()> {
+        if mapping_table(entity.kind, target.kind).is_some() {
+            self.db.link(entity, target)
+        } else if mapping_table(target.kind, entity.kind).is_some() {
+            self.db.link(target, entity)
+        } else {
+            self.conflict(
+                note,
+                format!(
+                    "There is no way to link a {} to a {}",
+                    entity.kind, target.kind
+                ),
+            );
+            Ok(())
+        }
+    }
+
+    fn set_parent(&mut self, note: &Note, entity: EntityRef, parent: &str) -> Result<()> {
+        let name = link_name(parent);
+        let res = match entity.kind {
+            EntityKind::Place => match self.db.get_place_by_name(&name)? {
+                Some(p) => {
+                    let mut place = self.db.get_place(entity.id)?.unwrap();
+                    place.t.parent_place = Some(p.id);
+                    self.db.update_place(&place)
+                }
+                None => {
+                    self.unresolved(note, parent);
+                    Ok(())
+                }
+            },
+            EntityKind::Group => match self.db.get_group_by_name(&name)? {
+                Some(g) => {
+                    let mut group = self.db.get_group(entity.id)?.unwrap();
+                    group.t.parent_group = Some(g.id);
+                    self.db.update_group(&group)
+                }
+                None => {
+                    self.unresolved(note, parent);
+                    Ok(())
+                }
+            },
+            _ => Ok(()),
+        };
+        match res {
+            Err(e @ Error::ParentCycle(_)) => {
+                self.conflict(note, e.to_string());
+                Ok(())
+            }
+            res => res,
+        }
+    }
+
+    fn set_session(&mut self, note: &Note, entity: EntityRef, number: &str) -> Result<()> {
+        if entity.kind != EntityKind::Event {
+            return Ok(());
+        }
+        let session = match number.trim().parse::<i64>() {
+            Ok(number) => self
+                .db
+                .list_sessions()?
+                .into_iter()
+                .find(|s| s.t.number == number),
+            Err(_) => None,
+        };
+        match session {
+            Some(session) => self.db.set_event_session(entity.id, Some(session.id)),
+            None => {
+                self.conflict(note, format!("There is no session number {number}"));
+                Ok(())
+            }
+        }
+    }
+
+    fn add_relationship(&mut self, note: &Note, entity: EntityRef, rel: &Yaml) -> Result<()> {
+        let text = |key: &str| {
+            rel.get(key)
+                .and_then(Yaml::scalar)
+                .filter(|s| !s.is_empty())
+                .map(|s| s.to_string())
+        };
+        let Some(target) = text("target") else {
+            return Ok(());
+        };
+        if entity.kind != EntityKind::Subject {
+            self.conflict(note, "Only subjects can have relationships".to_string());
+            return Ok(());
+        }
+        let Some(other) = self.db.get_sub_by_name(&link_name(&target))? else {
+            self.unresolved(note, &target);
+            return Ok(());
+        };
+        self.db.add_relationship(&Relationship {
+            from: entity.id,
+            to: other.id,
+            label: text("label").unwrap_or_default(),
+            inverse_label: text("inverse"),
+            note: text("note").unwrap_or_default(),
+            valid_from: text("since"),
+            valid_to: text("until"),
+            secret: rel.get("secret").map_or(false, is_true),
+        })?;
+        Ok(())
+    }
+
+    fn set_field(
+        &mut self,
+        note: &Note,
+        entity: EntityRef,
+        name: &str,
+        value: &Yaml,
+    ) -> Result<()> {
+        let text = match value {
+            Yaml::Scalar(s) => s.trim().to_string(),
+            Yaml::List(_) => value.items().join(", "),
+            Yaml::Map(_) => {
+                self.conflict(note, format!("The property {name} has nested values"));
+                return Ok(());
+            }
+        };
+        if text.is_empty() {
+            return Ok(());
+        }
+        let value = if text.starts_with("[[") && text.ends_with("]]") {
+            match self.resolve(&text)? {
+                Some(target) if target.kind != EntityKind::Event => FieldValue::Reference(target),
+                _ => {
+                    self.unresolved(note, &text);
+                    return Ok(());
+                }
+            }
+        } else if let Some(n) = text.parse::<f64>().ok().filter(|n| n.is_finite()) {
+            FieldValue::Number(n)
+        } else if text.eq_ignore_ascii_case("true") || text.eq_ignore_ascii_case("false") {
+            FieldValue::Boolean(is_true(value))
+        } else {
+            FieldValue::Text(text)
+        };
+        self.db.set_field(
+            entity,
+            &Field {
+                name: name.to_string(),
+                value,
+            },
+        )?;
+        Ok(())
+    }
+
+    /// Attaches an image to the entity, or makes it the map of a place
+    fn attach(&mut self, note: &Note, entity: EntityRef, reference: &str, map: bool) -> Result<()> {
+        let Some(path) = self.image_path(note, reference) else {
+            self.unresolved(note, reference);
+            return Ok(());
+        };
+        let data = fs::read(&path)?;
+        let file_name = path
+            .file_name()
+            .map(|n| n.to_string_lossy().to_string())
+            .unwrap_or_default();
+        let res = if map {
+            self.db.set_place_map(entity.id, &data, &file_name)
+        } else {
+            self.db.attach_image(entity, &data, &file_name)
+        };
+        match res {
+            Ok(_) => self.report.images += 1,
+            Err(e @ Error::UnsupportedImage(_)) => self.conflict(note, e.to_string()),
+            Err(e) => return Err(e),
+        }
+        Ok(())
+    }
+
+    /// Finds an image next to the note, relative to the vault, or anywhere in the vault
+    fn image_path(&self, note: &Note, reference: &str) -> Option<PathBuf> {
+        let reference = link_target(reference);
+        let relative = Path::new(reference);
+        if relative
+            .components()
+            .all(|c| matches!(c, Component::Normal(_) | Component::CurDir))
+        {
+            for base in [note.file.parent()?, self.dir] {
+                let path = base.join(relative);
+                if path.is_file() {
+                    return Some(path);
+                }
+            }
+        }
+        let name = relative.file_name()?.to_str()?.to_lowercase();
+        self.image_files.get(&name).cloned()
+    }
+
+    fn unresolved(&mut self, note: &Note, target: &str) {
+        self.report
+            .unresolved
+            .push((note.path.clone(), target.to_string()));
+    }
+
+    fn conflict(&mut self, note: &Note, reason: String) {
+        self.report.conflicts.push((note.path.clone(), reason));
+    }
+}
+
+/// All files below `dir`, sorted, without hidden ones like Obsidian's `.obsidian` folder
+fn find_files(dir: &Path, files: &mut Vec<PathBuf>) -> Result<()> {
+    let mut entries = vec![];
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        if !entry.file_name().to_string_lossy().starts_with('.') {
+            entries.push(entry.path());
+        }
+    }
+    entries.sort();
+    for path in entries {
+        if path.is_dir() {
+            find_files(&path, files)?;
+        } else {
+            files.push(path);
+        }
+    }
+    Ok(())
+}
+
+fn read_note(vault: &Path, file: &Path) -> Result<Note> {
+    let text = fs::read_to_string(file)?.replace("\r\n", "\n");
+    let (front, body) = split_front_matter(&text);
+    let (front, front_error) = match front.map(parse_yaml) {
+        Some(Ok(front)) => (front, None),
+        Some(Err(e)) => (vec![], Some(e)),
+        None => (vec![], None),
+    };
+    let relative = file.strip_prefix(vault).unwrap_or(file);
+    let name = file
+        .file_stem()
+        .map(|s| s.to_string_lossy().trim().to_string())
+        .unwrap_or_default();
+    let kind = ["kind", "type"]
+        .into_iter()
+        .filter_map(|key| get(&front, key))
+        .flat_map(Yaml::items)
+        .find_map(kind_from_word)
+        .or_else(|| {
+            relative
+                .parent()?
+                .components()
+                .rev()
+                .find_map(|c| kind_from_word(c.as_os_str().to_str()?))
+        })
+        .unwrap_or(EntityKind::Subject);
+    Ok(Note {
+        path: relative
+            .components()
+            .map(|c| c.as_os_str().to_string_lossy())
+            .collect::<Vec<_>>()
+            .join("/"),
+        file: file.to_path_buf(),
+        name,
+        kind,
+        front,
+        front_error,
+        body: body.to_string(),
+    })
+}
+
+/// The kind a folder name or a `type` in the front matter stands for
+fn kind_from_word(word: &str) -> Option<EntityKind> {
+    Some(match search_key(word).trim() {
+        "subject" | "subjects" | "character" | "characters" | "npc" | "npcs" | "person"
+        | "people" | "creature" | "creatures" => EntityKind::Subject,
+        "place" | "places" | "location" | "locations" | "region" | "regions" => EntityKind::Place,
+        "group" | "groups" | "faction" | "factions" | "organization" | "organizations"
+        | "organisation" | "organisations" => EntityKind::Group,
+        "event" | "events" => EntityKind::Event,
+        _ => return None,
+    })
+}
+
+/// The description for the body of a note: `> [!secret]` callouts become secret blocks,
+/// wikilinks lose folders and headings, links to events become plain text, and embedded images
+/// are taken out. Returns the description and the references of the images.
+fn convert_body(body: &str, event_names: &HashSet<String>) -> (String, Vec<String>) {
+    let text = secret_blocks(body);
+    let (text, mut images) = take_markdown_images(&text);
+    let mut res = String::with_capacity(text.len());
+    let mut last = 0;
+    for link in parse_links(&text) {
+        let embed = text[..link.range.start].ends_with('!');
+        let start = if embed {
+            link.range.start - 1
+        } else {
+            link.range.start
+        };
+        res.push_str(&text[last..start]);
+        last = link.range.end;
+        if embed && is_image(&link.target) {
+            images.push(link.target.clone());
+            continue;
+        }
+        let name = note_name(&link.target);
+        match &link.label {
+            _ if event_names.contains(&name.to_lowercase()) => {
+                res.push_str(link.label.as_deref().unwrap_or(name.as_str()))
+            }
+            Some(label) if *label != name => res.push_str(&format!("[[{name}|{label}]]")),
+            _ => res.push_str(&format!("[[{name}]]")),
+        }
+    }
+    res.push_str(&text[last..]);
+    (res, images)
+}
+
+/// Turns `> [!secret]` callouts into secret blocks, the opposite of [`secret_callouts`]
+fn secret_blocks(text: &str) -> String {
+    let mut res = String::with_capacity(text.len());
+    let mut in_callout = false;
+    let mut in_code = false;
+    for line in text.split_inclusive('\n') {
+        if in_callout {
+            if let Some(inner) = line.trim_start().strip_prefix('>') {
+                res.push_str(inner.strip_prefix(' ').unwrap_or(inner));
+                continue;
+            }
+            in_callout = false;
+            res.push_str(":::\n");
+            // the blank line that ends the callout
+            if line.trim().is_empty() {
+                continue;
+            }
+        }
+        if line.trim_start().starts_with("```") {
+            in_code = !in_code;
+        } else if let Some(title) = callout_title(line).filter(|_| !in_code) {
+            in_callout = true;
+            res.push_str(":::secret\n");
+            if !title.is_empty() {
+                res.push_str(title);
+                res.push('\n');
+            }
+            continue;
+        }
+        res.push_str(line);
+    }
+    if in_callout {
+        if !res.ends_with('\n') {
+            res.push('\n');
+        }
+        res.push_str(":::\n");
+    }
+    res
+}
+
+/// The title of a line that starts a secret callout, like `> [!secret]- Her true name`
+fn callout_title(line: &str) -> Option<&str> {
+    let rest = line.trim().strip_prefix('>')?.trim_start();
+    if !rest.get(..9)?.eq_ignore_ascii_case("[!secret]") {
+        return None;
+    }
+    Some(rest[9..].trim_start_matches(['-', '+']).trim())
+}
+
+/// Takes images in markdown syntax, like `![alt](map.png)`, out of the text. Images from the
+/// web stay.
+fn take_markdown_images(text: &str) -> (String, Vec<String>) {
+    let mut res = String::with_capacity(text.len());
+    let mut images = vec![];
+    let mut rest = text;
+    while let Some(start) = rest.find("![") {
+        let after = &rest[start + 2..];
+        let image = after
+            .find("](")
+            .filter(|_| !after.starts_with('['))
+            .and_then(|mid| {
+                let len = after[mid + 2..].find(')')?;
+                let inner = after[mid + 2..mid + 2 + len].trim();
+                let path = match inner.strip_prefix('<') {
+                    Some(inner) => inner.split('>').next()?,
+                    None => inner.split_whitespace().next()?,
+                };
+                let path = wiki::percent_decode(path)?;
+                let end = start + 2 + mid + 2 + len + 1;
+                let single_line = !rest[start..end].contains('\n');
+                (single_line && !path.contains("://") && is_image(&path)).then_some((end, path))
+            });
+        match image {
+            Some((end, path)) => {
+                res.push_str(&rest[..start]);
+                images.push(path);
+                rest = &rest[end..];
+            }
+            None => {
+                res.push_str(&rest[..start + 2]);
+                rest = &rest[start + 2..];
+            }
+        }
+    }
+    res.push_str(rest);
+    (res, images)
+}
+
+fn is_image(name: &str) -> bool {
+    let name = name.to_lowercase();
+    [".png", ".jpg", ".jpeg", ".webp"]
+        .iter()
+        .any(|ext| name.ends_with(ext))
+}
+
+/// What a wikilink points to, without the brackets and the label. Other text is returned as is.
+fn link_target(link: &str) -> &str {
+    let link = link.trim();
+    match link.strip_prefix("[[").and_then(|l| l.strip_suffix("]]")) {
+        Some(inner) => inner.split('|').next().unwrap_or(inner).trim(),
+        None => link,
+    }
+}
+
+/// The name of the note a wikilink or name in the front matter points to
+fn link_name(link: &str) -> String {
+    note_name(link_target(link))
+}
+
+/// The name of the note a link target points to, without folders, heading and `.md`
+fn note_name(target: &str) -> String {
+    let target = target.split(['#', '^']).next().unwrap_or(target);
+    let target = target.rsplit('/').next().unwrap_or(target);
+    let target = target.strip_suffix(".md").unwrap_or(target);
+    target.trim().to_string()
+}
+
+/// The value of a key of the front matter, ignoring case
+fn get<'y>(front: &'y [(String, Yaml)], key: &str) -> Option<&'y Yaml> {
+    front
+        .iter()
+        .find(|(k, _)| k.eq_ignore_ascii_case(key))
+        .map(|(_, v)| v)
+}
+
+fn is_true(value: &Yaml) -> bool {
+    matches!(
+        value.scalar().map(|s| s.to_lowercase()).as_deref(),
+        Some("true" | "yes")
+    )
+}
+
+/// A value of the front matter. Numbers and booleans are kept as text, like the rest.
+#[derive(Clone, Debug, PartialEq)]
+enum Yaml {
+    Scalar(String),
+    List(Vec<Yaml>),
+    Map(Vec<(String, Yaml)>),
+}
+
+impl Yaml {
+    fn scalar(&self) -> Option<&str> {
+        match self {
+            Yaml::Scalar(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    /// The non-empty scalars of a list, or the scalar itself
+    fn items(&self) -> Vec<&str> {
+        match self {
+            Yaml::Scalar(s) if s.is_empty() => vec![],
+            Yaml::Scalar(s) => vec![s],
+            Yaml::List(items) => items
+                .iter()
+                .filter_map(Yaml::scalar)
+                .filter(|s| !s.is_empty())
+                .collect(),
+            Yaml::Map(_) => vec![],
+        }
+    }
+
+    fn get(&self, key: &str) -> Option<&Yaml> {
+        match self {
+            Yaml::Map(map) => get(map, key),
+            _ => None,
+        }
+    }
+}
+
+/// Splits a note into its front matter, without the `---` lines, and the rest
+fn split_front_matter(text: &str) -> (Option<&str>, &str) {
+    let text = text.strip_prefix('\u{feff}').unwrap_or(text);
+    let Some(rest) = text.strip_prefix("---\n") else {
+        return (None, text);
+    };
+    let mut offset = 0;
+    for line in rest.split_inclusive('\n') {
+        if matches!(line.trim_end(), "---" | "...") {
+            return (Some(&rest[..offset]), &rest[offset + line.len()..]);
+        }
+        offset += line.len();
+    }
+    (None, text)
+}
+
+/// Reads the keys of the front matter, or why it can't be read
+fn parse_yaml(text: &str) -> StdResult<Vec<(String, Yaml)>, String> {
+    let docs = YamlLoader::load_from_str(text).map_err(|e| e.to_string())?;
+    match docs.into_iter().next() {
+        None => Ok(vec![]),
+        Some(yaml_rust2::Yaml::Hash(hash)) => Ok(hash
+            .iter()
+            .filter_map(|(k, v)| Some((scalar_text(k)?, convert_yaml(v))))
+            .collect()),
+        Some(_) => Err("it is not a list of properties".into()),
+    }
+}
+
+fn convert_yaml(value: &yaml_rust2::Yaml) -> Yaml {
+    use yaml_rust2::Yaml as Y;
+    match value {
+        // an unquoted wikilink reads as a list in a list
+        Y::Array(outer) => match outer.as_slice() {
+            [Y::Array(inner)] if matches!(inner.as_slice(), [Y::String(_)]) => Yaml::Scalar(
+                format!("[[{}]]", scalar_text(&inner[0]).unwrap_or_default()),
+            ),
+            items => Yaml::List(items.iter().map(convert_yaml).collect()),
+        },
+        Y::Hash(hash) => Yaml::Map(
+            hash.iter()
+                .filter_map(|(k, v)| Some((scalar_text(k)?, convert_yaml(v))))
+                .collect(),
+        ),
+        value => Yaml::Scalar(scalar_text(value).unwrap_or_default()),
+    }
+}
+
+fn scalar_text(value: &yaml_rust2::Yaml) -> Option<String> {
+    use yaml_rust2::Yaml as Y;
+    Some(match value {
+        Y::String(s) | Y::Real(s) => s.clone(),
+        Y::Integer(i) => i.to_string(),
+        Y::Boolean(b) => b.to_string(),
+        Y::Null => String::new(),
+        _ => return None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::png;
+
+    #[test]
+    fn exports_notes_with_front_matter_and_wikilinks() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut db = Schema::create_new(dir.path().join("test.db")).unwrap();
+        let mira = db
+            .insert_subject(&Subject {
+                name: "Mira".into(),
+                description:
+                    "Runs [[the rusty anchor|the inn]].\n:::secret\nWorks for [[Vex]].\n:::\nBye."
+                        .into(),
+            })
+            .unwrap();
+        let mira = EntityRef::new(EntityKind::Subject, mira);
+        let vex = db
+            .insert_subject(&Subject {
+                name: "Vex".into(),
+                description: "The villain".into(),
+            })
+            .unwrap();
+        db.set_secret(EntityRef::new(EntityKind::Subject, vex), true)
+            .unwrap();
+        let port = db
+            .insert_place(&Place {
+                name: "Port: Nyanzaru".into(),
+                description: "".into(),
+                parent_place: None,
+            })
+            .unwrap();
+        db.insert_place(&Place {
+            name: "The Rusty Anchor".into(),
+            description: "".into(),
+            parent_place: Some(port),
+        })
+        .unwrap();
+        db.add_tag(mira, "old friend").unwrap();
+        db.set_field(
+            mira,
+            &Field {
+                name: "hp".into(),
+                value: FieldValue::Number(12.0),
+            },
+        )
+        .unwrap();
+        db.add_relationship(&Relationship {
+            from: mira.id,
+            to: vex,
+            label: "spy for".into(),
+            inverse_label: None,
+            note: "".into(),
+            valid_from: None,
+            valid_to: None,
+            secret: false,
+        })
+        .unwrap();
+        db.attach_image(mira, &png(4, 4), "mira.png").unwrap();
+        db.attach_image(
+            EntityRef::new(EntityKind::Subject, vex),
+            &png(4, 4),
+            "vex.png",
+        )
+        .unwrap();
+
+        let out = dir.path().join("players");
+        let summary = export_vault(&mut db, &out, Audience::Players).unwrap();
+        assert_eq!(
+            summary,
+            VaultSummary {
+                notes: 3,
+                images: 1
+            }
+        );
+        let note = fs::read_to_string(out.join("Subjects/Mira.md")).unwrap();
+        assert!(note.starts_with("---\nkind: subject\ntags:\n  - \"old-friend\"\n"));
+        assert!(note.contains("portrait: \"[[mira.png]]\"\n"));
+        assert!(note.contains("hp: 12\n"));
+        assert!(note.contains("---\nRuns [[The Rusty Anchor|the inn]].\nBye."));
+        assert!(!note.contains("Vex"));
+        assert!(!out.join("Subjects/Vex.md").exists());
+        assert!(out.join("Attachments/mira.png").exists());
+        let anchor = fs::read_to_string(out.join("Places/The Rusty Anchor.md")).unwrap();
+        assert!(anchor.contains("parent: \"[[Port- Nyanzaru|Port: Nyanzaru]]\"\n"));
+        assert!(out.join("Places/Port- Nyanzaru.md").exists());
+
+        let out = dir.path().join("gm");
+        let summary = export_vault(&mut db, &out, Audience::GameMaster).unwrap();
+        assert_eq!(summary.notes, 4);
+        let note = fs::read_to_string(out.join("Subjects/Mira.md")).unwrap();
+        assert!(note.contains("relationships:\n  - label: \"spy for\"\n    target: \"[[Vex]]\"\n"));
+        assert!(note.contains("> [!secret]\n> Works for [[Vex]].\n\nBye."));
+        let vex = fs::read_to_string(out.join("Subjects/Vex.md")).unwrap();
+        assert!(vex.starts_with("---\nkind: subject\nsecret: true\n"));
+        assert!(out.join("Attachments/vex.png").exists());
+
+        // exporting for the players over it leaves nothing of the game master's export
+        export_vault(&mut db, &out, Audience::Players).unwrap();
+        assert!(!out.join("Subjects/Vex.md").exists());
+        assert!(!out.join("Attachments/vex.png").exists());
+        let note = fs::read_to_string(out.join("Subjects/Mira.md")).unwrap();
+        assert!(!note.contains("Vex"));
+    }
+
+    #[test]
+    fn file_names_and_yaml() {
+        assert_eq!(file_stem("Who? [Me]/You."), "Who- -Me--You");
+        assert_eq!(file_stem(" ..."), "");
+        assert_eq!(yaml_key("hit points"), "hit points");
+        assert_eq!(yaml_key("a: b"), "\"a: b\"");
+        assert_eq!(yaml_string("say \"hi\"\n"), "\"say \\\"hi\\\"\\n\"");
+        assert_eq!(
+            secret_callouts("a\n:::secret\nb\n\nc\n:::\nd\n"),
+            "a\n> [!secret]\n> b\n>\n> c\n\nd\n"
+        );
+    }
+
+    fn write(dir: &Path, path: &str, text: &str) {
+        let path = dir.join(path);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, text).unwrap();
+    }
+
+    #[test]
+    fn imports_notes_links_and_images() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut db = Schema::create_new(dir.path().join("test.db")).unwrap();
+        db.insert_subject(&Subject {
+            name: "Olaf".into(),
+            description: "".into(),
+        })
+        .unwrap();
+        let vault = dir.path().join("vault");
+        write(
+            &vault,
+            "Characters/Mira.md",
+            "---\ntags: [innkeeper, \"#friend\"]\nhp: 12\nhome: \"[[The Rusty Anchor]]\"\n\
+             relationships:\n  - label: rival of\n    target: \"[[Vex]]\"\n---\n\
+             Runs [[Places/The Rusty Anchor#Bar|the inn]].\n> [!secret] Truth\n> A dragon.\n\n\
+             See [[Nobody]].\n![[mira.png]]\nMet at [[The Feast]].",
+        );
+        write(
+            &vault,
+            "Places/The Rusty Anchor.md",
+            "---\nparent: \"[[Port]]\"\n---\nA tavern.\n",
+        );
+        write(&vault, "Places/Port.md", "A port.");
+        write(
+            &vault,
+            "Vex.md",
+            "---\ntype: character\nsecret: true\n---\n",
+        );
+        write(&vault, "Events/The Feast.md", "---\ndate: 12 March\n---\n");
+        write(&vault, "Notes/Guild.md", "---\nkind: faction\n---\n");
+        write(&vault, "Olaf.md", "Another Olaf");
+        write(&vault, "Broken.md", "---\ntags: [a\n---\nStill here.");
+        write(&vault, ".obsidian/Hidden.md", "");
+        fs::create_dir_all(vault.join("img")).unwrap();
+        fs::write(vault.join("img/mira.png"), png(4, 4)).unwrap();
+
+        let report = import_vault(&mut db, &vault).unwrap();
+        assert_eq!(report.created, 7);
+        assert_eq!(report.images, 1);
+        assert_eq!(
+            report.unresolved,
+            vec![("Characters/Mira.md".to_string(), "Nobody".to_string())]
+        );
+        assert_eq!(report.conflicts.len(), 2);
+        assert_eq!(report.conflicts[0].0, "Olaf.md");
+        assert_eq!(report.conflicts[1].0, "Broken.md");
+        let broken = db.get_sub_by_name("Broken").unwrap().unwrap();
+        assert_eq!(broken.t.description, "Still here.");
+
+        let mira = db.get_sub_by_name("Mira").unwrap().unwrap();
+        assert_eq!(
+            mira.t.description,
+            "Runs [[The Rusty Anchor|the inn]].\n:::secret\nTruth\nA dragon.\n:::\n\
+             See [[Nobody]].\n\nMet at The Feast."
+        );
+        let mira = EntityRef::new(EntityKind::Subject, mira.id);
+        let anchor = db.get_place_by_name("The Rusty Anchor").unwrap().unwrap();
+        let port = db.get_place_by_name("Port").unwrap().unwrap();
+        assert_eq!(anchor.t.parent_place, Some(port.id));
+        let anchor = EntityRef::new(EntityKind::Place, anchor.id);
+        assert!(db.is_linked(mira, anchor).unwrap());
+
+        let tags: Vec<_> = db
+            .tags_of(mira)
+            .unwrap()
+            .into_iter()
+            .map(|t| t.label)
+            .collect();
+        assert_eq!(tags, vec!["friend", "innkeeper"]);
+        let fields: Vec<_> = db
+            .fields_of(mira)
+            .unwrap()
+            .into_iter()
+            .map(|f| (f.t.name, f.t.value))
+            .collect();
+        assert!(fields.contains(&("hp".to_string(), FieldValue::Number(12.0))));
+        assert!(fields.contains(&("home".to_string(), FieldValue::Reference(anchor))));
+        assert_eq!(db.attachments_of(mira).unwrap().len(), 1);
+
+        let vex = db.get_sub_by_name("Vex").unwrap().unwrap();
+        assert!(db
+            .is_secret(EntityRef::new(EntityKind::Subject, vex.id))
+            .unwrap());
+        let rels = db.relationships_of(mira.id).unwrap();
+        assert_eq!(rels.len(), 1);
+        assert_eq!(rels[0].rel.t.label, "rival of");
+        assert_eq!(rels[0].other_id, vex.id);
+        assert!(db.get_group_by_name("Guild").unwrap().is_some());
+        let events = db.list_events().unwrap();
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].t.refered_date, "12 March");
+    }
+
+    #[test]
+    fn imports_its_own_export() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut db = Schema::create_new(dir.path().join("a.db")).unwrap();
+        let description = "Lives in [[Port: Nyanzaru|the port]].\n:::secret\nA spy.\n:::\nBye.\n";
+        let mira = db
+            .insert_subject(&Subject {
+                name: "Mira".into(),
+                description: description.into(),
+            })
+            .unwrap();
+        db.insert_place(&Place {
+            name: "Port: Nyanzaru".into(),
+            description: "".into(),
+            parent_place: None,
+        })
+        .unwrap();
+        db.add_tag(EntityRef::new(EntityKind::Subject, mira), "friend")
+            .unwrap();
+        let vault = dir.path().join("vault");
+        export_vault(&mut db, &vault, Audience::GameMaster).unwrap();
+
+        let mut copy = Schema::create_new(dir.path().join("b.db")).unwrap();
+        let report = import_vault(&mut copy, &vault).unwrap();
+        assert_eq!(report.created, 2);
+        assert!(report.unresolved.is_empty());
+        assert!(report.conflicts.is_empty());
+        let mira = copy.get_sub_by_name("Mira").unwrap().unwrap();
+        // the colon can't be part of a file name
+        assert_eq!(
+            mira.t.description,
+            "Lives in [[Port- Nyanzaru|the port]].\n:::secret\nA spy.\n:::\nBye.\n"
+        );
+        let tags = copy
+            .tags_of(EntityRef::new(EntityKind::Subject, mira.id))
+            .unwrap();
+        assert_eq!(tags.len(), 1);
+    }
+
+    #[test]
+    fn front_matter() {
+        let text = "---\nkind: place\ntags:\n- a\n- 'b c'\nlist: [x, \"y, z\"]\n\
+                    rels:\n  - label: \"friend of\"\n    target: [[Mira]]\n\
+                    note: |\n  two\n  lines\n# comment\nempty:\n---\nBody";
+        let (front, body) = split_front_matter(text);
+        assert_eq!(body, "Body");
+        let front = parse_yaml(front.unwrap()).unwrap();
+        let scalar = |s: &str| Yaml::Scalar(s.into());
+        assert_eq!(
+            front,
+            vec![
+                ("kind".into(), scalar("place")),
+                ("tags".into(), Yaml::List(vec![scalar("a"), scalar("b c")])),
+                ("list".into(), Yaml::List(vec![scalar("x"), scalar("y, z")])),
+                (
+                    "rels".into(),
+                    Yaml::List(vec![Yaml::Map(vec![
+                        ("label".into(), scalar("friend of")),
+                        ("target".into(), scalar("[[Mira]]")),
+                    ])])
+                ),
+                ("note".into(), scalar("two\nlines\n")),
+                ("empty".into(), scalar("")),
+            ]
+        );
+        assert_eq!(
+            parse_yaml("hp: 12\nsecret: true").unwrap(),
+            vec![
+                ("hp".into(), scalar("12")),
+                ("secret".into(), scalar("true"))
+            ]
+        );
+        assert!(parse_yaml("tags: [a").is_err());
+        assert!(parse_yaml("- a\n- b").is_err());
+        assert_eq!(
+            split_front_matter("No front matter"),
+            (None, "No front matter")
+        );
+        assert_eq!(link_name("[[Places/Port#Docks|the docks]]"), "Port");
+        assert_eq!(
+            take_markdown_images("a ![map](img/big%20map.png) b ![x](https://x.png)"),
+            (
+                "a  b ![x](https://x.png)".to_string(),
+                vec!["img/big map.png".to_string()]
+            )
+        );
+    }
+}
@@ -0,0 +1,418 @@
+//! Wiki style links inside descriptions. `[[Name]]` links to the entity called `Name`,
+//! `[[Name|label]]` does the same, but displays `label`.
+//!
+//! Before the markdown is rendered, links are turned into regular markdown links with a
+//! `wiki:` url, e.g. `wiki:subject/12`, or `wiki:missing/Some%20Name` if there is no entity with
+//! that name. [`LINK_HANDLER_JS`] intercepts clicks on those links, and hands the url to the
+//! [`crate::components::Markdown`] component, which does the navigation.
+//!
+//! Parts of a description that only the game master may see are put between a `:::secret` and
+//! a `:::` line. In the player view they are removed before rendering, see [`split_secrets`].
+use std::ops::Range;
+
+use comrak::{markdown_to_html, ComrakOptions};
+
+use crate::schema::{
+    v1::{Audience, EntityKind, EntityRef},
+    Result,
+};
+use crate::Schema;
+
+/// A `[[...]]` link in a text
+#[derive(Clone, Debug, PartialEq)]
+pub struct WikiLink {
+    pub target: String,
+    /// The text that is displayed, if it differs from the target
+    pub label: Option<String>,
+    /// byte range of the whole link, including the brackets
+    pub range: Range<usize>,
+}
+
+impl WikiLink {
+    pub fn display(&self) -> &str {
+        self.label.as_deref().unwrap_or(&self.target)
+    }
+}
+
+/// Where a clicked link wants to go
+#[derive(Clone, Debug, PartialEq)]
+pub enum LinkTarget {
+    Entity(EntityRef),
+    Missing(String),
+}
+
+/// Finds all wiki links in `text`, skipping fenced code blocks
+pub fn parse_links(text: &str) -> Vec<WikiLink> {
+    let mut res = vec![];
+    let mut offset = 0;
+    let mut in_code = false;
+    for line in text.split_inclusive('\n') {
+        if line.trim_start().starts_with("```") {
+            in_code = !in_code;
+        } else if !in_code {
+            res.extend(parse_line(line, offset));
+        }
+        offset += line.len();
+    }
+    res
+}
+
+fn parse_line(line: &str, offset: usize) -> Vec<WikiLink> {
+    let mut res = vec![];
+    let mut pos = 0;
+    while let Some(start) = line[pos..].find("[[").map(|i| i + pos) {
+        let Some(end) = line[start + 2..].find("]]").map(|i| i + start + 2) else {
+            break;
+        };
+        let inner = &line[start + 2..end];
+        // a nested opening bracket means the first [[ wasn't a link
+        if let Some(nested) = inner.rfind("[[") {
+            pos = start + 2 + nested;
+            continue;
+        }
+        let (target, label) = match inner.split_once('|') {
+            Some((t, l)) => (t.trim(), Some(l.trim().to_string())),
+            None => (inner.trim(), None),
+        };
+        if !target.is_empty() {
+            res.push(WikiLink {
+                target: target.to_string(),
+                label: label.filter(|l| !l.is_empty()),
+                range: offset + start..offset + end + 2,
+            });
+        }
+        pos = end + 2;
+    }
+    res
+}
+
+/// A part of a text that is either entirely inside or entirely outside of secret blocks
+#[derive(Clone, Debug, PartialEq)]
+pub struct Section<'a> {
+    pub text: &'a str,
+    pub secret: bool,
+}
+
+/// Splits `text` at the fences of secret blocks. A block starts with a line that only contains
+/// `:::secret`, and ends with a line that only contains `:::`. Other `:::` blocks may be nested
+/// inside, a block that is never closed runs until the end of the text. The fence lines are
+/// not part of any section, and fences in code blocks are ignored.
+pub fn split_secrets(text: &str) -> Vec<Section> {
+    let mut res = vec![];
+    let mut push = |range: Range<usize>, secret: bool| {
+        if !range.is_empty() {
+            res.push(Section {
+                text: &text[range],
+                secret,
+            });
+        }
+    };
+    let mut start = 0;
+    let mut offset = 0;
+    let mut depth = 0;
+    let mut in_code = false;
+    for line in text.split_inclusive('\n') {
+        let end = offset + line.len();
+        let fence = line.trim().strip_prefix(":::").map(str::trim);
+        if line.trim_start().starts_with("```") {
+            in_code = !in_code;
+        } else if in_code {
+            // fences in code blocks are just text
+        } else if depth == 0 && fence == Some("secret") {
+            push(start..offset, false);
+            start = end;
+            depth = 1;
+        } else if depth > 0 && fence == Some("") {
+            depth -= 1;
+            if depth == 0 {
+                push(start..offset, true);
+                start = end;
+            }
+        } else if depth > 0 && fence.is_some() {
+            depth += 1;
+        }
+        offset = end;
+    }
+    push(start..text.len(), depth > 0);
+    res
+}
+
+/// `text` without its secret blocks
+pub fn strip_secrets(text: &str) -> String {
+    split_secrets(text)
+        .into_iter()
+        .filter(|s| !s.secret)
+        .map(|s| s.text)
+        .collect()
+}
+
+/// Renders a description to html, with wiki links resolved against the database. Players get
+/// neither the secret blocks nor links to secret entities, the game master sees the secret
+/// blocks marked as such.
+pub fn render_markdown(db: &mut Schema, text: &str, audience: Audience) -> Result<String> {
+    render_markdown_with(db, text, audience, &mut |target| match target {
+        LinkTarget::Entity(entity) => Some(format!("wiki:{}/{}", url_kind(entity.kind), entity.id)),
+        // players can't create missing entities
+        LinkTarget::Missing(name) if audience == Audience::GameMaster => {
+            Some(format!("wiki:missing/{}", percent_encode(name)))
+        }
+        LinkTarget::Missing(_) => None,
+    })
+}
+
+/// Like [`render_markdown`], but `url` decides where links point to. Links it returns None
+/// for are shown as plain text, and so are links to entities `audience` may not see.
+pub fn render_markdown_with(
+    db: &mut Schema,
+    text: &str,
+    audience: Audience,
+    url: &mut dyn FnMut(&LinkTarget) -> Option<String>,
+) -> Result<String> {
+    if audience == Audience::Players {
+        return render_section(db, &strip_secrets(text), audience, url);
+    }
+    let mut html = String::new();
+    for section in split_secrets(text) {
+        let rendered = render_section(db, section.text, audience, url)?;
+        if section.secret {
+            html.push_str(SECRET_BLOCK_START);
+            html.push_str(&rendered);
+            html.push_str("</div>");
+        } else {
+            html.push_str(&rendered);
+        }
+    }
+    Ok(html)
+}
+
+/// Wraps secret blocks in the game master's view. Raw html is not allowed in descriptions, so
+/// this can't be written by hand.
+const SECRET_BLOCK_START: &str = "<div class=\"secret\" title=\"Hidden in the player view\" \
+    style=\"border-left: 4px solid #B00020; padding-left: 0.8em; background-color: #FBE9EC;\">\
+    <p><strong>Secret</strong></p>";
+
+fn render_section(
+    db: &mut Schema,
+    text: &str,
+    audience: Audience,
+    url: &mut dyn FnMut(&LinkTarget) -> Option<String>,
+) -> Result<String> {
+    let mut md = String::with_capacity(text.len());
+    let mut last = 0;
+    for link in parse_links(text) {
+        md.push_str(&text[last..link.range.start]);
+        let display = escape_markdown(link.display());
+        let target = match db.resolve_name(&link.target)? {
+            Some(entity) if !db.is_visible(entity, audience)? => None,
+            Some(entity) => Some(LinkTarget::Entity(entity)),
+            None => Some(LinkTarget::Missing(link.target.clone())),
+        };
+        let href = match &target {
+            Some(target) => url(target),
+            None => None,
+        };
+        match href {
+            Some(href) => md.push_str(&format!("[{display}]({href})")),
+            None => md.push_str(&display),
+        }
+        last = link.range.end;
+    }
+    md.push_str(&text[last..]);
+    Ok(markdown_to_html(&md, &ComrakOptions::default()))
+}
+
+/// Stores the entities the wiki links in `text` point to as links of `entity`, see
+/// [`Schema::sync_wiki_links`]. Links that only occur in secret blocks become secret links.
+pub fn sync_links(db: &mut Schema, entity: EntityRef, text: &str) -> Result<()> {
+    let mut targets: Vec<(EntityRef, bool)> = vec![];
+    for section in split_secrets(text) {
+        for link in parse_links(section.text) {
+            let Some(target) = db.resolve_name(&link.target)? else {
+                continue;
+            };
+            match targets.iter_mut().find(|(t, _)| *t == target) {
+                Some((_, secret)) => *secret &= section.secret,
+                None => targets.push((target, section.secret)),
+            }
+        }
+    }
+    db.sync_wiki_links(entity, &targets)
+}
+
+/// Parses the part of a wiki url after `wiki:`
+pub fn parse_url(url: &str) -> Option<LinkTarget> {
+    let (kind, rest) = url.split_once('/')?;
+    if kind == "missing" {
+        return Some(LinkTarget::Missing(percent_decode(rest)?));
+    }
+    let kind = EntityKind::ALL.into_iter().find(|k| url_kind(*k) == kind)?;
+    Some(LinkTarget::Entity(EntityRef::new(kind, rest.parse().ok()?)))
+}
+
+fn url_kind(kind: EntityKind) -> &'static str {
+    match kind {
+        EntityKind::Subject => "subject",
+        EntityKind::Place => "place",
+        EntityKind::Group => "group",
+        EntityKind::Event => "event",
+        EntityKind::Tag => "tag",
+    }
+}
+
+fn escape_markdown(s: &str) -> String {
+    let mut res = String::with_capacity(s.len());
+    for c in s.chars() {
+        if matches!(c, '\\' | '[' | ']' | '*' | '_' | '`' | '<' | '>') {
+            res.push('\\');
+        }
+        res.push(c);
+    }
+    res
+}
+
+fn percent_encode(s: &str) -> String {
+    let mut res = String::with_capacity(s.len());
+    for b in s.bytes() {
+        if b.is_ascii_alphanumeric() || matches!(b, b'-' | b'.' | b'~') {
+            res.push(b as char);
+        } else {
+            res.push_str(&format!("%{b:02X}"));
+        }
+    }
+    res
+}
+
+pub(crate) fn percent_decode(s: &str) -> Option<String> {
+    let mut bytes = vec![];
+    let mut iter = s.bytes();
+    while let Some(b) = iter.next() {
+        if b == b'%' {
+            let hex = [iter.next()?, iter.next()?];
+            bytes.push(u8::from_str_radix(std::str::from_utf8(&hex).ok()?, 16).ok()?);
+        } else {
+            bytes.push(b);
+        }
+    }
+    String::from_utf8(bytes).ok()
+}
+
+/// Installed once in the app. Clicks on wiki links are not followed by the webview, instead the
+/// url is written into the hidden input of the surrounding [`crate::components::Markdown`]
+/// component, which triggers its input handler.
+pub const LINK_HANDLER_JS: &str = r#"
+if (!window.campmanWikiLinks) {
+    window.campmanWikiLinks = true;
+    document.addEventListener('click', function (e) {
+        const a = e.target.closest('a[href^="wiki:"]');
+        if (!a) return;
+        e.preventDefault();
+        const root = a.closest('.wiki-root');
+        if (!root) return;
+        const input = root.querySelector('input.wiki-nav');
+        input.value = a.getAttribute('href').slice(5);
+        input.dispatchEvent(new Event('input', { bubbles: true }));
+    }, true);
+}
+"#;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_links_with_and_without_label() {
+        let links = parse_links("See [[Mira]] and [[The Rusty Anchor|the tavern]].");
+        assert_eq!(links.len(), 2);
+        assert_eq!(links[0].target, "Mira");
+        assert_eq!(links[0].label, None);
+        assert_eq!(links[1].target, "The Rusty Anchor");
+        assert_eq!(links[1].display(), "the tavern");
+        assert_eq!(
+            &"See [[Mira]] and [[The Rusty Anchor|the tavern]]."[links[1].range.clone()],
+            "[[The Rusty Anchor|the tavern]]"
+        );
+    }
+
+    #[test]
+    fn skips_code_blocks_and_broken_links() {
+        let text = "```\n[[Not a link]]\n```\n[[ [[Mira]] [[]] [[open";
+        let links = parse_links(text);
+        assert_eq!(links.len(), 1);
+        assert_eq!(links[0].target, "Mira");
+    }
+
+    #[test]
+    fn urls_round_trip() {
+        let url = format!("missing/{}", percent_encode("Éowyn & co/x"));
+        assert_eq!(
+            parse_url(&url),
+            Some(LinkTarget::Missing("Éowyn & co/x".into()))
+        );
+        assert_eq!(
+            parse_url("place/3"),
+            Some(LinkTarget::Entity(EntityRef::new(EntityKind::Place, 3)))
+        );
+        assert_eq!(parse_url("nonsense"), None);
+    }
+
+    #[test]
+    fn links_made_by_hand_outlive_wiki_links() {
+        use crate::schema::v1::{Place, Subject};
+
+        let dir = tempfile::tempdir().unwrap();
+        let mut db = Schema::create_new(dir.path().join("test.db")).unwrap();
+        let mira = db
+            .insert_subject(&Subject {
+                name: "Mira".into(),
+                description: "".into(),
+            })
+            .unwrap();
+        let mira = EntityRef::new(EntityKind::Subject, mira);
+        let anchor = db
+            .insert_place(&Place {
+                name: "The Rusty Anchor".into(),
+                description: "".into(),
+                parent_place: None,
+            })
+            .unwrap();
+        let anchor = EntityRef::new(EntityKind::Place, anchor);
+
+        sync_links(&mut db, mira, "Runs [[The Rusty Anchor]].").unwrap();
+        assert!(db.is_linked(mira, anchor).unwrap());
+        db.link(mira, anchor).unwrap();
+        sync_links(&mut db, mira, "Runs the inn.").unwrap();
+        assert!(db.is_linked(mira, anchor).unwrap());
+    }
+
+    #[test]
+    fn splits_secret_blocks() {
+        let text = "Open\n:::secret\nHidden\n:::note\nStill hidden\n:::\n:::\nOpen again\n\
+                    ```\n:::secret\n```\n:::secret\nUnclosed";
+        let sections = split_secrets(text);
+        assert_eq!(
+            sections,
+            vec![
+                Section {
+                    text: "Open\n",
+                    secret: false
+                },
+                Section {
+                    text: "Hidden\n:::note\nStill hidden\n:::\n",
+                    secret: true
+                },
+                Section {
+                    text: "Open again\n```\n:::secret\n```\n",
+                    secret: false
+                },
+                Section {
+                    text: "Unclosed",
+                    secret: true
+                },
+            ]
+        );
+        assert_eq!(
+            strip_secrets(text),
+            "Open\nOpen again\n```\n:::secret\n```\n"
+        );
+    }
+}